@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Stdout;
@@ -9,10 +10,11 @@ use std::time::{Duration, Instant};
 use anyhow::{Context, Result};
 use crossbeam_channel::{unbounded, Receiver};
 use crossterm::event::{
-    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEventKind,
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind,
 };
 use crossterm::terminal::{Clear as TermClear, ClearType};
 use ratatui::backend::CrosstermBackend;
+use ratatui::layout::Rect;
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{Paragraph, Widget, Wrap};
@@ -20,23 +22,48 @@ use ratatui::Terminal;
 use serde::{Deserialize, Serialize};
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use crate::background::Background;
+use crate::clipboard;
+use crate::config::{
+    slugify, ClipboardBackend, Config, ContextBudget, ContextConfig, MemoryScope, RetentionPolicy,
+};
+use crate::editor;
+use crate::providers::RunEvent;
 use crate::{
     cleaned_assistant_text, cleaned_assistant_text_for_model, default_commands,
     detect_available_providers, execute_line, extract_agent_name, high_risk_check,
-    input_cursor_position, kill_pid, memory::MemoryStore, ordered_providers, provider_from_name,
-    providers_label, resolve_dispatch_providers, truncate, DispatchTarget, WORKING_PLACEHOLDER,
+    input_cursor_position, kill_pid,
+    memory::{
+        estimate_tokens, format_bytes, format_timestamp, unix_now, with_pins, CompactionBatch,
+        ContextSource, MemoryStore, RetentionReport, SearchFilter, TransferArgs, Visibility,
+    },
+    ordered_providers, provider_from_name, providers_label, resolve_dispatch_providers, truncate,
+    DispatchTarget, WORKING_PLACEHOLDER,
 };
 
 const COLLAPSED_PASTE_CHAR_THRESHOLD: usize = 800;
 const COLLAPSED_PASTE_LINE_THRESHOLD: usize = 12;
 const MEM_SHOW_DEFAULT_LIMIT: usize = 20;
 const MEM_SHOW_MAX_LIMIT: usize = 200;
+/// How often retention limits are re-applied while dagent stays open.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+/// How often `~/.dagent/themes` is checked for edited theme files.
+const THEME_POLL_INTERVAL: Duration = Duration::from_secs(1);
 const MEM_FIND_DEFAULT_LIMIT: usize = 12;
 const MEM_PRUNE_DEFAULT_KEEP: usize = 200;
+const MEM_COMPACT_DEFAULT_KEEP: usize = 20;
+const MEM_FIND_USAGE: &str =
+    "usage: /mem find <query> [--all] [--events] [--agent claude|codex] [--role user|assistant] [--since 7d]";
 const MAX_ACTIVITY_LOG_LINES: usize = 7;
 const STARTUP_BANNER_PREFIX: &str = "__startup_banner__:";
 const ASSISTANT_DIVIDER: char = '│';
 
+#[path = "find.rs"]
+mod find;
+#[path = "highlight.rs"]
+mod highlight;
+#[path = "themes.rs"]
+mod themes;
 #[path = "ui.rs"]
 pub(crate) mod ui;
 
@@ -74,9 +101,21 @@ pub(crate) enum ThemePreset {
     Solarized,
     Aurora,
     Ember,
+    Daylight,
 }
 
 impl ThemePreset {
+    fn all() -> [ThemePreset; 6] {
+        [
+            ThemePreset::Fjord,
+            ThemePreset::Graphite,
+            ThemePreset::Solarized,
+            ThemePreset::Aurora,
+            ThemePreset::Ember,
+            ThemePreset::Daylight,
+        ]
+    }
+
     pub(crate) fn as_str(&self) -> &'static str {
         match self {
             ThemePreset::Fjord => "fjord",
@@ -84,6 +123,7 @@ impl ThemePreset {
             ThemePreset::Solarized => "solarized",
             ThemePreset::Aurora => "aurora",
             ThemePreset::Ember => "ember",
+            ThemePreset::Daylight => "daylight",
         }
     }
 
@@ -94,6 +134,7 @@ impl ThemePreset {
             "solarized" | "sand" | "amber" => Some(ThemePreset::Solarized),
             "aurora" | "mint" | "teal" => Some(ThemePreset::Aurora),
             "ember" | "warm" | "copper" => Some(ThemePreset::Ember),
+            "daylight" | "light" | "day" => Some(ThemePreset::Daylight),
             _ => None,
         }
     }
@@ -132,6 +173,15 @@ impl ThemePreset {
                 inline_code_fg: Color::Rgb(190, 190, 190),
                 inline_code_bg: Color::Rgb(20, 20, 20),
                 bullet: Color::Rgb(150, 150, 150),
+                link: Color::Rgb(120, 170, 230),
+                syntax_keyword: Color::Rgb(215, 160, 100),
+                syntax_string: Color::Rgb(160, 190, 130),
+                syntax_number: Color::Rgb(200, 150, 200),
+                syntax_comment: Color::Rgb(110, 110, 110),
+                syntax_type: Color::Rgb(130, 170, 220),
+                diff_added: Color::Rgb(120, 200, 120),
+                diff_removed: Color::Rgb(225, 110, 110),
+                diff_hunk: Color::Rgb(120, 170, 210),
             },
             ThemePreset::Graphite => ThemePalette {
                 // 深海蓝主题 - 专业科技感
@@ -165,6 +215,15 @@ impl ThemePreset {
                 inline_code_fg: Color::Rgb(160, 180, 200),
                 inline_code_bg: Color::Rgb(20, 30, 40),
                 bullet: Color::Rgb(110, 130, 150),
+                link: Color::Rgb(120, 190, 240),
+                syntax_keyword: Color::Rgb(110, 170, 230),
+                syntax_string: Color::Rgb(150, 200, 170),
+                syntax_number: Color::Rgb(200, 170, 120),
+                syntax_comment: Color::Rgb(80, 100, 120),
+                syntax_type: Color::Rgb(130, 200, 220),
+                diff_added: Color::Rgb(110, 190, 130),
+                diff_removed: Color::Rgb(220, 110, 110),
+                diff_hunk: Color::Rgb(100, 150, 200),
             },
             ThemePreset::Solarized => ThemePalette {
                 // 森林绿主题 - 自然护眼感
@@ -198,6 +257,15 @@ impl ThemePreset {
                 inline_code_fg: Color::Rgb(165, 200, 165),
                 inline_code_bg: Color::Rgb(20, 35, 20),
                 bullet: Color::Rgb(130, 170, 130),
+                link: Color::Rgb(110, 190, 200),
+                syntax_keyword: Color::Rgb(200, 180, 90),
+                syntax_string: Color::Rgb(150, 210, 150),
+                syntax_number: Color::Rgb(210, 150, 110),
+                syntax_comment: Color::Rgb(95, 120, 95),
+                syntax_type: Color::Rgb(120, 190, 170),
+                diff_added: Color::Rgb(120, 200, 120),
+                diff_removed: Color::Rgb(220, 110, 100),
+                diff_hunk: Color::Rgb(150, 180, 120),
             },
             ThemePreset::Aurora => ThemePalette {
                 // 紫罗兰主题 - 创意艺术感
@@ -231,6 +299,15 @@ impl ThemePreset {
                 inline_code_fg: Color::Rgb(210, 185, 230),
                 inline_code_bg: Color::Rgb(30, 20, 40),
                 bullet: Color::Rgb(170, 140, 190),
+                link: Color::Rgb(160, 190, 250),
+                syntax_keyword: Color::Rgb(200, 150, 240),
+                syntax_string: Color::Rgb(160, 220, 180),
+                syntax_number: Color::Rgb(240, 170, 140),
+                syntax_comment: Color::Rgb(120, 100, 140),
+                syntax_type: Color::Rgb(150, 190, 240),
+                diff_added: Color::Rgb(130, 210, 150),
+                diff_removed: Color::Rgb(235, 120, 140),
+                diff_hunk: Color::Rgb(190, 160, 230),
             },
             ThemePreset::Ember => ThemePalette {
                 // 碳纤维主题 - 现代工业感
@@ -264,6 +341,57 @@ impl ThemePreset {
                 inline_code_fg: Color::Rgb(200, 200, 200),
                 inline_code_bg: Color::Rgb(20, 20, 20),
                 bullet: Color::Rgb(160, 160, 160),
+                link: Color::Rgb(230, 170, 110),
+                syntax_keyword: Color::Rgb(235, 150, 80),
+                syntax_string: Color::Rgb(200, 190, 120),
+                syntax_number: Color::Rgb(230, 120, 90),
+                syntax_comment: Color::Rgb(120, 110, 100),
+                syntax_type: Color::Rgb(220, 180, 120),
+                diff_added: Color::Rgb(140, 200, 120),
+                diff_removed: Color::Rgb(230, 100, 80),
+                diff_hunk: Color::Rgb(210, 160, 110),
+            },
+            ThemePreset::Daylight => ThemePalette {
+                // Light background: dark text, pale surfaces.
+                prompt: Color::Rgb(60, 90, 160),
+                input_text: Color::Rgb(30, 30, 35),
+                muted_text: Color::Rgb(120, 120, 130),
+                highlight_fg: Color::Rgb(20, 20, 25),
+                highlight_bg: Color::Rgb(205, 215, 235),
+                activity_badge_fg: Color::Rgb(255, 255, 255),
+                activity_badge_bg: Color::Rgb(90, 110, 150),
+                activity_text: Color::Rgb(90, 90, 100),
+                status_text: Color::Rgb(95, 95, 105),
+                user_fg: Color::Rgb(20, 20, 25),
+                user_bg: Color::Rgb(228, 232, 240),
+                claude_label: Color::Rgb(200, 85, 40),
+                codex_label: Color::Rgb(40, 80, 190),
+                processing_label: Color::Rgb(110, 110, 120),
+                assistant_text: Color::Rgb(35, 35, 40),
+                assistant_processing_text: Color::Rgb(100, 100, 110),
+                system_text: Color::Rgb(85, 85, 95),
+                tool_icon: Color::Rgb(110, 110, 120),
+                tool_text: Color::Rgb(90, 90, 100),
+                error_label: Color::Rgb(190, 40, 40),
+                error_text: Color::Rgb(170, 40, 40),
+                banner_title: Color::Rgb(40, 40, 50),
+                panel_bg: Color::Rgb(245, 245, 248),
+                panel_fg: Color::Rgb(35, 35, 40),
+                approval_title: Color::Rgb(170, 100, 30),
+                code_fg: Color::Rgb(35, 35, 40),
+                code_bg: Color::Rgb(238, 238, 242),
+                inline_code_fg: Color::Rgb(120, 40, 90),
+                inline_code_bg: Color::Rgb(232, 232, 238),
+                bullet: Color::Rgb(110, 110, 130),
+                link: Color::Rgb(30, 90, 190),
+                syntax_keyword: Color::Rgb(160, 50, 140),
+                syntax_string: Color::Rgb(40, 120, 50),
+                syntax_number: Color::Rgb(170, 90, 20),
+                syntax_comment: Color::Rgb(130, 130, 140),
+                syntax_type: Color::Rgb(30, 100, 150),
+                diff_added: Color::Rgb(30, 130, 50),
+                diff_removed: Color::Rgb(190, 40, 40),
+                diff_hunk: Color::Rgb(60, 90, 170),
             },
         }
     }
@@ -273,6 +401,14 @@ fn default_theme() -> ThemePreset {
     ThemePreset::Graphite
 }
 
+/// Theme used until one is chosen with `/theme`.
+fn default_theme_for(background: Option<Background>) -> ThemePreset {
+    match background {
+        Some(Background::Light) => ThemePreset::Daylight,
+        Some(Background::Dark) | None => default_theme(),
+    }
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct ThemePalette {
     pub(crate) prompt: Color,
@@ -308,6 +444,15 @@ pub(crate) struct ThemePalette {
     pub(crate) inline_code_fg: Color,
     pub(crate) inline_code_bg: Color,
     pub(crate) bullet: Color,
+    pub(crate) link: Color,
+    pub(crate) syntax_keyword: Color,
+    pub(crate) syntax_string: Color,
+    pub(crate) syntax_number: Color,
+    pub(crate) syntax_comment: Color,
+    pub(crate) syntax_type: Color,
+    pub(crate) diff_added: Color,
+    pub(crate) diff_removed: Color,
+    pub(crate) diff_hunk: Color,
 }
 
 impl ThemePalette {
@@ -381,6 +526,13 @@ pub(crate) struct LogEntry {
     pub(crate) elapsed_secs: Option<u64>,
 }
 
+/// `/mem compact` run waiting for the agent's digest.
+#[derive(Debug)]
+struct PendingCompaction {
+    provider: Provider,
+    batch: CompactionBatch,
+}
+
 #[derive(Clone, Debug)]
 struct PendingApproval {
     line: String,
@@ -394,11 +546,69 @@ struct PendingPaste {
     content: String,
 }
 
+/// Text queued for the clipboard and what to call it once it is written.
+#[derive(Clone, Debug)]
+struct PendingCopy {
+    text: String,
+    what: String,
+}
+
+/// Where the prompt being dispatched is already stored, so its own context leaves it out.
+#[derive(Clone, Copy, Debug, Default)]
+struct CurrentTurn {
+    /// Transcript entry echoing the prompt.
+    entry: Option<usize>,
+    /// Memory row holding the prompt.
+    message: Option<i64>,
+}
+
 #[derive(Clone, Copy, Debug)]
 enum Mode {
     Normal,
     HistorySearch,
+    TranscriptSearch,
     Approval,
+    ContextPreview,
+}
+
+/// How the entries of a multi-agent run are shown while they stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AgentLayout {
+    /// One column per agent, each scrolled on its own.
+    Split,
+    /// Entries one below the other in the transcript.
+    Stacked,
+}
+
+impl AgentLayout {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "split" | "columns" => Some(Self::Split),
+            "stacked" | "stack" => Some(Self::Stacked),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Split => "split",
+            Self::Stacked => "stacked",
+        }
+    }
+}
+
+/// Dry-run output of `/context <prompt>`, shown in a scrollable modal.
+#[derive(Clone, Debug, Default)]
+struct ContextPreview {
+    lines: Vec<PreviewLine>,
+    scroll: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PreviewLine {
+    Heading(String),
+    Meta(String),
+    Body(String),
 }
 
 #[derive(Debug)]
@@ -423,14 +633,23 @@ pub(crate) enum WorkerEvent {
         to: Provider,
         reason: String,
     },
+    /// Structured tool activity; persisted, not rendered.
+    RunEvent {
+        provider: Provider,
+        event: RunEvent,
+    },
     Error(String),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SessionSnapshot {
     primary_provider: Provider,
-    #[serde(default = "default_theme")]
-    theme: ThemePreset,
+    /// `None` in snapshots saved before any theme was picked.
+    #[serde(default)]
+    theme: Option<ThemePreset>,
+    /// Theme file selected with `/theme`; `theme` is the fallback if it disappears.
+    #[serde(default)]
+    custom_theme: Option<String>,
     entries: Vec<LogEntry>,
     history: Vec<String>,
     #[serde(default = "default_session_id")]
@@ -441,6 +660,76 @@ fn default_session_id() -> String {
     "default".to_string()
 }
 
+/// Per-session transcript and input history, stored next to `session.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionTranscript {
+    #[serde(default)]
+    entries: Vec<LogEntry>,
+    #[serde(default)]
+    history: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StartupOptions {
+    /// Session requested with `--session <name>`.
+    pub(crate) session: Option<String>,
+    /// `--fullscreen`: alternate screen with an in-app transcript pane.
+    pub(crate) fullscreen: bool,
+    /// Detected terminal background, picks the default theme.
+    pub(crate) background: Option<Background>,
+    /// `NO_COLOR` is set: render with modifiers only.
+    pub(crate) no_color: bool,
+    /// `--mouse`: wheel scrolling and clickable transcript entries.
+    pub(crate) mouse: bool,
+}
+
+fn format_age(secs: i64) -> String {
+    let secs = secs.max(0);
+    if secs < 60 {
+        "just now".to_string()
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86_400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86_400)
+    }
+}
+
+fn new_run_id() -> String {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("run-{millis:x}")
+}
+
+fn retention_breakdown(report: &RetentionReport) -> String {
+    let mut out = format!(
+        "{} expired, {} over row limit, {} over size limit",
+        report.expired, report.trimmed, report.over_size
+    );
+    if report.size_unreachable {
+        out.push_str(" (size limit unreachable)");
+    }
+    out
+}
+
+/// The last session saved a theme the user picked, so the terminal
+/// background does not need to be detected.
+pub(crate) fn saved_theme_chosen() -> bool {
+    fs::read_to_string(App::session_file_path()).is_ok_and(|raw| snapshot_has_theme(&raw))
+}
+
+fn snapshot_has_theme(raw: &str) -> bool {
+    #[derive(Deserialize)]
+    struct SavedTheme {
+        #[serde(default)]
+        theme: Option<ThemePreset>,
+    }
+    serde_json::from_str::<SavedTheme>(raw).is_ok_and(|saved| saved.theme.is_some())
+}
+
 fn restore_transcript_on_start(memory_available: bool) -> bool {
     if !memory_available {
         return true;
@@ -467,6 +756,24 @@ struct RenderCache {
     lines: Vec<Line<'static>>,
     /// The cached maximum scroll offset.
     scroll_max: u16,
+    line_map: LineMap,
+    /// Wrapped row each cached line starts on at `width`.
+    row_starts: Vec<usize>,
+    /// Wrapped rows of all cached lines.
+    total_rows: usize,
+}
+
+/// How rendered transcript lines relate to entries and to each other.
+#[derive(Default)]
+struct LineMap {
+    /// `(entry index, first line)` for every entry that rendered lines.
+    entry_rows: Vec<(usize, usize)>,
+    /// Lines that continue the text of the line above after `wrap_spans` broke it.
+    continuations: HashSet<usize>,
+    /// Spans of the assistant label gutter in front of a line's content.
+    gutters: HashMap<usize, usize>,
+    /// `(entry index, code block)` for each line drawn as a code fence.
+    fences: HashMap<usize, (usize, usize)>,
 }
 
 impl RenderCache {
@@ -477,12 +784,34 @@ impl RenderCache {
             height: 0,
             lines: Vec::new(),
             scroll_max: 0,
+            line_map: LineMap::default(),
+            row_starts: Vec::new(),
+            total_rows: 0,
+        }
+    }
+
+    /// Wrapped row on which line `line` starts.
+    fn line_row(&self, line: usize) -> usize {
+        self.row_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.total_rows)
+    }
+
+    /// Index of the line drawn on wrapped row `row`.
+    fn line_at_row(&self, row: usize) -> Option<usize> {
+        if row >= self.total_rows {
+            return None;
         }
+        Some(self.row_starts.partition_point(|&start| start <= row) - 1)
     }
 }
 
-pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
-    let mut app = App::new();
+pub(crate) fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    options: StartupOptions,
+) -> Result<()> {
+    let mut app = App::with_options(options);
     const ACTIVE_POLL_MS: u64 = 33;
     const IDLE_POLL_MS: u64 = 100;
     const SPINNER_TICK_MS: u64 = 120;
@@ -501,6 +830,12 @@ pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Resu
         if app.poll_worker() {
             state_changed = true;
         }
+        if !app.running && app.apply_retention_if_due() {
+            state_changed = true;
+        }
+        if app.reload_themes_if_due() {
+            state_changed = true;
+        }
         if app.running && last_spinner_tick.elapsed() >= Duration::from_millis(SPINNER_TICK_MS) {
             app.spinner_idx = (app.spinner_idx + 1) % 8;
             last_spinner_tick = Instant::now();
@@ -510,16 +845,29 @@ pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Resu
             needs_draw = true;
         }
 
+        if std::mem::take(&mut app.editor_requested) {
+            compose_in_editor(terminal, &mut app)?;
+            needs_draw = true;
+        }
+
+        if let Some(copy) = app.pending_copy.take() {
+            let result = clipboard::copy(&copy.text, app.clipboard_backend);
+            app.report_copy(&copy, result);
+            needs_draw = true;
+        }
+
         if app.needs_screen_clear {
             app.needs_screen_clear = false;
             flushed_log_lines.clear();
             last_flush_was_running = false;
-            // Clear the entire terminal including scrollback, not just the ratatui viewport.
-            crossterm::execute!(
-                std::io::stdout(),
-                TermClear(ClearType::Purge),
-                crossterm::cursor::MoveTo(0, 0)
-            )?;
+            if !app.fullscreen {
+                // Clear the entire terminal including scrollback, not just the ratatui viewport.
+                crossterm::execute!(
+                    std::io::stdout(),
+                    TermClear(ClearType::Purge),
+                    crossterm::cursor::MoveTo(0, 0)
+                )?;
+            }
             terminal.clear()?;
             needs_draw = true;
         }
@@ -534,12 +882,14 @@ pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Resu
                     app.update_viewport(area.width, area.height);
                 }
                 app.ensure_render_cache();
-                flush_new_log_lines(
-                    terminal,
-                    &app,
-                    &mut flushed_log_lines,
-                    &mut last_flush_was_running,
-                )?;
+                if !app.fullscreen {
+                    flush_new_log_lines(
+                        terminal,
+                        &app,
+                        &mut flushed_log_lines,
+                        &mut last_flush_was_running,
+                    )?;
+                }
                 terminal.draw(|f| ui::draw(f, &app))?;
                 last_draw_at = Instant::now();
                 needs_draw = false;
@@ -565,15 +915,17 @@ pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Resu
 
         loop {
             match event::read().context("event read")? {
-                Event::Key(key) => {
-                    if !matches!(key.kind, KeyEventKind::Release) {
-                        app.handle_key(key);
-                        input_changed = true;
-                    }
+                Event::Key(key) if !matches!(key.kind, KeyEventKind::Release) => {
+                    app.handle_key(key);
+                    input_changed = true;
                 }
                 Event::Mouse(mouse) => match mouse.kind {
                     MouseEventKind::ScrollUp => wheel_delta -= 1,
                     MouseEventKind::ScrollDown => wheel_delta += 1,
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        app.handle_transcript_click(mouse.column, mouse.row);
+                        input_changed = true;
+                    }
                     _ => {}
                 },
                 Event::Paste(text) => {
@@ -581,6 +933,8 @@ pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Resu
                     input_changed = true;
                 }
                 Event::Resize(_, _) => {
+                    // Repaint from scratch so no stale cells survive a shrink.
+                    app.needs_screen_clear |= app.fullscreen;
                     input_changed = true;
                 }
                 _ => {}
@@ -610,8 +964,27 @@ pub(crate) fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Resu
 
     app.persist_session();
 
-    // Clear input/status bars while keeping transcript in terminal scrollback.
-    terminal.draw(|f| ui::draw_exit(f, &app))?;
+    if !app.fullscreen {
+        // Clear input/status bars while keeping transcript in terminal scrollback.
+        terminal.draw(|f| ui::draw_exit(f, &app))?;
+    }
+    Ok(())
+}
+
+/// Suspend the TUI, edit the composer in `$VISUAL`/`$EDITOR` with pastes
+/// expanded, then resume with the result loaded back.
+fn compose_in_editor(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+) -> Result<()> {
+    let seed = app.expand_pending_pastes(&app.input);
+    crate::suspend_terminal(terminal, app.fullscreen)?;
+    let edited = editor::edit_text(&editor::editor_command(), &seed);
+    crate::resume_terminal(terminal, app.fullscreen, app.mouse_capture)?;
+    match edited {
+        Ok(text) => app.load_composed_input(&text),
+        Err(err) => app.push_entry(EntryKind::Error, format!("{err:#}")),
+    }
     Ok(())
 }
 
@@ -674,6 +1047,9 @@ fn flush_new_log_lines(
         terminal.insert_before(height, |buf| {
             let paragraph = Paragraph::new(Text::from(new_lines)).wrap(Wrap { trim: false });
             paragraph.render(buf.area, buf);
+            if app.no_color {
+                ui::strip_colors(buf, app.theme_palette());
+            }
         })
     }));
     match insert_result {
@@ -782,6 +1158,7 @@ enum StartupBannerRow<'a> {
     Title(&'a str),
     Agents(&'a str),
     Cwd(&'a str),
+    Memory(&'a str),
     Keys(&'a str),
 }
 
@@ -792,6 +1169,7 @@ fn parse_startup_banner_row(text: &str) -> Option<StartupBannerRow<'_>> {
         "title" => Some(StartupBannerRow::Title(value)),
         "agents" => Some(StartupBannerRow::Agents(value)),
         "cwd" => Some(StartupBannerRow::Cwd(value)),
+        "memory" => Some(StartupBannerRow::Memory(value)),
         "keys" => Some(StartupBannerRow::Keys(value)),
         _ => None,
     }
@@ -857,6 +1235,7 @@ struct App {
     history_pos: Option<usize>,
     history_query: String,
     history_idx: usize,
+    find: find::TranscriptFind,
 
     commands: Vec<String>,
     slash_hint_idx: usize,
@@ -864,6 +1243,13 @@ struct App {
     approval: Option<PendingApproval>,
     allow_high_risk_tools: HashSet<String>,
     theme: ThemePreset,
+    /// Active theme from `~/.dagent/themes`, overriding `theme` while it loads.
+    custom_theme: Option<String>,
+    /// Set once a theme comes from `/theme` or a snapshot; until then the
+    /// default follows the terminal background and is not persisted.
+    theme_chosen: bool,
+    themes: themes::ThemeLibrary,
+    themes_checked_at: Instant,
 
     rx: Option<Receiver<WorkerEvent>>,
     assistant_idx: Option<usize>,
@@ -873,6 +1259,8 @@ struct App {
     active_provider: Option<Provider>,
     run_started_at: Option<Instant>,
     run_target: String,
+    /// Groups the `run_events` rows written during one submitted line.
+    run_id: String,
     last_tool_event: String,
     finished_at: Option<Instant>,
     finished_elapsed_secs: u64,
@@ -886,31 +1274,85 @@ struct App {
 
     last_status: String,
     session_id: String,
+    memory_scope: MemoryScope,
+    context_config: ContextConfig,
+    retention: RetentionPolicy,
+    /// When retention last ran and what it removed.
+    last_retention: Option<(Instant, RetentionReport)>,
     memory: Option<MemoryStore>,
     child_pids: Arc<Mutex<Vec<u32>>>,
 
     /// Set by /clear to tell the main loop to wipe the terminal scrollback.
     needs_screen_clear: bool,
+    /// Transcript is drawn in a pane instead of flushed to terminal scrollback.
+    fullscreen: bool,
+    /// `NO_COLOR`: colours are stripped from every rendered cell.
+    no_color: bool,
+    agent_layout: AgentLayout,
+    /// Entries of the last multi-agent run's agents. In full-screen mode their
+    /// columns stay up after the run until the next prompt.
+    split_entries: HashMap<Provider, usize>,
+    /// Index into `split_columns()` that PgUp/PgDn scroll.
+    split_focus: usize,
+    /// Rows each split column is scrolled back from its tail; absent follows the tail.
+    split_scroll_back: HashMap<Provider, u16>,
+    /// Where the full-screen transcript pane was last drawn, for mouse hits.
+    transcript_area: Cell<Rect>,
+    /// Entry picked with a mouse click, highlighted in the transcript.
+    selected_entry: Option<usize>,
+    /// Text waiting to be written to the clipboard by the main loop.
+    pending_copy: Option<PendingCopy>,
+    /// Ctrl+G or Ctrl+X Ctrl+E: the main loop hands the composer to `$EDITOR`.
+    editor_requested: bool,
+    /// Ctrl+X was pressed; Ctrl+E next opens the editor.
+    ctrl_x_pending: bool,
+    /// `--mouse`: capture is turned back on after the editor returns.
+    mouse_capture: bool,
+    clipboard_backend: ClipboardBackend,
 
     /// Monotonically increasing counter bumped whenever entries change.
     render_generation: u64,
     /// Cached rendering output to avoid expensive recomputation every frame.
     render_cache: RenderCache,
+    pending_compaction: Option<PendingCompaction>,
+    context_preview: Option<ContextPreview>,
+    /// Prompts the last dispatch would have sent; tests record them instead of spawning.
+    #[cfg(test)]
+    spawned_prompts: Option<HashMap<Provider, String>>,
 }
 
 impl App {
+    #[cfg(test)]
     fn new() -> Self {
+        Self::with_options(StartupOptions::default())
+    }
+
+    fn with_options(options: StartupOptions) -> Self {
         let available_providers = detect_available_providers();
         let primary_provider = if available_providers.contains(&Provider::Claude) {
             Provider::Claude
         } else {
             *available_providers.first().unwrap_or(&Provider::Claude)
         };
+        let (config, config_error) = if cfg!(test) {
+            (Config::default(), None)
+        } else {
+            match Config::load() {
+                Ok(config) => (config, None),
+                Err(err) => (Config::default(), Some(format!("{err:#}"))),
+            }
+        };
         let memory = if cfg!(test) {
             None
         } else {
-            MemoryStore::open_default().ok()
+            MemoryStore::open_default().ok().map(|memory| {
+                memory
+                    .with_retrieval(config.memory.retrieval)
+                    .with_recency_half_life(config.memory.recency_half_life_days)
+            })
         };
+        let memory_scope = MemoryScope::detect(&config.memory);
+        let retention = config.memory.retention;
         let mut app = Self {
             primary_provider,
             available_providers,
@@ -930,11 +1372,20 @@ impl App {
             history_pos: None,
             history_query: String::new(),
             history_idx: 0,
+            find: find::TranscriptFind::default(),
             commands: default_commands(),
             slash_hint_idx: 0,
             approval: None,
             allow_high_risk_tools: HashSet::new(),
-            theme: default_theme(),
+            theme: default_theme_for(options.background),
+            custom_theme: None,
+            theme_chosen: false,
+            themes: if cfg!(test) {
+                themes::ThemeLibrary::disabled()
+            } else {
+                themes::ThemeLibrary::load(crate::config::themes_dir())
+            },
+            themes_checked_at: Instant::now(),
             rx: None,
             assistant_idx: None,
             stream_had_chunk: false,
@@ -943,6 +1394,7 @@ impl App {
             active_provider: None,
             run_started_at: None,
             run_target: String::new(),
+            run_id: String::new(),
             last_tool_event: String::new(),
             finished_at: None,
             finished_elapsed_secs: 0,
@@ -953,15 +1405,56 @@ impl App {
             agent_tool_event: HashMap::new(),
             activity_log: std::collections::VecDeque::new(),
             last_status: "ready".to_string(),
-            session_id: default_session_id(),
+            session_id: memory_scope.session_id.clone(),
+            memory_scope,
+            context_config: config.context,
+            retention,
+            last_retention: None,
             memory,
             child_pids: Arc::new(Mutex::new(Vec::new())),
             needs_screen_clear: false,
+            fullscreen: options.fullscreen,
+            no_color: options.no_color,
             render_generation: 0,
             render_cache: RenderCache::new(),
+            pending_compaction: None,
+            context_preview: None,
+            #[cfg(test)]
+            spawned_prompts: None,
+            // The inline viewport is too short for columns, so only full-screen splits.
+            agent_layout: if options.fullscreen {
+                AgentLayout::Split
+            } else {
+                AgentLayout::Stacked
+            },
+            split_entries: HashMap::new(),
+            split_focus: 0,
+            split_scroll_back: HashMap::new(),
+            transcript_area: Cell::new(Rect::default()),
+            selected_entry: None,
+            pending_copy: None,
+            editor_requested: false,
+            ctrl_x_pending: false,
+            mouse_capture: options.mouse,
+            clipboard_backend: config.clipboard.backend,
         };
         app.restore_session();
+        if let Some(name) = options.session.as_deref() {
+            app.open_startup_session(name);
+        }
+        if let Some(memory) = &app.memory {
+            if app.session_id == app.memory_scope.session_id {
+                memory
+                    .create_session(&app.session_id, &app.memory_scope.label)
+                    .ok();
+            } else {
+                memory.ensure_session(&app.session_id).ok();
+            }
+        }
         app.maybe_show_startup_banner();
+        if let Some(err) = config_error {
+            app.push_entry(EntryKind::Error, format!("config ignored: {err}"));
+        }
         app
     }
 
@@ -975,6 +1468,7 @@ impl App {
         self.finished_at = None;
         self.run_started_at = Some(Instant::now());
         self.run_target = target;
+        self.run_id = new_run_id();
     }
 
     fn clear_running_state(&mut self) {
@@ -992,18 +1486,51 @@ impl App {
         self.active_provider = None;
         self.run_started_at = None;
         self.run_target.clear();
+        self.pending_compaction = None;
         if let Ok(mut pids) = self.child_pids.lock() {
             pids.clear();
         }
     }
 
-    #[allow(dead_code)]
-    pub(super) fn theme_name(&self) -> &'static str {
-        self.theme.as_str()
+    pub(super) fn theme_name(&self) -> &str {
+        self.custom_theme
+            .as_deref()
+            .unwrap_or_else(|| self.theme.as_str())
     }
 
     pub(super) fn theme_palette(&self) -> ThemePalette {
-        self.theme.palette()
+        self.custom_theme
+            .as_deref()
+            .and_then(|name| self.themes.get(name))
+            .map(|theme| theme.palette)
+            .unwrap_or_else(|| self.theme.palette())
+    }
+
+    /// Pick up edits to theme files; polled from the main loop.
+    fn reload_themes_if_due(&mut self) -> bool {
+        if self.themes_checked_at.elapsed() < THEME_POLL_INTERVAL {
+            return false;
+        }
+        self.themes_checked_at = Instant::now();
+        if !self.themes.reload_if_changed() {
+            return false;
+        }
+        self.invalidate_render_cache();
+        if let Some(name) = self.custom_theme.clone() {
+            if self.themes.get(&name).is_none() {
+                self.custom_theme = None;
+                self.push_entry(
+                    EntryKind::Error,
+                    format!(
+                        "theme {name} no longer loads; using {}",
+                        self.theme.as_str()
+                    ),
+                );
+            } else {
+                self.last_status = format!("theme {name} reloaded");
+            }
+        }
+        true
     }
 
     pub(super) fn running_elapsed_secs(&self) -> u64 {
@@ -1050,7 +1577,32 @@ impl App {
 
         let w = self.viewport_width.max(1);
         let h = self.viewport_height;
-        let lines = self.render_log_lines_inner(w);
+        let (mut lines, line_map) = self.render_log_lines_mapped(w);
+        if let Some(selected) = self.selected_entry {
+            let palette = self.theme_palette();
+            let range = entry_line_range(&line_map.entry_rows, selected, lines.len());
+            for line in &mut lines[range] {
+                if let Some(first) = line.spans.first_mut().filter(|s| !s.content.is_empty()) {
+                    first.style = palette.hint_selected_style();
+                }
+            }
+        }
+        if !self.find.query.is_empty() {
+            let hits = find::find_hits(
+                &lines,
+                |idx| line_map.gutters.get(&idx).copied().unwrap_or(0),
+                &line_map.continuations,
+                &self.find.query,
+            );
+            self.find.current = self.find.current.min(hits.len().saturating_sub(1));
+            self.find.hits = hits;
+            // Inline mode already flushed these lines to scrollback; its
+            // hits are shown in the find panel instead.
+            if self.fullscreen {
+                let (hit, current) = self.find_styles();
+                find::highlight_hits(&mut lines, 0, &self.find, hit, current);
+            }
+        }
 
         // Compute scroll_max
         let prompt_width = UnicodeWidthStr::width("> ") as u16;
@@ -1062,9 +1614,15 @@ impl App {
         // fixed height: input + activity line + hints + status
         let fixed_h = input_height.saturating_add(3);
         let available_for_log = h.saturating_sub(fixed_h);
-        let paragraph = Paragraph::new(Text::from(lines.clone())).wrap(Wrap { trim: false });
-        let rendered_line_count = paragraph.line_count(w) as u16;
-        let scroll_max = rendered_line_count.saturating_sub(available_for_log);
+        let mut row_starts = Vec::with_capacity(lines.len());
+        let mut total_rows = 0usize;
+        for line in &lines {
+            row_starts.push(total_rows);
+            total_rows += Paragraph::new(line.clone())
+                .wrap(Wrap { trim: false })
+                .line_count(w);
+        }
+        let scroll_max = (total_rows as u16).saturating_sub(available_for_log);
 
         self.render_cache = RenderCache {
             generation: self.render_generation,
@@ -1072,6 +1630,9 @@ impl App {
             height: self.viewport_height,
             lines,
             scroll_max,
+            line_map,
+            row_starts,
+            total_rows,
         };
         true
     }
@@ -1085,6 +1646,25 @@ impl App {
         &self.render_cache.lines
     }
 
+    /// Wrapped rows of the cached transcript.
+    pub(super) fn cached_log_rows(&self) -> usize {
+        self.render_cache.total_rows
+    }
+
+    /// Cached lines drawn on wrapped rows `top..top + height`, and how many
+    /// rows of the first one sit above `top`.
+    pub(super) fn cached_log_window(&self, top: usize, height: usize) -> (&[Line<'static>], u16) {
+        let cache = &self.render_cache;
+        let Some(first) = cache.line_at_row(top) else {
+            return (&[], 0);
+        };
+        let end = cache
+            .line_at_row(top + height.max(1) - 1)
+            .map_or(cache.lines.len(), |last| last + 1);
+        let skip = top - cache.line_row(first);
+        (&cache.lines[first..end], skip as u16)
+    }
+
     /// Render transcript lines for running flushes.
     /// This includes streaming rows so users can see in-progress output.
     fn running_flush_log_lines(&self, width: u16) -> Vec<Line<'static>> {
@@ -1100,7 +1680,7 @@ impl App {
         if let Some(idx) = self.assistant_idx {
             active_entry_indices.insert(idx);
         }
-        let skip_indices = active_entry_indices
+        let mut skip_indices = active_entry_indices
             .into_iter()
             .filter(|idx| {
                 self.entries.get(*idx).is_some_and(|entry| {
@@ -1110,11 +1690,15 @@ impl App {
                 })
             })
             .collect::<HashSet<_>>();
+        // Split columns live in the viewport; they reach scrollback once the run ends.
+        if self.split_view_active() {
+            skip_indices.extend(self.agent_entries.values().copied());
+        }
 
         if skip_indices.is_empty() {
             self.render_entries_lines(width)
         } else {
-            self.render_entries_lines_filtered(width, &skip_indices)
+            self.render_entries_lines_filtered(width, &skip_indices, &mut LineMap::default())
         }
     }
     fn update_viewport(&mut self, width: u16, height: u16) {
@@ -1193,12 +1777,16 @@ impl App {
     }
 
     fn consume_pending_pastes(&mut self, text: &str) -> String {
-        if self.pending_pastes.is_empty() {
-            return text.to_string();
-        }
+        let merged = self.expand_pending_pastes(text);
+        self.pending_pastes.clear();
+        merged
+    }
+
+    /// `text` with every collapsed paste marker replaced by its content.
+    fn expand_pending_pastes(&self, text: &str) -> String {
         let mut merged = text.to_string();
         let mut search_from = 0usize;
-        for pending in self.pending_pastes.drain(..) {
+        for pending in &self.pending_pastes {
             if let Some(rel) = merged[search_from..].find(&pending.marker) {
                 let start = search_from + rel;
                 let end = start + pending.marker.len();
@@ -1209,6 +1797,27 @@ impl App {
         merged
     }
 
+    /// Put text saved in `$EDITOR` into the composer. Pastes that come back
+    /// unchanged are collapsed behind their markers again.
+    fn load_composed_input(&mut self, text: &str) {
+        let pastes = std::mem::take(&mut self.pending_pastes);
+        let mut input = text.to_string();
+        let mut search_from = 0usize;
+        for pending in pastes {
+            let Some(rel) = input[search_from..].find(&pending.content) else {
+                continue;
+            };
+            let start = search_from + rel;
+            input.replace_range(start..start + pending.content.len(), &pending.marker);
+            search_from = start + pending.marker.len();
+            self.pending_pastes.push(pending);
+        }
+        self.input = input;
+        self.cursor = self.input.len();
+        self.history_pos = None;
+        self.last_status = "prompt loaded from editor".to_string();
+    }
+
     fn clear_input_buffer(&mut self) {
         self.input.clear();
         self.cursor = 0;
@@ -1242,6 +1851,18 @@ impl App {
             EntryKind::System,
             format!("{STARTUP_BANNER_PREFIX}cwd|cwd: {}", cwd),
         );
+        let scope = if self.session_id == self.memory_scope.session_id {
+            self.memory_scope.label.clone()
+        } else {
+            "--session".to_string()
+        };
+        self.push_entry(
+            EntryKind::System,
+            format!(
+                "{STARTUP_BANNER_PREFIX}memory|memory: {} ({})",
+                self.session_id, scope
+            ),
+        );
         self.push_entry(
             EntryKind::System,
             format!(
@@ -1251,32 +1872,71 @@ impl App {
         self.last_status = "ready".to_string();
     }
 
-    fn build_contextual_prompt(&self, prompt: &str) -> String {
+    fn context_budget(&self, provider: Provider) -> ContextBudget {
+        self.context_config.budget(provider.as_str())
+    }
+
+    fn build_contextual_prompt(
+        &self,
+        prompt: &str,
+        provider: Provider,
+        current: CurrentTurn,
+    ) -> String {
+        let budget = self.context_budget(provider);
         if let Some(memory) = &self.memory {
-            if let Ok(text) = memory.build_context(&self.session_id, prompt) {
+            if let Ok(text) = memory.build_context(
+                &self.session_id,
+                prompt,
+                Some(provider.as_str()),
+                current.message,
+                &budget,
+            ) {
                 return text;
             }
+            let pins = memory.list_pins(&self.session_id).unwrap_or_default();
+            return with_pins(
+                &pins,
+                self.build_contextual_prompt_from_entries(prompt, &budget, current.entry),
+            );
         }
-        self.build_contextual_prompt_from_entries(prompt)
+        self.build_contextual_prompt_from_entries(prompt, &budget, current.entry)
     }
 
-    fn build_contextual_prompt_from_entries(&self, prompt: &str) -> String {
-        const CONTEXT_ENTRY_LIMIT: usize = 18;
-        const CONTEXT_CHAR_LIMIT: usize = 6000;
+    fn build_contextual_prompt_from_entries(
+        &self,
+        prompt: &str,
+        budget: &ContextBudget,
+        current: Option<usize>,
+    ) -> String {
+        let selected = self.transcript_context_lines(budget, current);
+        if selected.is_empty() {
+            return prompt.to_string();
+        }
+        format!(
+            "Conversation context from this DAgent session:\n{}\n\nCurrent user request:\n{}",
+            selected.join("\n"),
+            prompt
+        )
+    }
 
+    /// Latest transcript turns that fit the fallback part of `budget`, oldest first,
+    /// leaving out the `current` entry that holds the prompt being sent.
+    fn transcript_context_lines(
+        &self,
+        budget: &ContextBudget,
+        current: Option<usize>,
+    ) -> Vec<String> {
         let mut lines = Vec::<String>::new();
-        let mut skipped_current_prompt = false;
-        for entry in self.entries.iter().rev() {
-            if lines.len() >= CONTEXT_ENTRY_LIMIT {
+        for (idx, entry) in self.entries.iter().enumerate().rev() {
+            if lines.len() >= budget.transcript_entries {
                 break;
             }
+            if Some(idx) == current {
+                continue;
+            }
             match entry.kind {
                 EntryKind::User => {
                     let text = entry.text.trim();
-                    if !skipped_current_prompt && text == prompt.trim() {
-                        skipped_current_prompt = true;
-                        continue;
-                    }
                     if !text.is_empty() {
                         lines.push(format!("user: {}", text));
                     }
@@ -1296,28 +1956,18 @@ impl App {
             }
         }
 
-        if lines.is_empty() {
-            return prompt.to_string();
-        }
-
-        lines.reverse();
         let mut selected = Vec::<String>::new();
         let mut used = 0usize;
-        for line in lines.into_iter().rev() {
-            let delta = line.len().saturating_add(1);
-            if used + delta > CONTEXT_CHAR_LIMIT && !selected.is_empty() {
+        for line in lines {
+            let tokens = estimate_tokens(&line);
+            if used + tokens > budget.transcript_tokens && !selected.is_empty() {
                 break;
             }
-            used += delta;
+            used += tokens;
             selected.push(line);
         }
         selected.reverse();
-
-        format!(
-            "Conversation context from this DAgent session:\n{}\n\nCurrent user request:\n{}",
-            selected.join("\n"),
-            prompt
-        )
+        selected
     }
 
     fn session_file_path() -> PathBuf {
@@ -1328,6 +1978,18 @@ impl App {
         }
     }
 
+    fn session_transcript_path(session_id: &str) -> PathBuf {
+        let file = format!("{session_id}.json");
+        if let Some(home) = std::env::var_os("HOME") {
+            PathBuf::from(home)
+                .join(".dagent")
+                .join("sessions")
+                .join(file)
+        } else {
+            PathBuf::from(".dagent").join("sessions").join(file)
+        }
+    }
+
     fn restore_session(&mut self) {
         let path = Self::session_file_path();
         let Ok(raw) = fs::read_to_string(path) else {
@@ -1343,18 +2005,27 @@ impl App {
         {
             self.primary_provider = snapshot.primary_provider;
         }
-        self.theme = snapshot.theme;
+        if let Some(theme) = snapshot.theme {
+            self.theme = theme;
+            self.theme_chosen = true;
+        }
+        self.custom_theme = snapshot
+            .custom_theme
+            .filter(|name| self.themes.get(name).is_some());
+        // The snapshot belongs to whichever session ran last, possibly in another
+        // project; only reuse it when it matches this project's scope.
+        let (entries, history) = if snapshot.session_id == self.session_id {
+            (snapshot.entries, snapshot.history)
+        } else {
+            let transcript = Self::load_session_transcript(&self.session_id);
+            (transcript.entries, transcript.history)
+        };
         if restore_transcript_on_start(self.memory.is_some()) {
-            self.entries = snapshot.entries;
+            self.entries = entries;
         } else {
             self.entries = Vec::new();
         }
-        self.history = snapshot.history;
-        self.session_id = if snapshot.session_id.trim().is_empty() {
-            default_session_id()
-        } else {
-            snapshot.session_id
-        };
+        self.history = history;
         self.history_pos = None;
         self.autoscroll = true;
         self.scroll = self.scroll_max();
@@ -1363,16 +2034,49 @@ impl App {
         }
     }
 
-    fn persist_session(&self) {
-        const MAX_PERSISTED_ENTRIES: usize = 600;
-        const MAX_PERSISTED_HISTORY: usize = 400;
+    /// Apply `--session <name>`: reuse a matching session or create it.
+    fn open_startup_session(&mut self, name: &str) {
+        let Some(id) = self.resolve_or_create_session(name) else {
+            return;
+        };
+        if id == self.session_id {
+            return;
+        }
+        let transcript = Self::load_session_transcript(&id);
+        self.session_id = id;
+        if restore_transcript_on_start(self.memory.is_some()) {
+            self.entries = transcript.entries;
+        } else {
+            self.entries = Vec::new();
+        }
+        self.history = transcript.history;
+        self.history_pos = None;
+        self.invalidate_render_cache();
+    }
 
-        let path = Self::session_file_path();
-        if let Some(parent) = path.parent() {
-            if fs::create_dir_all(parent).is_err() {
-                return;
+    fn resolve_or_create_session(&mut self, name: &str) -> Option<String> {
+        if let Some(memory) = &self.memory {
+            if let Ok(Some(found)) = memory.find_session(name) {
+                return Some(found.id);
             }
         }
+        let id = slugify(name)?;
+        if let Some(memory) = &self.memory {
+            memory.create_session(&id, name.trim()).ok();
+        }
+        Some(id)
+    }
+
+    fn load_session_transcript(session_id: &str) -> SessionTranscript {
+        fs::read_to_string(Self::session_transcript_path(session_id))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<SessionTranscript>(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    fn persisted_entries_and_history(&self) -> (Vec<LogEntry>, Vec<String>) {
+        const MAX_PERSISTED_ENTRIES: usize = 600;
+        const MAX_PERSISTED_HISTORY: usize = 400;
 
         let entries = if self.entries.len() > MAX_PERSISTED_ENTRIES {
             self.entries[self.entries.len().saturating_sub(MAX_PERSISTED_ENTRIES)..].to_vec()
@@ -1384,9 +2088,37 @@ impl App {
         } else {
             self.history.clone()
         };
+        (entries, history)
+    }
+
+    fn persist_session_transcript(&self) {
+        let path = Self::session_transcript_path(&self.session_id);
+        if let Some(parent) = path.parent() {
+            if fs::create_dir_all(parent).is_err() {
+                return;
+            }
+        }
+        let (entries, history) = self.persisted_entries_and_history();
+        let transcript = SessionTranscript { entries, history };
+        let Ok(serialized) = serde_json::to_string_pretty(&transcript) else {
+            return;
+        };
+        let _ = fs::write(path, serialized);
+    }
+
+    fn persist_session(&self) {
+        let path = Self::session_file_path();
+        if let Some(parent) = path.parent() {
+            if fs::create_dir_all(parent).is_err() {
+                return;
+            }
+        }
+
+        let (entries, history) = self.persisted_entries_and_history();
         let snapshot = SessionSnapshot {
             primary_provider: self.primary_provider,
-            theme: self.theme,
+            theme: self.theme_chosen.then_some(self.theme),
+            custom_theme: self.custom_theme.clone(),
             entries,
             history,
             session_id: self.session_id.clone(),
@@ -1396,13 +2128,43 @@ impl App {
             return;
         };
         let _ = fs::write(path, serialized);
+        self.persist_session_transcript();
     }
 
-    fn interrupt_running_task(&mut self, reason: &str) {
-        if !self.running {
+    /// Swap transcript, input history and memory scope to another session.
+    fn switch_session(&mut self, session_id: String) {
+        if session_id == self.session_id {
+            self.push_entry(
+                EntryKind::System,
+                format!("already on session {}", session_id),
+            );
             return;
         }
-
+        self.persist_session_transcript();
+        let transcript = Self::load_session_transcript(&session_id);
+        if let Some(memory) = &self.memory {
+            memory.ensure_session(&session_id).ok();
+        }
+        self.session_id = session_id;
+        self.entries = transcript.entries;
+        self.clear_split_columns();
+        self.history = transcript.history;
+        self.history_pos = None;
+        self.needs_screen_clear = true;
+        self.autoscroll = true;
+        self.invalidate_render_cache();
+        self.push_entry(
+            EntryKind::System,
+            format!("switched to session {}", self.session_id),
+        );
+        self.last_status = format!("session {}", self.session_id);
+    }
+
+    fn interrupt_running_task(&mut self, reason: &str) {
+        if !self.running {
+            return;
+        }
+
         if let Ok(mut pids) = self.child_pids.lock() {
             for &pid in pids.iter() {
                 kill_pid(pid);
@@ -1513,34 +2275,38 @@ impl App {
                                 }
                             }
                         }
-                        if let Some(i) = self.agent_entries.get(&provider).copied() {
-                            if let Some(entry) = self.entries.get(i) {
-                                let text =
-                                    cleaned_assistant_text_for_model(entry).trim().to_string();
-                                if !text.is_empty()
+                        let reply = self
+                            .agent_entries
+                            .get(&provider)
+                            .and_then(|i| self.entries.get(*i))
+                            .map(|entry| cleaned_assistant_text_for_model(entry).trim().to_string())
+                            .filter(|text| {
+                                !text.is_empty()
                                     && text != "(no output)"
                                     && text != "(failed)"
                                     && text != "(interrupted)"
                                     && text != "(cancelled)"
                                     && text != "(disconnected)"
-                                {
-                                    if let Some(memory) = &self.memory {
-                                        if let Err(err) = memory.append_message(
-                                            &self.session_id,
-                                            "assistant",
-                                            Some(provider.as_str()),
-                                            &text,
-                                        ) {
-                                            self.push_entry(
-                                                EntryKind::System,
-                                                format!(
-                                                    "memory write failed: {}",
-                                                    truncate(&err.to_string(), 80)
-                                                ),
-                                            );
-                                        }
-                                    }
-                                }
+                            });
+                        if let Some(pending) = self
+                            .pending_compaction
+                            .take_if(|pending| pending.provider == provider)
+                        {
+                            self.finish_compaction(pending, reply.as_deref());
+                        } else if let (Some(text), Some(memory)) = (&reply, &self.memory) {
+                            if let Err(err) = memory.append_message(
+                                &self.session_id,
+                                "assistant",
+                                Some(provider.as_str()),
+                                text,
+                            ) {
+                                self.push_entry(
+                                    EntryKind::System,
+                                    format!(
+                                        "memory write failed: {}",
+                                        truncate(&err.to_string(), 80)
+                                    ),
+                                );
                             }
                         }
                         if self.active_provider == Some(provider) {
@@ -1651,6 +2417,25 @@ impl App {
                         self.last_status =
                             format!("progress {}: {}", provider.as_str(), truncate(&msg, 42));
                     }
+                    Ok(WorkerEvent::RunEvent { provider, event }) => {
+                        processed_any = true;
+                        if let Some(memory) = &self.memory {
+                            if let Err(err) = memory.append_run_event(
+                                &self.session_id,
+                                &self.run_id,
+                                provider.as_str(),
+                                &event,
+                            ) {
+                                self.push_entry(
+                                    EntryKind::System,
+                                    format!(
+                                        "memory write failed: {}",
+                                        truncate(&err.to_string(), 80)
+                                    ),
+                                );
+                            }
+                        }
+                    }
                     Ok(WorkerEvent::PromotePrimary { to, reason }) => {
                         processed_any = true;
                         if self.primary_provider != to {
@@ -1775,6 +2560,7 @@ impl App {
 
         if line == "/clear" {
             self.entries.clear();
+            self.clear_split_columns();
             self.invalidate_render_cache();
             self.needs_screen_clear = true;
             if let Some(memory) = &self.memory {
@@ -1791,7 +2577,20 @@ impl App {
         }
 
         if let Some(rest) = line.strip_prefix("/mem") {
+            // Cleared first: `/mem use` refills the composer.
+            self.clear_input_buffer();
             self.handle_memory_command(rest.trim());
+            return;
+        }
+
+        if let Some(rest) = line.strip_prefix("/session") {
+            self.handle_session_command(rest.trim());
+            self.clear_input_buffer();
+            return;
+        }
+
+        if let Some(rest) = line.strip_prefix("/context") {
+            self.handle_context_command(rest.trim());
             self.clear_input_buffer();
             return;
         }
@@ -1802,6 +2601,24 @@ impl App {
             return;
         }
 
+        if let Some(rest) = line.strip_prefix("/find") {
+            self.clear_input_buffer();
+            self.open_find(rest.trim());
+            return;
+        }
+
+        if let Some(rest) = line.strip_prefix("/copy") {
+            self.handle_copy_command(rest.trim());
+            self.clear_input_buffer();
+            return;
+        }
+
+        if let Some(rest) = line.strip_prefix("/layout") {
+            self.handle_layout_command(rest.trim());
+            self.clear_input_buffer();
+            return;
+        }
+
         if let Some(rest) = line.strip_prefix("/provider") {
             let target = rest.trim();
             self.handle_primary_change(target);
@@ -1864,19 +2681,29 @@ impl App {
 
         line = self.consume_pending_pastes(&line);
         self.push_entry(EntryKind::User, typed_line);
+        let mut current = CurrentTurn {
+            entry: Some(self.entries.len() - 1),
+            message: None,
+        };
         if !is_slash {
             if let Some(memory) = &self.memory {
-                if let Err(err) = memory.append_message(&self.session_id, "user", None, &line) {
-                    self.push_entry(
+                let recipient = match providers.as_slice() {
+                    [only] => Some(only.as_str()),
+                    _ => None,
+                };
+                match memory.append_message(&self.session_id, "user", recipient, &line) {
+                    Ok(id) => current.message = id,
+                    Err(err) => self.push_entry(
                         EntryKind::System,
                         format!("memory write failed: {}", truncate(&err.to_string(), 80)),
-                    );
+                    ),
                 }
             }
         }
         self.assistant_idx = None;
         self.agent_entries.clear();
         self.agent_had_chunk.clear();
+        self.clear_split_columns();
         self.active_provider = None;
         let run_target = if is_slash {
             "command".to_string()
@@ -1898,6 +2725,7 @@ impl App {
                     self.active_provider = Some(provider);
                 }
             }
+            self.split_entries = self.agent_entries.clone();
         }
         self.autoscroll = true;
         self.scroll = self.scroll_max();
@@ -1907,24 +2735,55 @@ impl App {
         self.last_status = format!("dispatching {}", run_target);
         self.clear_input_buffer();
 
-        let line_for_worker = if line.starts_with("/") {
-            line.clone()
-        } else {
-            self.build_contextual_prompt(&line)
-        };
+        let prompts = self.agent_prompts(&line, &providers, current);
+        self.spawn_worker(line, prompts, dispatch_target);
+    }
+
+    /// Per-agent prompts for `line`. `current` names the transcript entry and memory row
+    /// already holding `line`, so a preview and the real dispatch see the same context.
+    fn agent_prompts(
+        &self,
+        line: &str,
+        providers: &[Provider],
+        current: CurrentTurn,
+    ) -> HashMap<Provider, String> {
+        providers
+            .iter()
+            .map(|provider| {
+                (
+                    *provider,
+                    self.build_contextual_prompt(line, *provider, current),
+                )
+            })
+            .collect()
+    }
 
+    /// Run `line` on a worker thread; agents use their entry in `prompts` when present.
+    fn spawn_worker(
+        &mut self,
+        line: String,
+        prompts: HashMap<Provider, String>,
+        dispatch_target: DispatchTarget,
+    ) {
+        #[cfg(test)]
+        {
+            self.spawned_prompts = Some(prompts.clone());
+        }
+        if cfg!(test) {
+            return;
+        }
         let provider = self.primary_provider;
         let available = self.available_providers.clone();
         let child_pids: Arc<Mutex<Vec<u32>>> = Arc::new(Mutex::new(Vec::new()));
         self.child_pids = child_pids.clone();
         let (tx, rx) = unbounded::<WorkerEvent>();
-        let dispatch_target_for_worker = dispatch_target.clone();
         std::thread::spawn(move || {
             execute_line(
                 provider,
                 available,
-                line_for_worker,
-                dispatch_target_for_worker,
+                line,
+                prompts,
+                dispatch_target,
                 tx,
                 child_pids,
             )
@@ -1971,39 +2830,62 @@ impl App {
 
     fn handle_theme_change(&mut self, target: &str) {
         if target.is_empty() {
-            self.push_entry(
-                EntryKind::System,
-                format!(
-                    "theme: {} | options: fjord, graphite, solarized, aurora, ember",
-                    self.theme.as_str()
-                ),
+            let presets = ThemePreset::all().map(|preset| preset.as_str()).join(", ");
+            let mut lines = vec![
+                format!("theme: {}", self.theme_name()),
+                format!("  presets: {presets}"),
+            ];
+            let custom = self
+                .themes
+                .themes()
+                .iter()
+                .map(|theme| theme.name.as_str())
+                .collect::<Vec<_>>();
+            if !custom.is_empty() {
+                lines.push(format!("  custom: {}", custom.join(", ")));
+            }
+            lines.push(format!(
+                "  theme files: {}",
+                crate::config::themes_dir().join("<name>.toml").display()
+            ));
+            lines.extend(
+                self.themes
+                    .warnings()
+                    .iter()
+                    .map(|warning| format!("  ! {warning}")),
             );
+            self.push_entry(EntryKind::System, lines.join("\n"));
             return;
         }
-        let Some(theme) = ThemePreset::parse(target) else {
+        if let Some(theme) = ThemePreset::parse(target) {
+            self.theme = theme;
+            self.custom_theme = None;
+        } else if let Some(theme) = self.themes.get(&target.to_lowercase()) {
+            self.custom_theme = Some(theme.name.clone());
+        } else {
             self.push_entry(
                 EntryKind::Error,
-                "usage: /theme [fjord|graphite|solarized|aurora|ember]",
+                format!("unknown theme {target}; /theme lists presets and theme files"),
             );
             return;
-        };
-        self.theme = theme;
+        }
+        self.theme_chosen = true;
         self.invalidate_render_cache();
-        self.last_status = format!("theme {}", self.theme.as_str());
+        self.last_status = format!("theme {}", self.theme_name());
         self.push_entry(
             EntryKind::System,
-            format!("theme set to {}", self.theme.as_str()),
+            format!("theme set to {}", self.theme_name()),
         );
     }
 
-    fn handle_memory_command(&mut self, args: &str) {
+    fn handle_session_command(&mut self, args: &str) {
         let usage = [
-            "memory commands",
-            "  /mem                     show summary",
-            "  /mem show [n]            show latest n records (default 20)",
-            "  /mem find <query>        search memory in this session",
-            "  /mem prune [keep]        keep latest N records (default 200)",
-            "  /mem clear               clear memory only (keep transcript)",
+            "session commands",
+            "  /session                 list sessions",
+            "  /session new <name>      create and switch to a session",
+            "  /session switch <name>   switch transcript, history and memory",
+            "  /session rename <title>  rename the current session",
+            "  /session delete <name>   delete a session and its memory",
         ]
         .join("\n");
 
@@ -2013,186 +2895,1117 @@ impl App {
             return;
         };
 
-        let mut parts = args.split_whitespace();
-        let sub = parts.next().unwrap_or("");
+        let (sub, rest) = match args.split_once(char::is_whitespace) {
+            Some((sub, rest)) => (sub, rest.trim()),
+            None => (args, ""),
+        };
 
-        if sub.is_empty() || sub == "help" {
-            match memory.session_message_count(&self.session_id) {
-                Ok(count) => {
+        match sub {
+            "" | "list" | "ls" => match memory.list_sessions() {
+                Ok(sessions) => {
+                    let now = unix_now();
+                    let mut lines = Vec::new();
+                    for info in &sessions {
+                        let marker = if info.id == self.session_id { "*" } else { " " };
+                        let title = if info.title == info.id {
+                            String::new()
+                        } else {
+                            format!(" \"{}\"", info.title)
+                        };
+                        lines.push(format!(
+                            "{} {}{} | {} msgs | created {} | updated {}",
+                            marker,
+                            info.id,
+                            title,
+                            info.message_count,
+                            format_age(now - info.created_at),
+                            format_age(now - info.updated_at)
+                        ));
+                    }
+                    if sub.is_empty() {
+                        lines.push(String::new());
+                        lines.push(usage);
+                    }
                     self.push_entry(
                         EntryKind::System,
-                        format!("session memory: {} records\n{}", count, usage),
+                        format!("sessions ({}):\n{}", sessions.len(), lines.join("\n")),
                     );
-                    self.last_status = format!("memory {} records", count);
+                    self.last_status = format!("session {}", self.session_id);
                 }
                 Err(err) => {
                     self.push_entry(
                         EntryKind::Error,
-                        format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                        format!("session list failed: {}", truncate(&err.to_string(), 80)),
                     );
                     self.last_status = "memory error".to_string();
                 }
-            }
-            return;
-        }
-
-        match sub {
-            "show" => {
-                let limit = match parts.next() {
-                    Some(raw) => match raw.parse::<usize>() {
-                        Ok(v) if v > 0 => v.min(MEM_SHOW_MAX_LIMIT),
-                        _ => {
-                            self.push_entry(EntryKind::Error, "usage: /mem show [positive-number]");
-                            self.last_status = "memory usage".to_string();
-                            return;
-                        }
-                    },
-                    None => MEM_SHOW_DEFAULT_LIMIT,
-                };
-                if parts.next().is_some() {
-                    self.push_entry(EntryKind::Error, "usage: /mem show [n]");
-                    self.last_status = "memory usage".to_string();
+            },
+            "new" => {
+                let Some(id) = slugify(rest) else {
+                    self.push_entry(EntryKind::Error, "usage: /session new <name>");
                     return;
-                }
-
-                match memory.list_session_lines(&self.session_id, limit) {
-                    Ok(lines) => {
-                        if lines.is_empty() {
-                            self.push_entry(EntryKind::System, "memory is empty");
-                            self.last_status = "memory empty".to_string();
-                        } else {
-                            self.push_entry(
-                                EntryKind::System,
-                                format!("memory (latest {}):\n{}", lines.len(), lines.join("\n")),
-                            );
-                            self.last_status = format!("memory show {}", lines.len());
-                        }
+                };
+                match memory.create_session(&id, rest) {
+                    Ok(true) => self.switch_session(id),
+                    Ok(false) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("session {} already exists; use /session switch", id),
+                        );
                     }
                     Err(err) => {
                         self.push_entry(
                             EntryKind::Error,
-                            format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                            format!("session create failed: {}", truncate(&err.to_string(), 80)),
                         );
-                        self.last_status = "memory error".to_string();
                     }
                 }
             }
-            "find" => {
-                let query = args.strip_prefix("find").map(str::trim).unwrap_or_default();
-                if query.is_empty() {
-                    self.push_entry(EntryKind::Error, "usage: /mem find <query>");
-                    self.last_status = "memory usage".to_string();
+            "switch" | "use" => {
+                if rest.is_empty() {
+                    self.push_entry(EntryKind::Error, "usage: /session switch <name>");
                     return;
                 }
-
-                match memory.search_session_lines(&self.session_id, query, MEM_FIND_DEFAULT_LIMIT) {
-                    Ok(lines) => {
-                        if lines.is_empty() {
-                            self.push_entry(EntryKind::System, "memory search: no match");
-                            self.last_status = "memory no match".to_string();
-                        } else {
-                            self.push_entry(
-                                EntryKind::System,
-                                format!(
-                                    "memory search results ({}):\n{}",
-                                    lines.len(),
-                                    lines.join("\n")
-                                ),
-                            );
-                            self.last_status = format!("memory match {}", lines.len());
-                        }
+                match memory.find_session(rest) {
+                    Ok(Some(info)) => self.switch_session(info.id),
+                    Ok(None) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("unknown session {}; use /session new {}", rest, rest),
+                        );
                     }
                     Err(err) => {
                         self.push_entry(
                             EntryKind::Error,
-                            format!("memory search failed: {}", truncate(&err.to_string(), 80)),
+                            format!("session lookup failed: {}", truncate(&err.to_string(), 80)),
                         );
-                        self.last_status = "memory error".to_string();
                     }
                 }
             }
-            "prune" | "trim" => {
-                let keep = match parts.next() {
-                    Some(raw) => match raw.parse::<usize>() {
-                        Ok(v) => v,
-                        Err(_) => {
-                            self.push_entry(
-                                EntryKind::Error,
-                                "usage: /mem prune [non-negative-number]",
-                            );
-                            self.last_status = "memory usage".to_string();
-                            return;
-                        }
-                    },
-                    None => MEM_PRUNE_DEFAULT_KEEP,
-                };
-                if parts.next().is_some() {
-                    self.push_entry(EntryKind::Error, "usage: /mem prune [keep]");
-                    self.last_status = "memory usage".to_string();
+            "rename" => {
+                if rest.is_empty() {
+                    self.push_entry(EntryKind::Error, "usage: /session rename <title>");
                     return;
                 }
-
-                let prune_result = memory.prune_session_keep_recent(&self.session_id, keep);
-                let count_result = memory.session_message_count(&self.session_id);
-                match (prune_result, count_result) {
-                    (Ok(removed), Ok(remaining)) => {
+                memory.ensure_session(&self.session_id).ok();
+                match memory.rename_session(&self.session_id, rest) {
+                    Ok(_) => {
                         self.push_entry(
                             EntryKind::System,
-                            format!(
-                                "memory pruned: removed {}, remaining {}",
-                                removed, remaining
-                            ),
+                            format!("session {} renamed to \"{}\"", self.session_id, rest),
                         );
-                        self.last_status = format!("memory pruned {}", removed);
                     }
-                    (Err(err), _) | (_, Err(err)) => {
+                    Err(err) => {
                         self.push_entry(
                             EntryKind::Error,
-                            format!("memory prune failed: {}", truncate(&err.to_string(), 80)),
+                            format!("session rename failed: {}", truncate(&err.to_string(), 80)),
                         );
-                        self.last_status = "memory error".to_string();
                     }
                 }
             }
-            "clear" => match memory.clear_session(&self.session_id) {
-                Ok(()) => {
-                    self.push_entry(EntryKind::System, "memory cleared for current session");
-                    self.last_status = "memory cleared".to_string();
+            "delete" | "rm" => {
+                if rest.is_empty() {
+                    self.push_entry(EntryKind::Error, "usage: /session delete <name>");
+                    return;
                 }
-                Err(err) => {
+                let info = match memory.find_session(rest) {
+                    Ok(Some(info)) => info,
+                    Ok(None) => {
+                        self.push_entry(EntryKind::Error, format!("unknown session {}", rest));
+                        return;
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("session lookup failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        return;
+                    }
+                };
+                if info.id == self.session_id {
                     self.push_entry(
                         EntryKind::Error,
-                        format!("memory clear failed: {}", truncate(&err.to_string(), 80)),
+                        "cannot delete the active session; switch first",
                     );
-                    self.last_status = "memory error".to_string();
+                    return;
                 }
-            },
+                match memory.delete_session(&info.id) {
+                    Ok(removed) => {
+                        let _ = fs::remove_file(Self::session_transcript_path(&info.id));
+                        self.push_entry(
+                            EntryKind::System,
+                            format!("session {} deleted ({} records)", info.id, removed),
+                        );
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("session delete failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                    }
+                }
+            }
             _ => {
-                self.push_entry(EntryKind::Error, "usage: /mem [show|find|prune|clear]");
-                self.last_status = "memory usage".to_string();
+                self.push_entry(
+                    EntryKind::Error,
+                    "usage: /session [list|new|switch|rename|delete]",
+                );
             }
         }
     }
 
-    fn filtered_history(&self) -> Vec<String> {
-        let q = self.history_query.to_lowercase();
-        if q.trim().is_empty() {
-            return self.history.iter().rev().take(14).cloned().collect();
+    fn start_compaction(&mut self, batch: CompactionBatch, provider: Provider) {
+        if !self.available_providers.contains(&provider) {
+            self.push_entry(
+                EntryKind::Error,
+                format!("{} not available on PATH", provider.as_str()),
+            );
+            return;
         }
-        self.history
-            .iter()
-            .rev()
-            .filter(|h| h.to_lowercase().contains(&q))
-            .take(14)
-            .cloned()
-            .collect()
+
+        self.push_entry(
+            EntryKind::System,
+            format!(
+                "compacting {} memory records with {}",
+                batch.count,
+                provider.as_str()
+            ),
+        );
+        self.assistant_idx = None;
+        self.agent_entries.clear();
+        self.agent_had_chunk.clear();
+        self.clear_split_columns();
+        self.push_entry(
+            EntryKind::Assistant,
+            format!("[{}]\n{}", provider.as_str(), WORKING_PLACEHOLDER),
+        );
+        self.agent_entries.insert(provider, self.entries.len() - 1);
+        self.agent_had_chunk.insert(provider, false);
+        self.active_provider = Some(provider);
+        self.autoscroll = true;
+        self.scroll = self.scroll_max();
+        self.stream_had_chunk = false;
+        self.start_running_state("compact".to_string());
+        self.last_tool_event.clear();
+        self.last_status = "compacting memory".to_string();
+
+        let prompt = batch.prompt();
+        self.pending_compaction = Some(PendingCompaction { provider, batch });
+        self.spawn_worker(prompt, HashMap::new(), DispatchTarget::Provider(provider));
     }
 
-    fn slash_hints(&self) -> Vec<String> {
-        if !self.input.starts_with('/') {
-            return Vec::new();
-        }
-        let query = self.input.trim();
+    fn finish_compaction(&mut self, pending: PendingCompaction, reply: Option<&str>) {
+        let failed_prefix = format!("{} error:", pending.provider.as_str());
+        let Some(summary) = reply.filter(|text| !text.starts_with(&failed_prefix)) else {
+            self.push_entry(
+                EntryKind::Error,
+                "memory compact: no summary returned, nothing archived",
+            );
+            self.last_status = "memory compact failed".to_string();
+            return;
+        };
+        let Some(memory) = &self.memory else {
+            return;
+        };
+        match memory.store_summary(&pending.batch, summary) {
+            Ok(archived) => {
+                self.push_entry(
+                    EntryKind::System,
+                    format!(
+                        "memory compacted: {} records archived into a summary",
+                        archived
+                    ),
+                );
+                self.last_status = format!("memory compacted {}", archived);
+            }
+            Err(err) => {
+                self.push_entry(
+                    EntryKind::Error,
+                    format!("memory compact failed: {}", truncate(&err.to_string(), 80)),
+                );
+                self.last_status = "memory error".to_string();
+            }
+        }
+    }
+
+    /// `/context <prompt>`: build the agent prompts as `submit_current_line` would, without sending.
+    fn open_context_preview(&mut self, raw: &str) {
+        let raw = self.consume_pending_pastes(raw);
+        let (target, prompt) = match parse_dispatch_override(&raw) {
+            Ok(Some((target, prompt))) => (target, prompt),
+            Ok(None) => (DispatchTarget::Primary, raw.clone()),
+            Err(err) => {
+                self.push_entry(EntryKind::Error, err);
+                return;
+            }
+        };
+
+        let mut lines = Vec::new();
+        let mut providers =
+            resolve_dispatch_providers(self.primary_provider, &self.available_providers, &target);
+        if providers.is_empty() {
+            lines.push(PreviewLine::Meta(
+                "note: requested agents are not on PATH; showing the primary agent".to_string(),
+            ));
+            providers.push(self.primary_provider);
+        }
+
+        let prompts = self.agent_prompts(&prompt, &providers, CurrentTurn::default());
+        for provider in providers {
+            let budget = self.context_budget(provider);
+            let text = prompts.get(&provider).cloned().unwrap_or_default();
+            if !lines.is_empty() {
+                lines.push(PreviewLine::Body(String::new()));
+            }
+            lines.push(PreviewLine::Heading(format!(
+                "{} · {} tok ({} memory budget)",
+                provider.as_str(),
+                estimate_tokens(&text),
+                budget.max_tokens
+            )));
+
+            let plan = self.memory.as_ref().map(|memory| {
+                memory.plan_context(
+                    &self.session_id,
+                    &prompt,
+                    Some(provider.as_str()),
+                    None,
+                    &budget,
+                )
+            });
+            match plan {
+                Some(Ok(plan)) => {
+                    if !plan.pins.is_empty() {
+                        lines.push(PreviewLine::Meta(format!(
+                            "pins: {} ({} tok)",
+                            plan.pins.len(),
+                            plan.pin_tokens()
+                        )));
+                    }
+                    if plan.items.is_empty() {
+                        lines.push(PreviewLine::Meta("memory hits: none".to_string()));
+                    }
+                    for item in &plan.items {
+                        let score = item
+                            .score
+                            .map(|score| format!(" score {:.2}", score))
+                            .unwrap_or_default();
+                        lines.push(PreviewLine::Meta(format!(
+                            "{:<7} #{:<5} {:>4} tok{}  {}",
+                            item.source.as_str(),
+                            item.id,
+                            item.tokens,
+                            score,
+                            truncate(&item.text.replace('\n', " "), 60)
+                        )));
+                    }
+                }
+                Some(Err(err)) => lines.push(PreviewLine::Meta(format!(
+                    "memory read failed, transcript fallback used: {}",
+                    truncate(&err.to_string(), 60)
+                ))),
+                None => lines.push(PreviewLine::Meta(
+                    "memory unavailable, transcript fallback used".to_string(),
+                )),
+            }
+            lines.push(PreviewLine::Body(String::new()));
+            lines.extend(text.lines().map(|line| PreviewLine::Body(line.to_string())));
+        }
+
+        self.context_preview = Some(ContextPreview { lines, scroll: 0 });
+        self.mode = Mode::ContextPreview;
+        self.last_status = "context preview".to_string();
+    }
+
+    fn handle_context_preview_key(&mut self, key: KeyEvent) {
+        let Some(preview) = self.context_preview.as_mut() else {
+            self.mode = Mode::Normal;
+            return;
+        };
+        let page = self.viewport_height.saturating_sub(4).max(1);
+        let max_scroll = preview.lines.len().saturating_sub(1) as u16;
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
+                self.context_preview = None;
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => preview.scroll = preview.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                preview.scroll = (preview.scroll + 1).min(max_scroll)
+            }
+            KeyCode::PageUp => preview.scroll = preview.scroll.saturating_sub(page),
+            KeyCode::PageDown => preview.scroll = (preview.scroll + page).min(max_scroll),
+            KeyCode::Home => preview.scroll = 0,
+            KeyCode::End => preview.scroll = max_scroll,
+            _ => {}
+        }
+    }
+
+    /// `/context`: token budget and what the next prompt would carry, per agent.
+    fn handle_context_command(&mut self, args: &str) {
+        if !args.is_empty() {
+            self.open_context_preview(args);
+            return;
+        }
+
+        let sample = self
+            .history
+            .iter()
+            .rev()
+            .find(|line| !line.starts_with('/'))
+            .cloned()
+            .unwrap_or_default();
+        let providers = if self.available_providers.is_empty() {
+            vec![self.primary_provider]
+        } else {
+            self.available_providers.clone()
+        };
+
+        let mut out = vec![if sample.is_empty() {
+            "context budget (no prompt yet)".to_string()
+        } else {
+            format!("context budget for last prompt: {}", truncate(&sample, 60))
+        }];
+        for provider in providers {
+            let budget = self.context_budget(provider);
+            let model = self
+                .context_config
+                .model(provider.as_str())
+                .unwrap_or("default model");
+            out.push(String::new());
+            out.push(format!(
+                "{} ({}): {} memory tokens, {} recent, {} search",
+                provider.as_str(),
+                model,
+                budget.max_tokens,
+                budget.recent,
+                budget.search
+            ));
+
+            let prompt_tokens = estimate_tokens(&sample);
+            let plan = self.memory.as_ref().map(|memory| {
+                memory.plan_context(
+                    &self.session_id,
+                    &sample,
+                    Some(provider.as_str()),
+                    None,
+                    &budget,
+                )
+            });
+            match plan {
+                Some(Ok(plan)) => {
+                    let rows = [
+                        ("summaries", ContextSource::Summary),
+                        ("recent", ContextSource::Recent),
+                        ("search", ContextSource::Search),
+                    ];
+                    let pin_tokens = plan.pin_tokens();
+                    out.push(format!(
+                        "  {:<10} {:>3} items {:>6} tok  (outside budget)",
+                        "pins",
+                        plan.pins.len(),
+                        pin_tokens
+                    ));
+                    let mut total = pin_tokens + prompt_tokens;
+                    for (label, source) in rows {
+                        let (count, tokens) = plan.usage(source);
+                        total += tokens;
+                        out.push(format!(
+                            "  {:<10} {:>3} items {:>6} tok",
+                            label, count, tokens
+                        ));
+                    }
+                    out.push(format!("  {:<10} {:>16} tok", "prompt", prompt_tokens));
+                    out.push(format!("  {:<10} {:>16} tok", "total", total));
+                }
+                Some(Err(err)) => {
+                    out.push(format!(
+                        "  memory read failed: {}",
+                        truncate(&err.to_string(), 80)
+                    ));
+                }
+                None => {
+                    let lines = self.transcript_context_lines(&budget, None);
+                    let tokens = lines
+                        .iter()
+                        .map(|line| estimate_tokens(line))
+                        .sum::<usize>();
+                    out.push(format!(
+                        "  {:<10} {:>3} items {:>6} tok  (memory unavailable, limit {})",
+                        "transcript",
+                        lines.len(),
+                        tokens,
+                        budget.transcript_tokens
+                    ));
+                    out.push(format!("  {:<10} {:>16} tok", "prompt", prompt_tokens));
+                    out.push(format!(
+                        "  {:<10} {:>16} tok",
+                        "total",
+                        tokens + prompt_tokens
+                    ));
+                }
+            }
+        }
+        self.push_entry(EntryKind::System, out.join("\n"));
+        self.last_status = "context budget".to_string();
+    }
+
+    /// Enforce `[memory.retention]` at startup and then every `RETENTION_INTERVAL`.
+    /// Returns true when it added a transcript entry.
+    fn apply_retention_if_due(&mut self) -> bool {
+        if !self.retention.is_enabled()
+            || self
+                .last_retention
+                .is_some_and(|(at, _)| at.elapsed() < RETENTION_INTERVAL)
+        {
+            return false;
+        }
+        let Some(memory) = self.memory.as_ref() else {
+            return false;
+        };
+        match memory.apply_retention(&self.retention) {
+            Ok(report) => {
+                self.last_retention = Some((Instant::now(), report));
+                if report.total() == 0 && !report.size_unreachable {
+                    return false;
+                }
+                if report.total() > 0 {
+                    self.push_entry(
+                        EntryKind::System,
+                        format!(
+                            "memory retention removed {} records ({})",
+                            report.total(),
+                            retention_breakdown(&report)
+                        ),
+                    );
+                }
+                if report.size_unreachable {
+                    self.push_entry(
+                        EntryKind::System,
+                        "memory db is over max_db_mb even without messages, archives and \
+                         run events; nothing was deleted for the size limit"
+                            .to_string(),
+                    );
+                }
+            }
+            Err(err) => {
+                self.last_retention = Some((Instant::now(), RetentionReport::default()));
+                self.push_entry(
+                    EntryKind::System,
+                    format!(
+                        "memory retention failed: {}",
+                        truncate(&err.to_string(), 80)
+                    ),
+                );
+            }
+        }
+        true
+    }
+
+    fn retention_summary(&self) -> String {
+        let mut out = format!("retention: {}", self.retention.describe());
+        if let Some((at, report)) = &self.last_retention {
+            out.push_str(&format!(
+                "\nlast retention pass {}: {}",
+                format_age(at.elapsed().as_secs() as i64),
+                retention_breakdown(report)
+            ));
+        }
+        out
+    }
+
+    fn handle_memory_command(&mut self, args: &str) {
+        let usage = [
+            "memory commands",
+            "  /mem                     show summary",
+            "  /mem show [n]            show latest n records (default 20)",
+            "  /mem find <query> [--all] [--agent a] [--role r] [--since 7d]",
+            "  /mem use <id>            add a search hit to the prompt",
+            "  /mem prune [keep]        keep latest N records (default 200)",
+            "  /mem compact [keep] [agent]  summarize older records (keep latest 20)",
+            "  /mem clear               clear memory only (keep transcript)",
+            "  /mem pin <text>          always include a fact in agent context",
+            "  /mem pins                list pinned facts",
+            "  /mem unpin <id>          remove a pinned fact",
+            "  /mem export <path> [--format jsonl|md] [--all]",
+            "  /mem import <path> [--session <name>]",
+            "  /mem visibility [shared|private|split]  what agents see of each other",
+            "  /mem stats               row counts, db size, oldest record",
+            "  /mem vacuum              reclaim free space in the db file",
+        ]
+        .join("\n");
+
+        let Some(memory) = self.memory.as_ref() else {
+            self.push_entry(EntryKind::Error, "memory backend unavailable");
+            self.last_status = "memory unavailable".to_string();
+            return;
+        };
+
+        let mut parts = args.split_whitespace();
+        let sub = parts.next().unwrap_or("");
+
+        if sub.is_empty() || sub == "help" {
+            match memory.session_message_count(&self.session_id) {
+                Ok(count) => {
+                    self.push_entry(
+                        EntryKind::System,
+                        format!(
+                            "session memory: {} records\n{}\n{}",
+                            count,
+                            self.retention_summary(),
+                            usage
+                        ),
+                    );
+                    self.last_status = format!("memory {} records", count);
+                }
+                Err(err) => {
+                    self.push_entry(
+                        EntryKind::Error,
+                        format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                    );
+                    self.last_status = "memory error".to_string();
+                }
+            }
+            return;
+        }
+
+        match sub {
+            "show" => {
+                let limit = match parts.next() {
+                    Some(raw) => match raw.parse::<usize>() {
+                        Ok(v) if v > 0 => v.min(MEM_SHOW_MAX_LIMIT),
+                        _ => {
+                            self.push_entry(EntryKind::Error, "usage: /mem show [positive-number]");
+                            self.last_status = "memory usage".to_string();
+                            return;
+                        }
+                    },
+                    None => MEM_SHOW_DEFAULT_LIMIT,
+                };
+                if parts.next().is_some() {
+                    self.push_entry(EntryKind::Error, "usage: /mem show [n]");
+                    self.last_status = "memory usage".to_string();
+                    return;
+                }
+
+                match memory.list_session_lines(&self.session_id, limit) {
+                    Ok(lines) => {
+                        if lines.is_empty() {
+                            self.push_entry(EntryKind::System, "memory is empty");
+                            self.last_status = "memory empty".to_string();
+                        } else {
+                            self.push_entry(
+                                EntryKind::System,
+                                format!("memory (latest {}):\n{}", lines.len(), lines.join("\n")),
+                            );
+                            self.last_status = format!("memory show {}", lines.len());
+                        }
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "find" => {
+                let tokens = parts.collect::<Vec<_>>();
+                let (filter, query) = match SearchFilter::parse(&tokens) {
+                    Ok(parsed) => parsed,
+                    Err(err) => {
+                        self.push_entry(EntryKind::Error, format!("{err}; {MEM_FIND_USAGE}"));
+                        self.last_status = "memory usage".to_string();
+                        return;
+                    }
+                };
+                if tokens.is_empty() {
+                    self.push_entry(EntryKind::Error, MEM_FIND_USAGE);
+                    self.last_status = "memory usage".to_string();
+                    return;
+                }
+
+                let found = if filter.events {
+                    memory.search_run_event_lines(
+                        &self.session_id,
+                        &query,
+                        &filter,
+                        MEM_FIND_DEFAULT_LIMIT,
+                    )
+                } else {
+                    memory.search_session_lines(
+                        &self.session_id,
+                        &query,
+                        &filter,
+                        MEM_FIND_DEFAULT_LIMIT,
+                    )
+                };
+                match found {
+                    Ok(lines) => {
+                        if lines.is_empty() {
+                            self.push_entry(EntryKind::System, "memory search: no match");
+                            self.last_status = "memory no match".to_string();
+                        } else if filter.events {
+                            self.push_entry(
+                                EntryKind::System,
+                                format!(
+                                    "agent activity results ({}):\n{}",
+                                    lines.len(),
+                                    lines.join("\n")
+                                ),
+                            );
+                            self.last_status = format!("memory match {}", lines.len());
+                        } else {
+                            self.push_entry(
+                                EntryKind::System,
+                                format!(
+                                    "memory search results ({}):\n{}\nuse /mem use <id> to add a hit to the prompt",
+                                    lines.len(),
+                                    lines.join("\n")
+                                ),
+                            );
+                            self.last_status = format!("memory match {}", lines.len());
+                        }
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("memory search failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "prune" | "trim" => {
+                let keep = match parts.next() {
+                    Some(raw) => match raw.parse::<usize>() {
+                        Ok(v) => v,
+                        Err(_) => {
+                            self.push_entry(
+                                EntryKind::Error,
+                                "usage: /mem prune [non-negative-number]",
+                            );
+                            self.last_status = "memory usage".to_string();
+                            return;
+                        }
+                    },
+                    None => MEM_PRUNE_DEFAULT_KEEP,
+                };
+                if parts.next().is_some() {
+                    self.push_entry(EntryKind::Error, "usage: /mem prune [keep]");
+                    self.last_status = "memory usage".to_string();
+                    return;
+                }
+
+                let prune_result = memory.prune_session_keep_recent(&self.session_id, keep);
+                let count_result = memory.session_message_count(&self.session_id);
+                match (prune_result, count_result) {
+                    (Ok(removed), Ok(remaining)) => {
+                        self.push_entry(
+                            EntryKind::System,
+                            format!(
+                                "memory pruned: removed {}, remaining {}",
+                                removed, remaining
+                            ),
+                        );
+                        self.last_status = format!("memory pruned {}", removed);
+                    }
+                    (Err(err), _) | (_, Err(err)) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("memory prune failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "use" => {
+                let id = parts
+                    .next()
+                    .map(|raw| raw.trim_start_matches('#'))
+                    .and_then(|raw| raw.parse::<i64>().ok());
+                let Some(id) = id.filter(|_| parts.next().is_none()) else {
+                    self.push_entry(EntryKind::Error, "usage: /mem use <id>");
+                    self.last_status = "memory usage".to_string();
+                    return;
+                };
+                match memory.message_by_id(id) {
+                    Ok(Some((label, content))) => {
+                        let marker = format!("[Memory #{} {}]", id, label);
+                        self.pending_pastes.push(PendingPaste {
+                            marker: marker.clone(),
+                            content: format!(
+                                "Earlier {} message (memory #{}):\n{}\n",
+                                label, id, content
+                            ),
+                        });
+                        self.insert_str(&marker);
+                        self.insert_char(' ');
+                        self.last_status = format!("memory #{} added to prompt", id);
+                    }
+                    Ok(None) => {
+                        self.push_entry(EntryKind::Error, format!("no memory record #{}", id));
+                        self.last_status = "memory usage".to_string();
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "pin" => {
+                let text = args.strip_prefix("pin").map(str::trim).unwrap_or_default();
+                if text.is_empty() {
+                    self.push_entry(EntryKind::Error, "usage: /mem pin <text>");
+                    self.last_status = "memory usage".to_string();
+                    return;
+                }
+                match memory.add_pin(&self.session_id, text) {
+                    Ok(id) => {
+                        self.push_entry(EntryKind::System, format!("pinned #{}: {}", id, text));
+                        self.last_status = format!("pinned #{}", id);
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("pin failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "visibility" => {
+                let Some(raw) = parts.next() else {
+                    match memory.session_visibility(&self.session_id) {
+                        Ok(visibility) => {
+                            self.push_entry(
+                                EntryKind::System,
+                                format!(
+                                    "memory visibility: {}\n  shared   every agent sees every turn\n  private  agents see only their own turns\n  split    agents see all user turns, only their own replies",
+                                    visibility.as_str()
+                                ),
+                            );
+                            self.last_status = format!("visibility {}", visibility.as_str());
+                        }
+                        Err(err) => {
+                            self.push_entry(
+                                EntryKind::Error,
+                                format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                            );
+                            self.last_status = "memory error".to_string();
+                        }
+                    }
+                    return;
+                };
+                let Some(visibility) = Visibility::parse(raw).filter(|_| parts.next().is_none())
+                else {
+                    self.push_entry(
+                        EntryKind::Error,
+                        "usage: /mem visibility [shared|private|split]",
+                    );
+                    self.last_status = "memory usage".to_string();
+                    return;
+                };
+                match memory.set_session_visibility(&self.session_id, visibility) {
+                    Ok(()) => {
+                        self.push_entry(
+                            EntryKind::System,
+                            format!(
+                                "memory visibility set to {} (applies to new turns)",
+                                visibility.as_str()
+                            ),
+                        );
+                        self.last_status = format!("visibility {}", visibility.as_str());
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!(
+                                "visibility update failed: {}",
+                                truncate(&err.to_string(), 80)
+                            ),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "stats" => match memory.stats() {
+                Ok(stats) => {
+                    let oldest = stats
+                        .oldest
+                        .map(format_timestamp)
+                        .unwrap_or_else(|| "none".to_string());
+                    self.push_entry(
+                        EntryKind::System,
+                        format!(
+                            "memory stats\n  sessions   {}\n  messages   {}\n  archived   {} ({} summaries)\n  pins       {}\n  run events {}\n  db size    {} ({} free)\n  oldest     {}\n{}",
+                            stats.sessions,
+                            stats.messages,
+                            stats.archived,
+                            stats.summaries,
+                            stats.pins,
+                            stats.run_events,
+                            format_bytes(stats.db_bytes),
+                            format_bytes(stats.free_bytes),
+                            oldest,
+                            self.retention_summary()
+                        ),
+                    );
+                    self.last_status = format!("memory {}", format_bytes(stats.db_bytes));
+                }
+                Err(err) => {
+                    self.push_entry(
+                        EntryKind::Error,
+                        format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                    );
+                    self.last_status = "memory error".to_string();
+                }
+            },
+            "vacuum" => match memory.vacuum() {
+                Ok((before, after)) => {
+                    self.push_entry(
+                        EntryKind::System,
+                        format!(
+                            "memory vacuumed: {} -> {}",
+                            format_bytes(before),
+                            format_bytes(after)
+                        ),
+                    );
+                    self.last_status = "memory vacuumed".to_string();
+                }
+                Err(err) => {
+                    self.push_entry(
+                        EntryKind::Error,
+                        format!("memory vacuum failed: {}", truncate(&err.to_string(), 80)),
+                    );
+                    self.last_status = "memory error".to_string();
+                }
+            },
+            "pins" => match memory.list_pins(&self.session_id) {
+                Ok(pins) if pins.is_empty() => {
+                    self.push_entry(EntryKind::System, "no pinned facts");
+                    self.last_status = "pins 0".to_string();
+                }
+                Ok(pins) => {
+                    let lines = pins
+                        .iter()
+                        .map(|pin| format!("#{} {}", pin.id, pin.content))
+                        .collect::<Vec<_>>();
+                    self.push_entry(
+                        EntryKind::System,
+                        format!("pinned facts ({}):\n{}", lines.len(), lines.join("\n")),
+                    );
+                    self.last_status = format!("pins {}", lines.len());
+                }
+                Err(err) => {
+                    self.push_entry(
+                        EntryKind::Error,
+                        format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                    );
+                    self.last_status = "memory error".to_string();
+                }
+            },
+            "unpin" => {
+                let id = parts
+                    .next()
+                    .map(|raw| raw.trim_start_matches('#'))
+                    .and_then(|raw| raw.parse::<i64>().ok());
+                let Some(id) = id.filter(|_| parts.next().is_none()) else {
+                    self.push_entry(EntryKind::Error, "usage: /mem unpin <id>");
+                    self.last_status = "memory usage".to_string();
+                    return;
+                };
+                match memory.remove_pin(&self.session_id, id) {
+                    Ok(true) => {
+                        self.push_entry(EntryKind::System, format!("unpinned #{}", id));
+                        self.last_status = format!("unpinned #{}", id);
+                    }
+                    Ok(false) => {
+                        self.push_entry(EntryKind::Error, format!("no pin #{}", id));
+                        self.last_status = "memory usage".to_string();
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("unpin failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "compact" => {
+                let mut keep = MEM_COMPACT_DEFAULT_KEEP;
+                let mut agent = None;
+                for token in parts {
+                    if let Some(provider) = provider_from_name(token) {
+                        agent = Some(provider);
+                        continue;
+                    }
+                    match token.parse::<usize>() {
+                        Ok(v) => keep = v,
+                        Err(_) => {
+                            self.push_entry(
+                                EntryKind::Error,
+                                "usage: /mem compact [non-negative-number] [claude|codex]",
+                            );
+                            self.last_status = "memory usage".to_string();
+                            return;
+                        }
+                    }
+                }
+                // Outside shared sessions each agent compacts, and later reads, only
+                // its own private rows.
+                let provider = agent.unwrap_or(self.primary_provider);
+                let batch = memory
+                    .session_visibility(&self.session_id)
+                    .and_then(|mode| {
+                        let owner = (mode != Visibility::Shared).then_some(provider.as_str());
+                        memory.compaction_batch(&self.session_id, keep, owner)
+                    });
+                match batch {
+                    Ok(Some(batch)) => self.start_compaction(batch, provider),
+                    Ok(None) => {
+                        self.push_entry(EntryKind::System, "memory compact: nothing to compact");
+                        self.last_status = "memory compact skipped".to_string();
+                    }
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!("memory read failed: {}", truncate(&err.to_string(), 80)),
+                        );
+                        self.last_status = "memory error".to_string();
+                    }
+                }
+            }
+            "export" | "import" => {
+                let tokens = parts.collect::<Vec<_>>();
+                let transfer = match TransferArgs::parse(&tokens) {
+                    Ok(transfer) => transfer,
+                    Err(err) => {
+                        self.push_entry(
+                            EntryKind::Error,
+                            format!(
+                                "{err}; usage: /mem export <path> [--format jsonl|md] [--all] | /mem import <path> [--session <name>]"
+                            ),
+                        );
+                        self.last_status = "memory usage".to_string();
+                        return;
+                    }
+                };
+                let session = match transfer.session.as_deref() {
+                    Some(name) => match memory.resolve_session_id(name) {
+                        Ok(Some(id)) => Some(id),
+                        Ok(None) => {
+                            self.push_entry(EntryKind::Error, format!("invalid session {name}"));
+                            return;
+                        }
+                        Err(err) => {
+                            self.push_entry(
+                                EntryKind::Error,
+                                format!(
+                                    "session lookup failed: {}",
+                                    truncate(&err.to_string(), 80)
+                                ),
+                            );
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let path = transfer.path.display().to_string();
+                if sub == "export" {
+                    let scope = if transfer.all_sessions {
+                        None
+                    } else {
+                        Some(session.as_deref().unwrap_or(&self.session_id))
+                    };
+                    match memory.export_to_file(&transfer.path, transfer.format, scope) {
+                        Ok(count) => {
+                            self.push_entry(
+                                EntryKind::System,
+                                format!("memory exported: {} records -> {}", count, path),
+                            );
+                            self.last_status = format!("memory exported {}", count);
+                        }
+                        Err(err) => {
+                            self.push_entry(
+                                EntryKind::Error,
+                                format!(
+                                    "memory export failed: {}",
+                                    truncate(&format!("{err:#}"), 120)
+                                ),
+                            );
+                            self.last_status = "memory error".to_string();
+                        }
+                    }
+                } else {
+                    match memory.import_from_file(&transfer.path, session.as_deref()) {
+                        Ok(count) => {
+                            self.push_entry(
+                                EntryKind::System,
+                                format!("memory imported: {} new records from {}", count, path),
+                            );
+                            self.last_status = format!("memory imported {}", count);
+                        }
+                        Err(err) => {
+                            self.push_entry(
+                                EntryKind::Error,
+                                format!(
+                                    "memory import failed: {}",
+                                    truncate(&format!("{err:#}"), 120)
+                                ),
+                            );
+                            self.last_status = "memory error".to_string();
+                        }
+                    }
+                }
+            }
+            "clear" => match memory.clear_session(&self.session_id) {
+                Ok(()) => {
+                    self.push_entry(EntryKind::System, "memory cleared for current session");
+                    self.last_status = "memory cleared".to_string();
+                }
+                Err(err) => {
+                    self.push_entry(
+                        EntryKind::Error,
+                        format!("memory clear failed: {}", truncate(&err.to_string(), 80)),
+                    );
+                    self.last_status = "memory error".to_string();
+                }
+            },
+            _ => {
+                self.push_entry(
+                    EntryKind::Error,
+                    "usage: /mem [show|find|use|prune|compact|clear|pin|pins|unpin|export|import|visibility|stats|vacuum]",
+                );
+                self.last_status = "memory usage".to_string();
+            }
+        }
+    }
+
+    fn filtered_history(&self) -> Vec<String> {
+        let q = self.history_query.to_lowercase();
+        if q.trim().is_empty() {
+            return self.history.iter().rev().take(14).cloned().collect();
+        }
+        self.history
+            .iter()
+            .rev()
+            .filter(|h| h.to_lowercase().contains(&q))
+            .take(14)
+            .cloned()
+            .collect()
+    }
+
+    fn slash_hints(&self) -> Vec<String> {
+        if !self.input.starts_with('/') {
+            return Vec::new();
+        }
+        let query = self.input.trim();
         let mut matches: Vec<String> = self
             .commands
             .iter()
@@ -2484,6 +4297,8 @@ impl App {
         match self.mode {
             Mode::Approval => self.handle_approval_key(key),
             Mode::HistorySearch => self.handle_history_key(key),
+            Mode::TranscriptSearch => self.handle_find_key(key),
+            Mode::ContextPreview => self.handle_context_preview_key(key),
             Mode::Normal => self.handle_normal_key(key),
         }
     }
@@ -2518,13 +4333,115 @@ impl App {
         }
     }
 
+    /// Styles for find hits and for the hit the view is on.
+    fn find_styles(&self) -> (Style, Style) {
+        (
+            Style::default().add_modifier(Modifier::REVERSED),
+            self.theme_palette().hint_selected_style(),
+        )
+    }
+
+    /// Start a transcript search, or run `query` straight away.
+    fn open_find(&mut self, query: &str) {
+        self.mode = Mode::TranscriptSearch;
+        self.find.query = query.to_string();
+        self.find.editing = query.is_empty();
+        self.refresh_find();
+    }
+
+    fn close_find(&mut self) {
+        self.mode = Mode::Normal;
+        self.find = find::TranscriptFind::default();
+        self.invalidate_render_cache();
+    }
+
+    /// Re-run the query after it changed and jump to the newest hit.
+    fn refresh_find(&mut self) {
+        self.find.current = usize::MAX;
+        self.invalidate_render_cache();
+        self.ensure_render_cache();
+        self.jump_to_find_hit();
+    }
+
+    fn step_find(&mut self, forward: bool) {
+        self.find.step(forward);
+        self.invalidate_render_cache();
+        self.jump_to_find_hit();
+    }
+
+    /// Scroll the full-screen transcript so the current hit sits in its
+    /// upper third. Inline mode shows the hit in the find panel instead.
+    fn jump_to_find_hit(&mut self) {
+        let Some(hit) = self.find.hits.get(self.find.current) else {
+            return;
+        };
+        if !self.fullscreen {
+            return;
+        }
+        let area = self.transcript_area.get();
+        let height = if area.is_empty() {
+            self.viewport_height / 2
+        } else {
+            area.height
+        };
+        let row = self.render_cache.line_row(hit.line());
+        self.autoscroll = false;
+        self.scroll = (row as u16).saturating_sub(height / 3);
+    }
+
+    /// Transcript lines around the current hit, highlighted, for the inline
+    /// find panel.
+    pub(super) fn find_preview_lines(&self, before: usize, count: usize) -> Vec<Line<'static>> {
+        let lines = self.cached_log_lines();
+        let Some(hit) = self.find.hits.get(self.find.current) else {
+            return Vec::new();
+        };
+        let first = hit.line().saturating_sub(before);
+        let end = (first + count).min(lines.len());
+        let mut preview = lines[first..end].to_vec();
+        let (hit_style, current) = self.find_styles();
+        find::highlight_hits(&mut preview, first, &self.find, hit_style, current);
+        preview
+    }
+
+    fn handle_find_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('f') => self.find.editing = true,
+                KeyCode::Char('c') => self.close_find(),
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => self.close_find(),
+            KeyCode::Down => self.step_find(true),
+            KeyCode::Up => self.step_find(false),
+            KeyCode::PageUp => self.scroll_up(5),
+            KeyCode::PageDown => self.scroll_down(5),
+            KeyCode::Enter if self.find.editing => self.find.editing = false,
+            KeyCode::Enter => self.close_find(),
+            KeyCode::Backspace if self.find.editing => {
+                self.find.query.pop();
+                self.refresh_find();
+            }
+            KeyCode::Char(c) if self.find.editing => {
+                self.find.query.push(c);
+                self.refresh_find();
+            }
+            KeyCode::Char('n') => self.step_find(true),
+            KeyCode::Char('N') => self.step_find(false),
+            KeyCode::Char('/') => self.find.editing = true,
+            KeyCode::Char('q') => self.close_find(),
+            _ => {}
+        }
+    }
+
     fn handle_history_key(&mut self, key: KeyEvent) {
         match key.code {
             KeyCode::Esc => self.mode = Mode::Normal,
-            KeyCode::Up => {
-                if self.history_idx > 0 {
-                    self.history_idx -= 1;
-                }
+            KeyCode::Up if self.history_idx > 0 => {
+                self.history_idx -= 1;
             }
             KeyCode::Down => {
                 let len = self.filtered_history().len();
@@ -2553,8 +4470,21 @@ impl App {
     }
 
     fn handle_normal_key(&mut self, key: KeyEvent) {
+        let ctrl_x = std::mem::take(&mut self.ctrl_x_pending);
         if key.modifiers.contains(KeyModifiers::CONTROL) {
             match key.code {
+                KeyCode::Char('e') if ctrl_x => {
+                    self.editor_requested = true;
+                    return;
+                }
+                KeyCode::Char('x') => {
+                    self.ctrl_x_pending = true;
+                    return;
+                }
+                KeyCode::Char('g') => {
+                    self.editor_requested = true;
+                    return;
+                }
                 KeyCode::Char('c') => {
                     self.interrupt_running_task("task interrupted (Ctrl+C)");
                     self.persist_session();
@@ -2563,6 +4493,7 @@ impl App {
                 }
                 KeyCode::Char('l') => {
                     self.entries.clear();
+                    self.clear_split_columns();
                     self.invalidate_render_cache();
                     self.last_status = "cleared".to_string();
                     return;
@@ -2585,27 +4516,69 @@ impl App {
                     self.history_idx = 0;
                     return;
                 }
-                KeyCode::Char('j') => {
-                    self.insert_char('\n');
+                KeyCode::Char('j') => {
+                    self.insert_char('\n');
+                    return;
+                }
+                KeyCode::Char('p') => {
+                    self.history_prev();
+                    return;
+                }
+                KeyCode::Char('t') => {
+                    let next = match self.agent_layout {
+                        AgentLayout::Split => AgentLayout::Stacked,
+                        AgentLayout::Stacked => AgentLayout::Split,
+                    };
+                    self.set_agent_layout(next);
+                    return;
+                }
+                KeyCode::Char('n') => {
+                    self.history_next();
+                    return;
+                }
+                KeyCode::Char('y') => {
+                    self.handle_copy_command("");
+                    return;
+                }
+                KeyCode::Char('f') => {
+                    self.open_find("");
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        if key.modifiers.contains(KeyModifiers::ALT) && matches!(key.code, KeyCode::Backspace) {
+            self.backspace_word();
+            return;
+        }
+        if key.modifiers.contains(KeyModifiers::ALT) && matches!(key.code, KeyCode::Char('y')) {
+            self.handle_copy_command("code");
+            return;
+        }
+
+        if self.split_view_active() {
+            match key.code {
+                KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => {
+                    self.move_split_focus(false);
+                    return;
+                }
+                KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => {
+                    self.move_split_focus(true);
                     return;
                 }
-                KeyCode::Char('p') => {
-                    self.history_prev();
+                KeyCode::PageUp => {
+                    self.scroll_split_column(true, 5);
                     return;
                 }
-                KeyCode::Char('n') => {
-                    self.history_next();
+                KeyCode::PageDown => {
+                    self.scroll_split_column(false, 5);
                     return;
                 }
                 _ => {}
             }
         }
 
-        if key.modifiers.contains(KeyModifiers::ALT) && matches!(key.code, KeyCode::Backspace) {
-            self.backspace_word();
-            return;
-        }
-
         match key.code {
             KeyCode::PageUp => {
                 self.scroll_up(5);
@@ -2664,35 +4637,32 @@ impl App {
             KeyCode::Right => self.move_right(),
             KeyCode::Home => self.cursor = 0,
             KeyCode::End => self.cursor = self.input.len(),
-            KeyCode::Esc => {
-                if self.running {
-                    // Kill all child processes
-                    if let Ok(pids) = self.child_pids.lock() {
-                        for &pid in pids.iter() {
-                            kill_pid(pid);
-                        }
+            KeyCode::Esc if self.running => {
+                // Kill all child processes
+                if let Ok(pids) = self.child_pids.lock() {
+                    for &pid in pids.iter() {
+                        kill_pid(pid);
                     }
-                    // Mark running entries as cancelled
-                    for (_provider, &idx) in self.agent_entries.iter() {
-                        if let Some(entry) = self.entries.get_mut(idx) {
-                            if entry.text.contains(WORKING_PLACEHOLDER) {
-                                entry.text =
-                                    entry.text.replacen(WORKING_PLACEHOLDER, "(cancelled)", 1);
-                            }
+                }
+                // Mark running entries as cancelled
+                for (_provider, &idx) in self.agent_entries.iter() {
+                    if let Some(entry) = self.entries.get_mut(idx) {
+                        if entry.text.contains(WORKING_PLACEHOLDER) {
+                            entry.text = entry.text.replacen(WORKING_PLACEHOLDER, "(cancelled)", 1);
                         }
                     }
-                    if let Some(idx) = self.assistant_idx {
-                        if let Some(entry) = self.entries.get_mut(idx) {
-                            if entry.text.trim() == WORKING_PLACEHOLDER {
-                                entry.text = "(cancelled)".to_string();
-                            }
+                }
+                if let Some(idx) = self.assistant_idx {
+                    if let Some(entry) = self.entries.get_mut(idx) {
+                        if entry.text.trim() == WORKING_PLACEHOLDER {
+                            entry.text = "(cancelled)".to_string();
                         }
                     }
-                    self.clear_running_state();
-                    self.last_tool_event = "task cancelled".to_string();
-                    self.last_status = "cancelled".to_string();
-                    self.push_entry(EntryKind::System, "task cancelled (Esc)");
                 }
+                self.clear_running_state();
+                self.last_tool_event = "task cancelled".to_string();
+                self.last_status = "cancelled".to_string();
+                self.push_entry(EntryKind::System, "task cancelled (Esc)");
             }
             KeyCode::Char(c) => {
                 self.insert_char(c);
@@ -2706,7 +4676,7 @@ impl App {
     }
 
     fn render_entries_lines(&self, width: u16) -> Vec<Line<'static>> {
-        self.render_entries_lines_range(width, 0, self.entries.len())
+        self.render_entries_lines_range(width, 0, self.entries.len(), &mut LineMap::default())
     }
 
     fn render_entries_lines_range(
@@ -2714,9 +4684,10 @@ impl App {
         width: u16,
         start: usize,
         end: usize,
+        line_map: &mut LineMap,
     ) -> Vec<Line<'static>> {
         let mut lines = Vec::<Line>::new();
-        let palette = self.theme.palette();
+        let palette = self.theme_palette();
         let active_entry_indices: HashSet<usize> = self.agent_entries.values().copied().collect();
         let max_label_width = Provider::all()
             .iter()
@@ -2790,14 +4761,23 @@ impl App {
 
                     if raw_text.is_empty() {
                         if !(self.running && is_current_entry) {
+                            line_map.gutters.insert(lines.len(), 1);
                             lines.push(Line::from(vec![Span::styled(
                                 label_sep.clone(),
                                 label_style,
                             )]));
                         }
                     } else {
-                        let md_lines = render_markdown(&raw_text, base_style, palette);
+                        let mut fences = Vec::new();
+                        let md_lines = render_markdown_mapped(
+                            &raw_text,
+                            base_style,
+                            palette,
+                            content_width,
+                            &mut fences,
+                        );
                         for (i, md_line) in md_lines.into_iter().enumerate() {
+                            let fence = fences.iter().position(|&line| line == i);
                             // Pre-wrap: split content spans into multiple lines
                             // so each fits within content_width.
                             let wrapped = wrap_spans(md_line, content_width);
@@ -2817,7 +4797,14 @@ impl App {
                                         Span::raw(" "),
                                     ]
                                 };
+                                line_map.gutters.insert(lines.len(), spans.len());
                                 spans.extend(w_line);
+                                if wi > 0 {
+                                    line_map.continuations.insert(lines.len());
+                                } else if let Some(k) = fence {
+                                    // Opening and closing fences share a block.
+                                    line_map.fences.insert(lines.len(), (idx, k / 2));
+                                }
                                 lines.push(Line::from(spans));
                             }
                         }
@@ -2855,7 +4842,7 @@ impl App {
                                 StartupBannerRow::Agents(value) => {
                                     (format!(" {value}"), palette.secondary_style())
                                 }
-                                StartupBannerRow::Cwd(value) => {
+                                StartupBannerRow::Cwd(value) | StartupBannerRow::Memory(value) => {
                                     (format!(" {value}"), palette.secondary_style())
                                 }
                                 StartupBannerRow::Keys(value) => {
@@ -2887,7 +4874,7 @@ impl App {
                             StartupBannerRow::Agents(value) => {
                                 (value.to_string(), palette.secondary_style())
                             }
-                            StartupBannerRow::Cwd(value) => {
+                            StartupBannerRow::Cwd(value) | StartupBannerRow::Memory(value) => {
                                 (value.to_string(), palette.secondary_style())
                             }
                             StartupBannerRow::Keys(value) => {
@@ -2953,6 +4940,7 @@ impl App {
             if lines.len() == line_count_before_entry {
                 continue;
             }
+            line_map.entry_rows.push((idx, line_count_before_entry));
 
             let next_idx = idx + 1;
             let next_entry = if next_idx < end {
@@ -2984,6 +4972,7 @@ impl App {
                 let label_col_width = max_label_width + 2;
                 let indent = " ".repeat(label_col_width.saturating_sub(1));
                 let indent_sep = format!("{}{}", indent, ASSISTANT_DIVIDER);
+                line_map.gutters.insert(lines.len(), 1);
                 lines.push(Line::from(vec![Span::styled(indent_sep, label_style)]));
             } else if !is_running_stream_entry {
                 lines.push(Line::from(""));
@@ -2999,9 +4988,10 @@ impl App {
         &self,
         width: u16,
         skip_indices: &std::collections::HashSet<usize>,
+        line_map: &mut LineMap,
     ) -> Vec<Line<'static>> {
         let mut lines = Vec::<Line>::new();
-        let palette = self.theme.palette();
+        let palette = self.theme_palette();
         let active_entry_indices: HashSet<usize> = self.agent_entries.values().copied().collect();
         let max_label_width = Provider::all()
             .iter()
@@ -3067,14 +5057,23 @@ impl App {
                     let content_width = (width as usize).saturating_sub(label_col_width + 1);
                     if raw_text.is_empty() {
                         if !(self.running && is_current_entry) {
+                            line_map.gutters.insert(lines.len(), 1);
                             lines.push(Line::from(vec![Span::styled(
                                 label_sep.clone(),
                                 label_style,
                             )]));
                         }
                     } else {
-                        let md_lines = render_markdown(&raw_text, base_style, palette);
+                        let mut fences = Vec::new();
+                        let md_lines = render_markdown_mapped(
+                            &raw_text,
+                            base_style,
+                            palette,
+                            content_width,
+                            &mut fences,
+                        );
                         for (i, md_line) in md_lines.into_iter().enumerate() {
+                            let fence = fences.iter().position(|&line| line == i);
                             let wrapped = wrap_spans(md_line, content_width);
                             for (wi, w_line) in wrapped.into_iter().enumerate() {
                                 let mut spans = if i == 0 && wi == 0 {
@@ -3088,7 +5087,14 @@ impl App {
                                         Span::raw(" "),
                                     ]
                                 };
+                                line_map.gutters.insert(lines.len(), spans.len());
                                 spans.extend(w_line);
+                                if wi > 0 {
+                                    line_map.continuations.insert(lines.len());
+                                } else if let Some(k) = fence {
+                                    // Opening and closing fences share a block.
+                                    line_map.fences.insert(lines.len(), (idx, k / 2));
+                                }
                                 lines.push(Line::from(spans));
                             }
                         }
@@ -3125,7 +5131,7 @@ impl App {
                                 StartupBannerRow::Agents(value) => {
                                     (format!(" {value}"), palette.secondary_style())
                                 }
-                                StartupBannerRow::Cwd(value) => {
+                                StartupBannerRow::Cwd(value) | StartupBannerRow::Memory(value) => {
                                     (format!(" {value}"), palette.secondary_style())
                                 }
                                 StartupBannerRow::Keys(value) => {
@@ -3155,7 +5161,7 @@ impl App {
                             StartupBannerRow::Agents(value) => {
                                 (value.to_string(), palette.secondary_style())
                             }
-                            StartupBannerRow::Cwd(value) => {
+                            StartupBannerRow::Cwd(value) | StartupBannerRow::Memory(value) => {
                                 (value.to_string(), palette.secondary_style())
                             }
                             StartupBannerRow::Keys(value) => {
@@ -3221,6 +5227,7 @@ impl App {
             if lines.len() == line_count_before_entry {
                 continue;
             }
+            line_map.entry_rows.push((idx, line_count_before_entry));
 
             let next_non_skipped =
                 (idx + 1..self.entries.len()).find(|i| !skip_indices.contains(i));
@@ -3252,6 +5259,7 @@ impl App {
                 let label_col_width = max_label_width + 2;
                 let indent = " ".repeat(label_col_width.saturating_sub(1));
                 let indent_sep = format!("{}{}", indent, ASSISTANT_DIVIDER);
+                line_map.gutters.insert(lines.len(), 1);
                 lines.push(Line::from(vec![Span::styled(indent_sep, label_style)]));
             } else if !is_running_stream_entry {
                 lines.push(Line::from(""));
@@ -3261,9 +5269,356 @@ impl App {
         lines
     }
 
+    #[cfg(test)]
     fn render_log_lines_inner(&self, width: u16) -> Vec<Line<'static>> {
-        self.render_entries_lines(width)
+        self.render_log_lines_mapped(width).0
+    }
+
+    /// Transcript lines plus where each entry starts in them.
+    fn render_log_lines_mapped(&self, width: u16) -> (Vec<Line<'static>>, LineMap) {
+        let mut line_map = LineMap::default();
+        let lines = if self.split_view_active() {
+            let skip = self.split_entries.values().copied().collect::<HashSet<_>>();
+            self.render_entries_lines_filtered(width, &skip, &mut line_map)
+        } else {
+            self.render_entries_lines_range(width, 0, self.entries.len(), &mut line_map)
+        };
+        (lines, line_map)
+    }
+
+    /// Agents of the current run get their own columns instead of stacking.
+    /// Full-screen mode keeps them until the next prompt; inline mode hands
+    /// them to scrollback when the run ends.
+    fn split_view_active(&self) -> bool {
+        self.agent_layout == AgentLayout::Split
+            && (self.running || self.fullscreen)
+            && self.split_entries.len() > 1
+    }
+
+    fn clear_split_columns(&mut self) {
+        self.split_entries.clear();
+        self.split_focus = 0;
+        self.split_scroll_back.clear();
+    }
+
+    /// Providers shown as split columns, left to right.
+    pub(super) fn split_columns(&self) -> Vec<Provider> {
+        Provider::all()
+            .iter()
+            .copied()
+            .filter(|provider| self.split_entries.contains_key(provider))
+            .collect()
+    }
+
+    /// Body of one agent's streaming entry, without the stacked label gutter.
+    pub(super) fn split_column_lines(&self, provider: Provider) -> Vec<Line<'static>> {
+        let Some(entry) = self
+            .split_entries
+            .get(&provider)
+            .and_then(|&idx| self.entries.get(idx))
+        else {
+            return Vec::new();
+        };
+        let palette = self.theme_palette();
+        let text = cleaned_assistant_text(entry);
+        if text.trim().is_empty() {
+            return vec![Line::styled(
+                WORKING_PLACEHOLDER.to_string(),
+                palette.muted_style(),
+            )];
+        }
+        let column_width = (self.viewport_width as usize / self.split_entries.len().max(1))
+            .saturating_sub(2)
+            .max(1);
+        render_markdown(&text, palette.body_style(), palette, column_width)
+            .into_iter()
+            .map(Line::from)
+            .collect()
+    }
+
+    /// Seconds shown in a split column title: frozen once the agent is done.
+    pub(super) fn split_column_elapsed(&self, provider: Provider) -> u64 {
+        let finished = self
+            .split_entries
+            .get(&provider)
+            .and_then(|&idx| self.entries.get(idx))
+            .and_then(|entry| entry.elapsed_secs);
+        finished.unwrap_or_else(|| {
+            self.agent_started_at
+                .get(&provider)
+                .map(|t| t.elapsed().as_secs())
+                .unwrap_or(0)
+        })
+    }
+
+    pub(super) fn split_scroll_back(&self, provider: Provider) -> u16 {
+        self.split_scroll_back.get(&provider).copied().unwrap_or(0)
+    }
+
+    fn move_split_focus(&mut self, forward: bool) {
+        let count = self.split_columns().len().max(1);
+        self.split_focus = if forward {
+            (self.split_focus + 1) % count
+        } else {
+            (self.split_focus + count - 1) % count
+        };
+    }
+
+    /// Scroll the focused column; scrolling back down to the tail resumes following it.
+    fn scroll_split_column(&mut self, up: bool, n: u16) {
+        let columns = self.split_columns();
+        let Some(&provider) = columns.get(self.split_focus.min(columns.len().saturating_sub(1)))
+        else {
+            return;
+        };
+        let column_width = (self.viewport_width / columns.len() as u16)
+            .saturating_sub(2)
+            .max(1);
+        let limit = Paragraph::new(Text::from(self.split_column_lines(provider)))
+            .wrap(Wrap { trim: false })
+            .line_count(column_width)
+            .min(u16::MAX as usize) as u16;
+        let back = self.split_scroll_back(provider);
+        let back = if up {
+            back.saturating_add(n).min(limit)
+        } else {
+            back.saturating_sub(n)
+        };
+        if back == 0 {
+            self.split_scroll_back.remove(&provider);
+        } else {
+            self.split_scroll_back.insert(provider, back);
+        }
+    }
+
+    fn set_agent_layout(&mut self, layout: AgentLayout) {
+        self.agent_layout = layout;
+        self.invalidate_render_cache();
+        self.last_status = format!("layout {}", layout.as_str());
+    }
+
+    fn handle_layout_command(&mut self, target: &str) {
+        if target.is_empty() {
+            self.push_entry(
+                EntryKind::System,
+                format!(
+                    "layout: {} | options: split, stacked (Ctrl+T toggles)",
+                    self.agent_layout.as_str()
+                ),
+            );
+            return;
+        }
+        let Some(layout) = AgentLayout::parse(target) else {
+            self.push_entry(EntryKind::Error, "usage: /layout [split|stacked]");
+            return;
+        };
+        self.set_agent_layout(layout);
+        self.push_entry(
+            EntryKind::System,
+            format!("multi-agent layout set to {}", layout.as_str()),
+        );
+    }
+
+    fn select_entry(&mut self, entry: Option<usize>) {
+        if self.selected_entry != entry {
+            self.selected_entry = entry;
+            self.invalidate_render_cache();
+        }
+    }
+
+    /// Left click in the full-screen transcript selects the entry under the
+    /// pointer; a click on a code block's fence line also copies the block.
+    fn handle_transcript_click(&mut self, column: u16, row: u16) {
+        let area = self.transcript_area.get();
+        if !self.fullscreen || !area.contains((column, row).into()) {
+            return;
+        }
+        let offset = ui::transcript_offset(self, self.cached_log_rows(), area.height);
+        let hit = self
+            .render_cache
+            .line_at_row(usize::from(offset + (row - area.y)));
+        let line_map = &self.render_cache.line_map;
+        let Some((entry_idx, line_idx)) = hit.and_then(|line_idx| {
+            let (entry_idx, _) = line_map
+                .entry_rows
+                .iter()
+                .rev()
+                .find(|(_, first)| *first <= line_idx)?;
+            Some((*entry_idx, line_idx))
+        }) else {
+            self.select_entry(None);
+            return;
+        };
+
+        let fence = line_map.fences.get(&line_idx).copied();
+        self.select_entry(Some(entry_idx));
+        let Some((_, block_idx)) = fence else {
+            return;
+        };
+        let Some(entry) = self
+            .entries
+            .get(entry_idx)
+            .filter(|entry| matches!(entry.kind, EntryKind::Assistant))
+        else {
+            return;
+        };
+        let Some(block) = fenced_code_blocks(&cleaned_assistant_text(entry))
+            .into_iter()
+            .nth(block_idx)
+        else {
+            return;
+        };
+        self.copy_text(block, &format!("code block {}", block_idx + 1));
+    }
+
+    /// Queue `text` for the clipboard; the main loop writes it out.
+    fn copy_text(&mut self, text: String, what: &str) {
+        self.pending_copy = Some(PendingCopy {
+            text,
+            what: what.to_string(),
+        });
+    }
+
+    /// Outcome of writing `copy` to the clipboard: the status line on
+    /// success, an error entry as well on failure.
+    fn report_copy(&mut self, copy: &PendingCopy, result: std::io::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.last_status =
+                    format!("copied {} ({} lines)", copy.what, copy.text.lines().count());
+            }
+            Err(err) => {
+                self.last_status = format!("copy of {} failed", copy.what);
+                self.push_entry(EntryKind::Error, format!("copy failed: {err}"));
+            }
+        }
+    }
+
+    /// Index of the `n`-th most recent non-empty assistant answer (1 = last).
+    fn recent_answer(&self, n: usize) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| {
+                matches!(entry.kind, EntryKind::Assistant)
+                    && !cleaned_assistant_text(entry).trim().is_empty()
+            })
+            .nth(n.checked_sub(1)?)
+            .map(|(idx, _)| idx)
+    }
+
+    /// `/copy [last|<n>|code [k]]`. Without arguments the selected entry is
+    /// copied, else the last answer; `code` copies the last code block (or
+    /// block `k`) of the selected or last answer.
+    fn handle_copy_command(&mut self, args: &str) {
+        const USAGE: &str = "usage: /copy [last|<n>|code [k]]";
+        let selected_answer = self.selected_entry.filter(|idx| {
+            self.entries
+                .get(*idx)
+                .is_some_and(|entry| matches!(entry.kind, EntryKind::Assistant))
+        });
+        let positive = |raw: &str| raw.parse::<usize>().ok().filter(|n| *n > 0);
+        let tokens = args.split_whitespace().collect::<Vec<_>>();
+        let (target, code_block) = match tokens.as_slice() {
+            [] => (
+                self.selected_entry
+                    .filter(|idx| *idx < self.entries.len())
+                    .or_else(|| self.recent_answer(1)),
+                None,
+            ),
+            ["last"] => (self.recent_answer(1), None),
+            ["code"] => (
+                selected_answer.or_else(|| self.recent_answer(1)),
+                Some(None),
+            ),
+            ["code", k] => match positive(k) {
+                Some(k) => (
+                    selected_answer.or_else(|| self.recent_answer(1)),
+                    Some(Some(k)),
+                ),
+                None => {
+                    self.push_entry(EntryKind::Error, USAGE);
+                    return;
+                }
+            },
+            [n] => match positive(n) {
+                Some(n) => (self.recent_answer(n), None),
+                None => {
+                    self.push_entry(EntryKind::Error, USAGE);
+                    return;
+                }
+            },
+            _ => {
+                self.push_entry(EntryKind::Error, USAGE);
+                return;
+            }
+        };
+        let Some(entry) = target.and_then(|idx| self.entries.get(idx)) else {
+            self.push_entry(EntryKind::Error, "no answer to copy yet");
+            return;
+        };
+        let text = match entry.kind {
+            EntryKind::Assistant => cleaned_assistant_text(entry),
+            _ => entry.text.clone(),
+        };
+        let Some(k) = code_block else {
+            let what = if matches!(entry.kind, EntryKind::Assistant) {
+                "answer"
+            } else {
+                "entry"
+            };
+            self.copy_text(text, what);
+            return;
+        };
+        let blocks = fenced_code_blocks(&text);
+        let k = k.unwrap_or(blocks.len());
+        match blocks.into_iter().nth(k.wrapping_sub(1)) {
+            Some(block) => self.copy_text(block, &format!("code block {k}")),
+            None if k == 0 => self.push_entry(EntryKind::Error, "that answer has no code blocks"),
+            None => self.push_entry(
+                EntryKind::Error,
+                format!("that answer has no code block {k}"),
+            ),
+        }
+    }
+}
+
+/// Lines of `entry` in a transcript mapped by `entry_rows`; empty when the
+/// entry rendered nothing.
+fn entry_line_range(
+    entry_rows: &[(usize, usize)],
+    entry: usize,
+    line_count: usize,
+) -> std::ops::Range<usize> {
+    let Some(pos) = entry_rows.iter().position(|(idx, _)| *idx == entry) else {
+        return 0..0;
+    };
+    let end = entry_rows
+        .get(pos + 1)
+        .map_or(line_count, |(_, first)| *first);
+    entry_rows[pos].1..end
+}
+
+/// Bodies of the fenced code blocks in `text`, in order. An unterminated
+/// block runs to the end, as `render_markdown` draws it.
+fn fenced_code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in text.split('\n') {
+        if line.trim().starts_with("```") {
+            match current.take() {
+                Some(body) => blocks.push(body.join("\n")),
+                None => current = Some(Vec::new()),
+            }
+        } else if let Some(body) = current.as_mut() {
+            body.push(line);
+        }
+    }
+    if let Some(body) = current {
+        blocks.push(body.join("\n"));
     }
+    blocks
 }
 
 /// Pre-wrap a list of spans so that each resulting line fits within `max_width`
@@ -3307,6 +5662,18 @@ fn wrap_spans(spans: Vec<Span<'static>>, max_width: usize) -> Vec<Vec<Span<'stat
                     cols += w;
                     split_byte = byte_idx + ch.len_utf8();
                 }
+                if split_byte < remaining.len() {
+                    // Prefer breaking after the last space so words stay whole;
+                    // a word that starts mid-line moves to the next line instead.
+                    if let Some(space) = remaining[..split_byte].rfind(' ') {
+                        split_byte = space + 1;
+                        cols = UnicodeWidthStr::width(&remaining[..split_byte]);
+                    } else if !current_line.is_empty() {
+                        result.push(std::mem::take(&mut current_line));
+                        current_width = 0;
+                        continue;
+                    }
+                }
                 if split_byte == 0 && current_line.is_empty() {
                     // Single char wider than avail (shouldn't happen normally).
                     // Force at least one char to avoid infinite loop.
@@ -3341,12 +5708,28 @@ fn wrap_spans(spans: Vec<Span<'static>>, max_width: usize) -> Vec<Vec<Span<'stat
 }
 
 /// Render markdown text into styled spans per line.
-/// Supports: headings (#), bold (**), italic (*), inline code (`),
-/// fenced code blocks (```), and unordered list bullets (- / *).
+/// Supports: headings (#), bold (**), italic (*), inline code (`), links,
+/// fenced code blocks (```) with syntax highlighting, unfenced unified diffs,
+/// tables, rules, blockquotes (>) and nested ordered/unordered lists.
+///
+/// Blocks that need a hanging indent or column layout are wrapped to `width`
+/// here; plain paragraphs are left for the caller to wrap. `0` means unbounded.
 fn render_markdown(
     text: &str,
     base_style: Style,
     palette: ThemePalette,
+    width: usize,
+) -> Vec<Vec<Span<'static>>> {
+    render_markdown_mapped(text, base_style, palette, width, &mut Vec::new())
+}
+
+/// `render_markdown` that also records which output lines are code fences.
+fn render_markdown_mapped(
+    text: &str,
+    base_style: Style,
+    palette: ThemePalette,
+    width: usize,
+    fences: &mut Vec<usize>,
 ) -> Vec<Vec<Span<'static>>> {
     if !contains_markdown_syntax(text) {
         return text
@@ -3359,95 +5742,403 @@ fn render_markdown(
     }
 
     let mut result: Vec<Vec<Span<'static>>> = Vec::new();
-    let mut in_code_block = false;
+    let mut code_block: Option<highlight::Highlighter> = None;
 
-    let code_block_style = Style::default().fg(palette.code_fg).bg(palette.code_bg);
     let heading_style = Style::default()
         .fg(palette.banner_title)
         .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
-    let bold_style = base_style.add_modifier(Modifier::BOLD);
-    let italic_style = base_style.add_modifier(Modifier::ITALIC);
-    let inline_code_style = Style::default()
-        .fg(palette.inline_code_fg)
-        .bg(palette.inline_code_bg);
+    let styles = InlineStyles::new(base_style, palette);
+    let quote_styles = InlineStyles::new(
+        palette.secondary_style().add_modifier(Modifier::ITALIC),
+        palette,
+    );
     let bullet_style = base_style.fg(palette.bullet);
 
-    for line in text.split('\n') {
-        let trimmed = line.trim();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        idx += 1;
+        let trimmed = line.trim();
+
+        // Toggle fenced code blocks
+        if trimmed.starts_with("```") {
+            // Render the fence line itself in code style
+            let lang = trimmed.trim_start_matches('`').trim();
+            code_block = match code_block {
+                Some(_) => None,
+                None => Some(highlight::Highlighter::new(lang)),
+            };
+            fences.push(result.len());
+            if lang.is_empty() {
+                result.push(vec![Span::styled("───".to_string(), palette.muted_style())]);
+            } else {
+                result.push(vec![
+                    Span::styled("─── ".to_string(), palette.muted_style()),
+                    Span::styled(
+                        lang.to_string(),
+                        palette.muted_style().add_modifier(Modifier::ITALIC),
+                    ),
+                ]);
+            }
+            continue;
+        }
+
+        if let Some(highlighter) = code_block.as_mut() {
+            result.push(highlighter.line(line, palette));
+            continue;
+        }
+
+        let diff_len = highlight::unfenced_diff_len(&lines[idx - 1..]);
+        if diff_len > 0 {
+            let mut highlighter = highlight::Highlighter::diff();
+            for diff_line in &lines[idx - 1..idx - 1 + diff_len] {
+                result.push(highlighter.line(diff_line, palette));
+            }
+            idx += diff_len - 1;
+            continue;
+        }
+
+        let table_len = markdown_table_len(&lines[idx - 1..]);
+        if table_len > 0 {
+            let rows = &lines[idx - 1..idx - 1 + table_len];
+            result.extend(render_markdown_table(rows, styles, palette, width));
+            idx += table_len - 1;
+            continue;
+        }
+
+        if is_markdown_rule(trimmed) {
+            let rule_width = if width == 0 { 24 } else { width };
+            result.push(vec![Span::styled(
+                "\u{2500}".repeat(rule_width),
+                palette.muted_style(),
+            )]);
+            continue;
+        }
+
+        // Headings
+        if trimmed.starts_with('#') {
+            let level = trimmed.chars().take_while(|c| *c == '#').count();
+            let heading_text = trimmed[level..].trim_start();
+            let prefix = "#".repeat(level);
+            if heading_text.is_empty() {
+                result.push(vec![Span::styled(prefix, heading_style)]);
+            } else {
+                result.push(vec![
+                    Span::styled(format!("{} ", prefix), palette.muted_style()),
+                    Span::styled(heading_text.to_string(), heading_style),
+                ]);
+            }
+            continue;
+        }
+
+        // Blockquotes, one bar per nesting level
+        if let Some(quoted) = trimmed.strip_prefix('>') {
+            let mut depth = 1;
+            let mut rest = quoted.trim_start();
+            while let Some(inner) = rest.strip_prefix('>') {
+                depth += 1;
+                rest = inner.trim_start();
+            }
+            let bar = vec![Span::styled(
+                "\u{2502} ".repeat(depth),
+                palette.muted_style(),
+            )];
+            push_hanging(
+                &mut result,
+                bar.clone(),
+                bar,
+                render_inline_markdown(rest, quote_styles),
+                width,
+            );
+            continue;
+        }
+
+        // Ordered and unordered list items; indentation sets the nesting depth
+        if let Some((indent, marker, rest)) = parse_list_item(line) {
+            let marker = match marker {
+                Some(number) => format!("{number} "),
+                None => format!("{} ", LIST_BULLETS[(indent / 2) % LIST_BULLETS.len()]),
+            };
+            let hang_width = indent + UnicodeWidthStr::width(marker.as_str());
+            let mut first = Vec::new();
+            if indent > 0 {
+                first.push(Span::raw(" ".repeat(indent)));
+            }
+            first.push(Span::styled(marker, bullet_style));
+            push_hanging(
+                &mut result,
+                first,
+                vec![Span::raw(" ".repeat(hang_width))],
+                render_inline_markdown(rest, styles),
+                width,
+            );
+            continue;
+        }
+
+        // Regular line with inline markdown
+        let content = if line.is_empty() { " " } else { line };
+        result.push(render_inline_markdown(content, styles));
+    }
+
+    result
+}
+
+/// Bullet glyphs by list nesting depth.
+const LIST_BULLETS: [char; 3] = ['\u{2022}', '\u{25e6}', '\u{25aa}'];
+
+/// Styles for inline markdown runs on top of a block's base style.
+#[derive(Clone, Copy)]
+struct InlineStyles {
+    base: Style,
+    bold: Style,
+    italic: Style,
+    code: Style,
+    link: Style,
+    url: Style,
+}
+
+impl InlineStyles {
+    fn new(base: Style, palette: ThemePalette) -> Self {
+        Self {
+            base,
+            bold: base.add_modifier(Modifier::BOLD),
+            italic: base.add_modifier(Modifier::ITALIC),
+            code: Style::default()
+                .fg(palette.inline_code_fg)
+                .bg(palette.inline_code_bg),
+            link: base.fg(palette.link).add_modifier(Modifier::UNDERLINED),
+            url: palette.muted_style(),
+        }
+    }
+}
+
+/// Wrap `body` to `width` behind `first` on the first row and `rest` after it,
+/// so list items and quotes keep their hanging indent.
+fn push_hanging(
+    result: &mut Vec<Vec<Span<'static>>>,
+    first: Vec<Span<'static>>,
+    rest: Vec<Span<'static>>,
+    body: Vec<Span<'static>>,
+    width: usize,
+) {
+    if width == 0 {
+        result.push(first.into_iter().chain(body).collect());
+        return;
+    }
+    let prefix_width = spans_display_width(&first);
+    let body_width = width.saturating_sub(prefix_width).max(1);
+    for (i, line) in wrap_spans(body, body_width).into_iter().enumerate() {
+        let prefix = if i == 0 { first.clone() } else { rest.clone() };
+        result.push(prefix.into_iter().chain(line).collect());
+    }
+}
+
+fn spans_display_width(spans: &[Span<'_>]) -> usize {
+    spans
+        .iter()
+        .map(|span| UnicodeWidthStr::width(span.content.as_ref()))
+        .sum()
+}
+
+/// `(indent, number, rest)` for `- x`, `* x`, `+ x` and `12. x` / `3) x`;
+/// `number` is `None` for bullets.
+fn parse_list_item(line: &str) -> Option<(usize, Option<&str>, &str)> {
+    let body = line.trim_start();
+    let indent = line.len() - body.len();
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = body.strip_prefix(bullet) {
+            return Some((indent, None, rest));
+        }
+    }
+    let digits = body.bytes().take_while(u8::is_ascii_digit).count();
+    if !(1..=9).contains(&digits) {
+        return None;
+    }
+    let after = &body[digits..];
+    if after.starts_with(". ") || after.starts_with(") ") {
+        return Some((indent, Some(&body[..digits + 1]), &after[2..]));
+    }
+    None
+}
+
+/// `---`, `***` or `___`, optionally spaced, on a line of its own.
+fn is_markdown_rule(trimmed: &str) -> bool {
+    let compact: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&mark| compact.chars().all(|c| c == mark))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CellAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Rows in a pipe table starting at `lines[0]`: a header row and a `|---|`
+/// delimiter row, then every following row that still has a pipe.
+fn markdown_table_len(lines: &[&str]) -> usize {
+    let [header, delimiter, ..] = lines else {
+        return 0;
+    };
+    let is_delimiter = {
+        let d = delimiter.trim();
+        d.contains('-') && d.contains('|') && d.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+    };
+    if !header.contains('|') || !is_delimiter {
+        return 0;
+    }
+    2 + lines[2..]
+        .iter()
+        .take_while(|line| !line.trim().is_empty() && line.contains('|'))
+        .count()
+}
 
-        // Toggle fenced code blocks
-        if trimmed.starts_with("```") {
-            in_code_block = !in_code_block;
-            // Render the fence line itself in code style
-            let lang = trimmed.trim_start_matches('`').trim();
-            if lang.is_empty() {
-                result.push(vec![Span::styled("───".to_string(), palette.muted_style())]);
-            } else {
-                result.push(vec![
-                    Span::styled("─── ".to_string(), palette.muted_style()),
-                    Span::styled(
-                        lang.to_string(),
-                        palette.muted_style().add_modifier(Modifier::ITALIC),
-                    ),
-                ]);
+/// Cells of one table row; pipes inside backticks or escaped as `\|` stay in the cell.
+fn split_table_row(line: &str) -> Vec<String> {
+    let row = line.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_code = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
             }
-            continue;
+            '`' => {
+                in_code = !in_code;
+                cell.push(c);
+            }
+            '|' if !in_code => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
         }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
 
-        if in_code_block {
-            let content = if line.is_empty() { " " } else { line };
-            result.push(vec![Span::styled(content.to_string(), code_block_style)]);
-            continue;
+/// Shrink natural column widths to fit `available`: columns narrower than an
+/// even share keep their width and the rest split what remains.
+fn fit_table_columns(natural: &[usize], available: usize) -> Vec<usize> {
+    if natural.iter().sum::<usize>() <= available {
+        return natural.to_vec();
+    }
+    let mut widths = natural.to_vec();
+    let mut open: Vec<usize> = (0..natural.len()).collect();
+    let mut budget = available.max(natural.len());
+    while !open.is_empty() {
+        let share = budget / open.len();
+        let (fits, wide): (Vec<usize>, Vec<usize>) =
+            open.iter().partition(|&&col| natural[col] <= share);
+        if fits.is_empty() {
+            let extra = budget % open.len();
+            for (i, &col) in open.iter().enumerate() {
+                widths[col] = (share + usize::from(i < extra)).max(1);
+            }
+            break;
         }
+        for col in fits {
+            budget -= natural[col];
+        }
+        open = wide;
+    }
+    widths
+}
 
-        // Headings
-        if trimmed.starts_with('#') {
-            let level = trimmed.chars().take_while(|c| *c == '#').count();
-            let heading_text = trimmed[level..].trim_start();
-            let prefix = "#".repeat(level);
-            if heading_text.is_empty() {
-                result.push(vec![Span::styled(prefix, heading_style)]);
-            } else {
-                result.push(vec![
-                    Span::styled(format!("{} ", prefix), palette.muted_style()),
-                    Span::styled(heading_text.to_string(), heading_style),
-                ]);
-            }
+fn render_markdown_table(
+    rows: &[&str],
+    styles: InlineStyles,
+    palette: ThemePalette,
+    width: usize,
+) -> Vec<Vec<Span<'static>>> {
+    let header_styles = InlineStyles {
+        base: styles.bold,
+        ..styles
+    };
+    let aligns: Vec<CellAlign> = split_table_row(rows[1])
+        .iter()
+        .map(|cell| match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => CellAlign::Center,
+            (false, true) => CellAlign::Right,
+            _ => CellAlign::Left,
+        })
+        .collect();
+    let mut table: Vec<Vec<Vec<Span<'static>>>> = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        if i == 1 {
             continue;
         }
+        let row_styles = if i == 0 { header_styles } else { styles };
+        table.push(
+            split_table_row(row)
+                .iter()
+                .map(|cell| render_inline_markdown(cell, row_styles))
+                .collect(),
+        );
+    }
+    let columns = table.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let mut natural = vec![1usize; columns];
+    for row in &table {
+        for (col, cell) in row.iter().enumerate() {
+            natural[col] = natural[col].max(spans_display_width(cell));
+        }
+    }
+    let separators = 3 * (columns - 1);
+    let widths = if width == 0 {
+        natural
+    } else {
+        fit_table_columns(&natural, width.saturating_sub(separators))
+    };
 
-        // Unordered list bullets
-        if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
-            let indent = line.len() - line.trim_start().len();
-            let rest = &trimmed[2..];
+    let border = palette.muted_style();
+    let mut out = Vec::new();
+    for (r, row) in table.into_iter().enumerate() {
+        let cells: Vec<Vec<Vec<Span<'static>>>> = widths
+            .iter()
+            .enumerate()
+            .map(|(col, &w)| wrap_spans(row.get(col).cloned().unwrap_or_default(), w))
+            .collect();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+        for line in 0..height {
             let mut spans = Vec::new();
-            if indent > 0 {
-                spans.push(Span::raw(" ".repeat(indent)));
+            for (col, wrapped) in cells.iter().enumerate() {
+                if col > 0 {
+                    spans.push(Span::styled(" \u{2502} ".to_string(), border));
+                }
+                let content = wrapped.get(line).cloned().unwrap_or_default();
+                let gap = widths[col].saturating_sub(spans_display_width(&content));
+                let (left, right) = match aligns.get(col).copied().unwrap_or(CellAlign::Left) {
+                    CellAlign::Left => (0, gap),
+                    CellAlign::Right => (gap, 0),
+                    CellAlign::Center => (gap / 2, gap - gap / 2),
+                };
+                if left > 0 {
+                    spans.push(Span::styled(" ".repeat(left), styles.base));
+                }
+                spans.extend(content);
+                if right > 0 && col + 1 < columns {
+                    spans.push(Span::styled(" ".repeat(right), styles.base));
+                }
             }
-            spans.push(Span::styled("\u{2022} ".to_string(), bullet_style));
-            spans.extend(render_inline_markdown(
-                rest,
-                base_style,
-                bold_style,
-                italic_style,
-                inline_code_style,
-            ));
-            result.push(spans);
-            continue;
+            out.push(spans);
+        }
+        if r == 0 {
+            let rule = widths
+                .iter()
+                .map(|&w| "\u{2500}".repeat(w))
+                .collect::<Vec<_>>()
+                .join("\u{2500}\u{253c}\u{2500}");
+            out.push(vec![Span::styled(rule, border)]);
         }
-
-        // Regular line with inline markdown
-        let content = if line.is_empty() { " " } else { line };
-        result.push(render_inline_markdown(
-            content,
-            base_style,
-            bold_style,
-            italic_style,
-            inline_code_style,
-        ));
     }
-
-    result
+    out
 }
 
 fn contains_markdown_syntax(text: &str) -> bool {
@@ -3455,8 +6146,18 @@ fn contains_markdown_syntax(text: &str) -> bool {
         return true;
     }
     for line in text.lines() {
+        if highlight::is_diff_header(line) {
+            return true;
+        }
         let trimmed = line.trim_start();
-        if trimmed.starts_with('#') || trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+        if trimmed.starts_with('#')
+            || trimmed.starts_with('>')
+            || parse_list_item(line).is_some()
+            || is_markdown_rule(trimmed)
+            || (trimmed.starts_with('|') && trimmed.len() > 1)
+            || line.contains("](")
+            || line.contains("<http")
+        {
             return true;
         }
         // Lightweight check for inline emphasis.
@@ -3512,14 +6213,15 @@ fn sanitize_runtime_text(text: &str) -> String {
     out
 }
 
-/// Parse inline markdown: **bold**, *italic*, `code`
-fn render_inline_markdown(
-    text: &str,
-    base_style: Style,
-    bold_style: Style,
-    italic_style: Style,
-    code_style: Style,
-) -> Vec<Span<'static>> {
+/// Parse inline markdown: **bold**, *italic*, `code`, [links](url) and <autolinks>
+fn render_inline_markdown(text: &str, styles: InlineStyles) -> Vec<Span<'static>> {
+    let InlineStyles {
+        base: base_style,
+        bold: bold_style,
+        italic: italic_style,
+        code: code_style,
+        ..
+    } = styles;
     let mut spans = Vec::new();
     let mut buf = String::new();
     let chars: Vec<char> = text.chars().collect();
@@ -3545,6 +6247,23 @@ fn render_inline_markdown(
             continue;
         }
 
+        // Links: [text](url) and <https://...>
+        if chars[i] == '[' || chars[i] == '<' {
+            if let Some((label, url, end)) = parse_inline_link(&chars, i) {
+                if !buf.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut buf), base_style));
+                }
+                if label.is_empty() || label == url {
+                    spans.push(Span::styled(url, styles.link));
+                } else {
+                    spans.push(Span::styled(label, styles.link));
+                    spans.push(Span::styled(format!(" ({url})"), styles.url));
+                }
+                i = end;
+                continue;
+            }
+        }
+
         // Bold: **...**
         if i + 1 < len && chars[i] == '*' && chars[i + 1] == '*' {
             if !buf.is_empty() {
@@ -3611,6 +6330,29 @@ fn render_inline_markdown(
     spans
 }
 
+/// `(label, url, end)` for a link opening at `chars[start]`; autolinks have
+/// an empty label.
+fn parse_inline_link(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    if chars[start] == '<' {
+        let close = chars[start..].iter().position(|&c| c == '>')? + start;
+        let url: String = chars[start + 1..close].iter().collect();
+        let is_url = (url.starts_with("http://") || url.starts_with("https://"))
+            && !url.contains(char::is_whitespace);
+        return is_url.then_some((String::new(), url, close + 1));
+    }
+    let label_end = chars[start..].iter().position(|&c| c == ']')? + start;
+    if chars.get(label_end + 1) != Some(&'(') {
+        return None;
+    }
+    let url_end = chars[label_end..].iter().position(|&c| c == ')')? + label_end;
+    let label: String = chars[start + 1..label_end].iter().collect();
+    let url: String = chars[label_end + 2..url_end].iter().collect();
+    if url.trim().is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((label, url, url_end + 1))
+}
+
 fn parse_dispatch_override(
     line: &str,
 ) -> std::result::Result<Option<(DispatchTarget, String)>, String> {
@@ -3692,6 +6434,329 @@ mod tests {
         assert_eq!(app.scroll, before.saturating_sub(5));
     }
 
+    fn screen(app: &App) -> String {
+        let mut terminal =
+            Terminal::new(ratatui::backend::TestBackend::new(60, 20)).expect("test terminal");
+        terminal.draw(|f| ui::draw(f, app)).expect("draw");
+        let buffer = terminal.backend().buffer();
+        buffer.content().iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn render_cache_maps_wrapped_rows_to_lines() {
+        let mut app = app_with_entries(3);
+        app.push_entry(
+            EntryKind::Assistant,
+            format!("[claude]\n{}", "lorem ipsum ".repeat(12)),
+        );
+        app.update_viewport(30, 20);
+        app.ensure_render_cache();
+
+        let lines = app.cached_log_lines().to_vec();
+        let wrapped = |line: &Line<'static>| {
+            Paragraph::new(line.clone())
+                .wrap(Wrap { trim: false })
+                .line_count(30)
+        };
+        assert_eq!(
+            app.cached_log_rows(),
+            Paragraph::new(Text::from(lines.clone()))
+                .wrap(Wrap { trim: false })
+                .line_count(30)
+        );
+        let mut row = 0;
+        for (idx, line) in lines.iter().enumerate() {
+            assert_eq!(app.render_cache.line_row(idx), row);
+            for _ in 0..wrapped(line) {
+                assert_eq!(app.render_cache.line_at_row(row), Some(idx));
+                row += 1;
+            }
+        }
+        assert_eq!(app.render_cache.line_at_row(row), None);
+
+        let (window, skip) = app.cached_log_window(row - 3, 2);
+        let first = app.render_cache.line_at_row(row - 3).expect("line");
+        assert_eq!(
+            usize::from(skip),
+            row - 3 - app.render_cache.line_row(first)
+        );
+        assert_eq!(window.first(), lines.get(first));
+        assert_eq!(app.cached_log_window(row, 2).0.len(), 0);
+    }
+
+    #[test]
+    fn fullscreen_transcript_pane_follows_tail_and_scrolls_back() {
+        let mut app = app_with_entries(40);
+        app.fullscreen = true;
+        app.update_viewport(60, 20);
+
+        let tail = screen(&app);
+        assert!(tail.contains("entry 39"));
+        assert!(!tail.contains("entry 0 "));
+
+        app.autoscroll = false;
+        app.scroll = 0;
+        let top = screen(&app);
+        assert!(top.contains("entry 0 "));
+        assert!(!top.contains("entry 39"));
+    }
+
+    #[test]
+    fn clicks_select_entries_and_copy_code_blocks_from_fence_lines() {
+        let mut app = App::new();
+        app.entries.clear();
+        app.push_entry(EntryKind::User, "show me");
+        app.push_entry(
+            EntryKind::Assistant,
+            "[claude]\nfirst\n```sh\nls -la\n```\nthen\n```rust\nfn main() {}\nlet x = 1;\n```",
+        );
+        app.fullscreen = true;
+        app.update_viewport(60, 20);
+        let rendered = screen(&app);
+        let rows = rendered
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(60)
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>();
+        let row_of =
+            |needle: &str| rows.iter().position(|row| row.contains(needle)).unwrap() as u16;
+
+        let mut fences = app
+            .render_cache
+            .line_map
+            .fences
+            .iter()
+            .map(|(&line, &fence)| (line, fence))
+            .collect::<Vec<_>>();
+        fences.sort();
+        assert_eq!(
+            fences.iter().map(|(_, fence)| *fence).collect::<Vec<_>>(),
+            vec![(1, 0), (1, 0), (1, 1), (1, 1)]
+        );
+
+        app.handle_transcript_click(2, row_of("show me"));
+        assert_eq!(app.selected_entry, Some(0));
+        assert!(app.pending_copy.is_none());
+
+        app.handle_transcript_click(12, row_of("\u{2500}\u{2500}\u{2500} rust"));
+        assert_eq!(app.selected_entry, Some(1));
+        assert_eq!(
+            app.pending_copy.as_ref().map(|copy| copy.text.as_str()),
+            Some("fn main() {}\nlet x = 1;")
+        );
+
+        // Selection highlights the entry's gutter.
+        app.ensure_render_cache();
+        let first = entry_line_range(
+            &app.render_cache.line_map.entry_rows,
+            1,
+            app.cached_log_lines().len(),
+        )
+        .start;
+        assert_eq!(
+            app.cached_log_lines()[first].spans[0].style,
+            app.theme_palette().hint_selected_style()
+        );
+
+        // Clicks outside the pane are ignored; blank pane rows clear the selection.
+        app.handle_transcript_click(2, 19);
+        assert_eq!(app.selected_entry, Some(1));
+        app.entries.truncate(2);
+        app.invalidate_render_cache();
+        app.ensure_render_cache();
+        screen(&app);
+        app.handle_transcript_click(2, app.transcript_area.get().bottom() - 1);
+        assert_eq!(app.selected_entry, None);
+    }
+
+    #[test]
+    fn transcript_find_highlights_wrapped_hits_and_scrolls_to_them() {
+        let mut app = app_with_entries(40);
+        let long = "lorem ipsum ".repeat(8);
+        app.push_entry(
+            EntryKind::Assistant,
+            format!("[claude]\n{long}needle in the haystack"),
+        );
+        app.fullscreen = true;
+        app.update_viewport(60, 20);
+        screen(&app);
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        assert!(matches!(app.mode, Mode::TranscriptSearch));
+        for c in "entry 1".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        // "entry 1", "entry 10".."entry 19"; the newest hit is current.
+        assert_eq!(app.find.hits.len(), 11);
+        assert_eq!(app.find.current, 10);
+        assert!(!app.autoscroll);
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE));
+        assert_eq!(app.find.current, 0);
+        let first_hit = screen(&app);
+        assert!(first_hit.contains("entry 1 "));
+        assert!(!first_hit.contains("entry 39"));
+        app.handle_key(KeyEvent::new(KeyCode::Char('N'), KeyModifiers::NONE));
+        assert_eq!(app.find.current, 10);
+        assert!(screen(&app).contains("entry 19"));
+
+        // A query split across a wrap_spans break still matches.
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        app.open_find("ipsum needle");
+        assert_eq!(app.find.hits.len(), 1);
+        assert!(app.find.hits[0].pieces.len() > 1);
+        app.ensure_render_cache();
+        let current = app.theme_palette().hint_selected_style();
+        let highlighted = app
+            .cached_log_lines()
+            .iter()
+            .flat_map(|line| &line.spans)
+            .filter(|span| span.style == current)
+            .map(|span| span.content.as_ref())
+            .collect::<String>();
+        assert_eq!(highlighted, "ipsum needle");
+
+        // Inline mode shows the hit in a panel instead of the scrollback.
+        app.fullscreen = false;
+        app.open_find("haystack");
+        let panel = screen(&app);
+        assert!(panel.contains("find"));
+        assert!(panel.contains("needle in the haystack"));
+
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(app.mode, Mode::Normal));
+        assert!(app.find.hits.is_empty());
+    }
+
+    #[test]
+    fn copy_command_picks_answers_and_code_blocks() {
+        let mut app = App::new();
+        app.entries.clear();
+        app.push_entry(EntryKind::Assistant, "[codex]\nolder answer");
+        app.push_entry(EntryKind::User, "again");
+        app.push_entry(
+            EntryKind::Assistant,
+            "[claude]\nnewer\n```\nfirst()\n```\n```py\nsecond()\n```",
+        );
+        let copy = |app: &mut App, args: &str| {
+            app.pending_copy = None;
+            app.handle_copy_command(args);
+            app.pending_copy.clone().map(|copy| copy.text)
+        };
+
+        let last = copy(&mut app, "").expect("last answer");
+        assert!(last.starts_with("newer\n```"));
+        assert!(!last.contains("[claude]"));
+        assert_eq!(copy(&mut app, "2").as_deref(), Some("older answer"));
+        assert_eq!(copy(&mut app, "code").as_deref(), Some("second()"));
+        assert_eq!(copy(&mut app, "code 1").as_deref(), Some("first()"));
+        assert_eq!(copy(&mut app, "code 3"), None);
+        assert_eq!(copy(&mut app, "nope"), None);
+        assert!(app.entries.last().unwrap().text.starts_with("usage: /copy"));
+
+        app.selected_entry = Some(1);
+        assert_eq!(copy(&mut app, "").as_deref(), Some("again"));
+        app.handle_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::ALT));
+        let pending = app.pending_copy.take().expect("pending copy");
+        assert_eq!(pending.text, "second()");
+
+        // Nothing is reported until the clipboard write returns.
+        let entries = app.entries.len();
+        app.report_copy(&pending, Ok(()));
+        assert_eq!(app.last_status, "copied code block 2 (1 lines)");
+        assert_eq!(app.entries.len(), entries);
+        app.report_copy(
+            &pending,
+            Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "closed",
+            )),
+        );
+        assert_eq!(app.last_status, "copy of code block 2 failed");
+        let last = app.entries.last().expect("error entry");
+        assert!(matches!(last.kind, EntryKind::Error));
+        assert_eq!(last.text, "copy failed: closed");
+    }
+
+    #[test]
+    fn background_picks_default_theme_until_one_is_chosen() {
+        let mut app = App::with_options(StartupOptions {
+            background: Some(Background::Light),
+            no_color: true,
+            ..StartupOptions::default()
+        });
+        assert_eq!(app.theme_name(), "daylight");
+
+        app.update_viewport(60, 20);
+        let mut terminal =
+            Terminal::new(ratatui::backend::TestBackend::new(60, 20)).expect("test terminal");
+        terminal.draw(|f| ui::draw(f, &app)).expect("draw");
+        let buffer = terminal.backend().buffer();
+        assert!(buffer
+            .content()
+            .iter()
+            .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+
+        // A detected default is not persisted, so the next start detects again.
+        assert!(!app.theme_chosen);
+        app.handle_theme_change("fjord");
+        assert!(app.theme_chosen);
+        assert_eq!(app.theme_name(), "fjord");
+    }
+
+    #[test]
+    fn multi_agent_run_streams_into_split_columns_until_stacked() {
+        assert_eq!(App::new().agent_layout, AgentLayout::Stacked);
+        let mut app = App::with_options(StartupOptions {
+            fullscreen: true,
+            ..StartupOptions::default()
+        });
+        assert_eq!(app.agent_layout, AgentLayout::Split);
+        app.entries.clear();
+        app.push_entry(EntryKind::Assistant, "[claude]\nclaude says hi");
+        let codex_body = (0..30)
+            .map(|i| format!("codex row {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        app.push_entry(EntryKind::Assistant, format!("[codex]\n{codex_body}"));
+        app.agent_entries.insert(Provider::Claude, 0);
+        app.agent_entries.insert(Provider::Codex, 1);
+        app.split_entries = app.agent_entries.clone();
+        app.running = true;
+        app.update_viewport(60, 20);
+
+        assert!(app.split_view_active());
+        let transcript = flatten_lines_to_plain(&app.render_log_lines_inner(60));
+        assert!(!transcript.iter().any(|line| line.contains("says hi")));
+        let split = screen(&app);
+        assert!(split.contains("claude says hi"));
+        assert!(split.contains("codex row 29"));
+
+        // PgUp scrolls only the focused column.
+        app.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::ALT));
+        app.handle_key(KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE));
+        assert_eq!(app.split_scroll_back(Provider::Codex), 5);
+        assert_eq!(app.split_scroll_back(Provider::Claude), 0);
+        assert!(!screen(&app).contains("codex row 29"));
+
+        // The columns outlive the run until the next prompt.
+        app.clear_running_state();
+        assert!(app.split_view_active());
+        assert_eq!(app.split_scroll_back(Provider::Codex), 5);
+        assert!(screen(&app).contains("claude says hi"));
+        app.fullscreen = false;
+        assert!(!app.split_view_active());
+        app.fullscreen = true;
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL));
+        assert!(!app.split_view_active());
+        let transcript = flatten_lines_to_plain(&app.render_log_lines_inner(60));
+        assert!(transcript
+            .iter()
+            .any(|line| line.contains("claude says hi")));
+    }
+
     #[test]
     fn pagedown_near_bottom_reenables_autoscroll() {
         let mut app = app_with_entries(40);
@@ -4297,6 +7362,54 @@ mod tests {
         assert_eq!(rendered, vec!["codex  │ streaming".to_string()]);
     }
 
+    #[test]
+    fn markdown_tables_align_columns_and_fit_the_width() {
+        let mut app = App::new();
+        app.entries.clear();
+        app.push_entry(
+            EntryKind::Assistant,
+            "[codex]\n| Name | Qty |\n|:-----|----:|\n| apple | 3 |\n| kiwi | 12 |",
+        );
+
+        let rendered = flatten_lines_to_plain(&app.render_entries_lines(80));
+
+        assert_eq!(rendered[0], "codex  │ Name  │ Qty");
+        assert_eq!(rendered[1], "       │ ──────┼────");
+        assert_eq!(rendered[2], "       │ apple │   3");
+        assert_eq!(rendered[3], "       │ kiwi  │  12");
+
+        app.entries.clear();
+        app.push_entry(
+            EntryKind::Assistant,
+            "[codex]\n| Step | Notes |\n|---|---|\n| 1 | run the migrations before starting |",
+        );
+        let rendered = flatten_lines_to_plain(&app.render_entries_lines(40));
+        assert!(rendered
+            .iter()
+            .all(|line| UnicodeWidthStr::width(line.as_str()) <= 40));
+        assert_eq!(rendered[2], "       │ 1    │ run the migrations ");
+        assert_eq!(rendered[3], "       │      │ before starting");
+    }
+
+    #[test]
+    fn markdown_lists_quotes_links_and_rules_render_without_raw_syntax() {
+        let mut app = App::new();
+        app.entries.clear();
+        app.push_entry(
+            EntryKind::Assistant,
+            "[codex]\n1. first step that wraps onto the next row\n   - nested\n> quoted **text**\n---\nsee [docs](https://x.dev)",
+        );
+
+        let rendered = flatten_lines_to_plain(&app.render_entries_lines(40));
+
+        assert_eq!(rendered[0], "codex  │ 1. first step that wraps onto ");
+        assert_eq!(rendered[1], "       │    the next row");
+        assert_eq!(rendered[2], "       │    ◦ nested");
+        assert_eq!(rendered[3], "       │ │ quoted text");
+        assert_eq!(rendered[4], format!("       │ {}", "─".repeat(31)));
+        assert_eq!(rendered[5], "       │ see docs (https://x.dev)");
+    }
+
     #[test]
     fn assistant_blank_content_line_keeps_divider() {
         let mut app = App::new();
@@ -4324,6 +7437,36 @@ mod tests {
         assert!(app.pending_pastes.is_empty());
     }
 
+    #[test]
+    fn editor_round_trip_expands_and_recollapses_pastes() {
+        let mut app = App::new();
+        app.handle_key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL));
+        assert!(std::mem::take(&mut app.editor_requested));
+        app.handle_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL));
+        app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        assert!(std::mem::take(&mut app.editor_requested));
+        // Ctrl+E alone still jumps to the end of the line.
+        app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        assert!(!app.editor_requested);
+
+        let payload = "line ".repeat(220);
+        app.insert_str("review: ");
+        app.handle_paste_event(&payload);
+        let seed = app.expand_pending_pastes(&app.input);
+        assert_eq!(seed, format!("review: {payload}"));
+
+        app.load_composed_input(&format!("Please review:\n\n{payload}\nthanks"));
+        assert_eq!(app.pending_pastes.len(), 1);
+        assert!(app.input.starts_with("Please review:\n\n[Pasted Content "));
+        assert!(app.input.ends_with("]\nthanks"));
+        assert_eq!(app.cursor, app.input.len());
+
+        // An edited paste no longer matches and stays expanded.
+        app.load_composed_input("line line edited");
+        assert!(app.pending_pastes.is_empty());
+        assert_eq!(app.input, "line line edited");
+    }
+
     #[test]
     fn short_paste_keeps_plain_text() {
         let mut app = App::new();
@@ -4365,9 +7508,138 @@ mod tests {
         assert_eq!(wait_count, 1);
     }
 
+    #[test]
+    fn background_is_only_detected_without_a_saved_theme() {
+        let app = App::new();
+        let mut snapshot = serde_json::to_value(SessionSnapshot {
+            primary_provider: Provider::Claude,
+            theme: None,
+            custom_theme: None,
+            entries: Vec::new(),
+            history: Vec::new(),
+            session_id: app.session_id.clone(),
+        })
+        .expect("json");
+        assert!(!snapshot_has_theme(&snapshot.to_string()));
+        snapshot["theme"] = serde_json::to_value(app.theme).expect("theme");
+        assert!(snapshot_has_theme(&snapshot.to_string()));
+        assert!(!snapshot_has_theme("not json"));
+    }
+
     #[test]
     fn transcript_restore_defaults_to_hidden_when_memory_is_available() {
         assert!(!restore_transcript_on_start(true));
         assert!(restore_transcript_on_start(false));
     }
+
+    #[test]
+    fn session_command_reports_backend_unavailable_in_tests() {
+        let mut app = App::new();
+        app.input = "/session new work".to_string();
+        app.cursor = app.input.len();
+
+        app.submit_current_line(false);
+
+        let last = app.entries.last().expect("expected session error entry");
+        assert!(matches!(last.kind, EntryKind::Error));
+        assert!(last.text.contains("memory backend unavailable"));
+    }
+
+    #[test]
+    fn startup_banner_shows_memory_scope() {
+        let mut app = App::new();
+        app.entries.clear();
+        app.maybe_show_startup_banner();
+
+        let rendered = flatten_lines_to_plain(&app.render_entries_lines(120));
+        let expected = format!("memory: {}", app.memory_scope.session_id);
+        assert!(rendered.iter().any(|line| line.contains(&expected)));
+        assert_eq!(app.session_id, app.memory_scope.session_id);
+    }
+
+    #[test]
+    fn context_command_reports_transcript_fallback_budget() {
+        let mut app = App::new();
+        app.history.push("explain the retry loop".to_string());
+        app.push_entry(EntryKind::User, "explain the retry loop");
+        app.push_entry(EntryKind::Assistant, "[claude]\nit backs off exponentially");
+
+        app.handle_context_command("");
+        let report = &app.entries.last().expect("report").text;
+        assert!(report.contains("context budget for last prompt: explain the retry loop"));
+        assert!(report.contains("transcript   2 items"));
+        assert!(report.contains("limit 1500"));
+    }
+
+    #[test]
+    fn context_preview_expands_pastes_and_mentions() {
+        let mut app = App::new();
+        app.available_providers = vec![Provider::Claude, Provider::Codex];
+        app.pending_pastes.push(PendingPaste {
+            marker: "[Pasted Content 9 chars]".to_string(),
+            content: "let x = 1".to_string(),
+        });
+
+        app.handle_context_command("@codex explain [Pasted Content 9 chars]");
+        assert!(matches!(app.mode, Mode::ContextPreview));
+        let preview = app.context_preview.as_ref().expect("preview");
+        assert!(preview
+            .lines
+            .iter()
+            .any(|line| matches!(line, PreviewLine::Heading(h) if h.starts_with("codex · "))));
+        assert!(preview
+            .lines
+            .contains(&PreviewLine::Body("explain let x = 1".to_string())));
+
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(app.context_preview.as_ref().expect("preview").scroll, 1);
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(app.mode, Mode::Normal));
+        assert!(app.context_preview.is_none());
+    }
+
+    #[test]
+    fn context_preview_matches_the_dispatched_prompt() {
+        for with_memory in [true, false] {
+            let mut app = App::new();
+            app.available_providers = vec![Provider::Claude];
+            app.primary_provider = Provider::Claude;
+            if with_memory {
+                let memory = MemoryStore::open_in_memory().expect("open");
+                for (role, text) in [
+                    ("user", "explain the retry loop"),
+                    ("assistant", "it backs off exponentially"),
+                ] {
+                    memory
+                        .append_message(&app.session_id, role, None, text)
+                        .expect("append");
+                }
+                app.memory = Some(memory);
+            }
+            app.push_entry(EntryKind::User, "explain the retry loop");
+            app.push_entry(EntryKind::Assistant, "[claude]\nit backs off exponentially");
+
+            app.handle_context_command("explain the retry loop");
+            let preview = app.context_preview.take().expect("preview");
+            let body = preview
+                .lines
+                .iter()
+                .filter_map(|line| match line {
+                    PreviewLine::Body(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .skip(1)
+                .collect::<Vec<_>>()
+                .join("\n");
+            app.mode = Mode::Normal;
+
+            app.input = "explain the retry loop".to_string();
+            app.cursor = app.input.len();
+            app.submit_current_line(false);
+            let prompts = app.spawned_prompts.as_ref().expect("dispatched");
+            let sent = prompts.get(&Provider::Claude).expect("claude prompt");
+            assert_eq!(&body, sent, "memory: {with_memory}");
+            assert!(sent.contains("user: explain the retry loop"));
+        }
+    }
 }
@@ -0,0 +1,164 @@
+//! Terminal background detection, used to pick a light or dark default theme.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+/// How long to wait for the terminal to answer the colour query.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+/// Extra time a slow terminal gets to finish its reply once `QUERY_TIMEOUT`
+/// passed, so the bytes are consumed here instead of typed into the composer.
+const LATE_REPLY_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Background {
+    Light,
+    Dark,
+}
+
+/// Ask the terminal with OSC 11, then fall back to `COLORFGBG`.
+/// `None` when neither says anything useful.
+pub(crate) fn detect() -> Option<Background> {
+    query_osc11().or_else(|| {
+        std::env::var("COLORFGBG")
+            .ok()
+            .and_then(|value| parse_colorfgbg(&value))
+    })
+}
+
+/// `NO_COLOR` set to any non-empty value: style with modifiers only.
+pub(crate) fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+fn query_osc11() -> Option<Background> {
+    let is_tty =
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDOUT_FILENO) == 1 };
+    if !is_tty {
+        return None;
+    }
+    enable_raw_mode().ok()?;
+    let reply = read_reply();
+    disable_raw_mode().ok();
+    parse_osc11_reply(&reply)
+}
+
+/// Send OSC 11 followed by a primary device attributes request. Every
+/// terminal answers the latter, so once its reply is in there is nothing
+/// left to wait for, even on terminals that ignore OSC 11.
+fn read_reply() -> Vec<u8> {
+    let mut reply = Vec::new();
+    let mut stdout = std::io::stdout();
+    if stdout.write_all(b"\x1b]11;?\x1b\\\x1b[c").is_err() || stdout.flush().is_err() {
+        return reply;
+    }
+    read_until_attributes(&mut reply, QUERY_TIMEOUT);
+    if !device_attributes_received(&reply) {
+        read_until_attributes(&mut reply, LATE_REPLY_GRACE);
+    }
+    reply
+}
+
+/// Append stdin bytes to `reply` until the device attributes answer is in
+/// or `timeout` passes.
+fn read_until_attributes(reply: &mut Vec<u8>, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 128];
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = unsafe { libc::poll(&mut fd, 1, left.as_millis().max(1) as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        // Read the fd directly: std's buffered stdin could hold back bytes
+        // that crossterm's event reader needs later.
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if n <= 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n as usize]);
+        if device_attributes_received(reply) {
+            break;
+        }
+    }
+}
+
+/// `ESC [ ? ... c`, the answer to the device attributes request.
+fn device_attributes_received(reply: &[u8]) -> bool {
+    reply
+        .windows(3)
+        .position(|w| w == b"\x1b[?")
+        .is_some_and(|start| reply[start..].contains(&b'c'))
+}
+
+/// Parses `ESC ] 11 ; rgb:RRRR/GGGG/BBBB` (1-4 hex digits per channel).
+fn parse_osc11_reply(reply: &[u8]) -> Option<Background> {
+    let text = String::from_utf8_lossy(reply);
+    let start = text.find("]11;rgb:")? + "]11;rgb:".len();
+    let body = &text[start..];
+    let end = body.find(['\x07', '\x1b']).unwrap_or(body.len());
+    let mut channels = body[..end].split('/').map(|hex| {
+        let hex = hex.get(..hex.len().min(4))?;
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len() as u32)) - 1;
+        Some(value as f64 / max as f64)
+    });
+    let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+    Some(classify(0.2126 * r + 0.7152 * g + 0.0722 * b))
+}
+
+/// `COLORFGBG` is `fg;bg` (sometimes `fg;default;bg`) in ANSI colour indices.
+fn parse_colorfgbg(value: &str) -> Option<Background> {
+    let bg = value.rsplit(';').next()?.trim().parse::<u8>().ok()?;
+    match bg {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+fn classify(luminance: f64) -> Background {
+    if luminance > 0.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc11_replies_are_classified_by_luminance() {
+        assert_eq!(
+            parse_osc11_reply(b"\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b[?62;c"),
+            Some(Background::Light)
+        );
+        assert_eq!(
+            parse_osc11_reply(b"\x1b]11;rgb:1e1e/1e1e/2e2e\x07"),
+            Some(Background::Dark)
+        );
+        assert_eq!(
+            parse_osc11_reply(b"\x1b]11;rgb:fd/f6/e3\x07"),
+            Some(Background::Light)
+        );
+        assert_eq!(parse_osc11_reply(b"\x1b[?62;22c"), None);
+        assert!(device_attributes_received(
+            b"\x1b]11;rgb:0/0/0\x07\x1b[?1;2c"
+        ));
+    }
+
+    #[test]
+    fn colorfgbg_uses_the_last_field_as_background() {
+        assert_eq!(parse_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(parse_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("12;default;7"), Some(Background::Light));
+        assert_eq!(parse_colorfgbg("default;default"), None);
+    }
+}
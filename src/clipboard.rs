@@ -0,0 +1,109 @@
+//! Copy text to the system clipboard, through the terminal (OSC 52) or a
+//! local clipboard command.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::config::ClipboardBackend;
+
+/// Local clipboard commands, tried in order until one succeeds.
+const LOCAL_COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+];
+
+/// Copy `text` with `backend`. The local backend falls back to OSC 52 when
+/// no clipboard command is available.
+pub(crate) fn copy(text: &str, backend: ClipboardBackend) -> io::Result<()> {
+    if backend == ClipboardBackend::Local && copy_local(text).is_ok() {
+        return Ok(());
+    }
+    let tmux = std::env::var_os("TMUX").is_some_and(|value| !value.is_empty());
+    let mut stdout = io::stdout();
+    stdout.write_all(osc52_sequence(text, tmux).as_bytes())?;
+    stdout.flush()
+}
+
+fn copy_local(text: &str) -> io::Result<()> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    for (program, args) in LOCAL_COMMANDS {
+        if *program == "wl-copy" && !wayland {
+            continue;
+        }
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard command found (wl-copy, xclip, xsel, pbcopy)",
+    ))
+}
+
+/// `ESC ] 52 ; c ; <base64> BEL`: set the clipboard selection. Inside tmux
+/// the sequence is wrapped in a DCS passthrough so it reaches the outer
+/// terminal.
+fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"))
+    } else {
+        osc
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_payload_is_base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode("héllo\n".as_bytes()), "aMOpbGxvCg==");
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn osc52_is_wrapped_for_tmux_passthrough() {
+        assert_eq!(
+            osc52_sequence("hi", true),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// User settings read from `~/.dagent/config.toml`. Every field is optional.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    pub(crate) memory: MemoryConfig,
+    pub(crate) context: ContextConfig,
+    pub(crate) clipboard: ClipboardConfig,
+}
+
+/// `[clipboard]`: how `/copy` reaches the system clipboard.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ClipboardConfig {
+    pub(crate) backend: ClipboardBackend,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ClipboardBackend {
+    /// Terminal escape; works over SSH and inside tmux.
+    #[default]
+    Osc52,
+    /// `wl-copy`, `xclip`, `xsel` or `pbcopy`, falling back to OSC 52.
+    Local,
+}
+
+/// Default `memory.recency_half_life_days`.
+pub(crate) const DEFAULT_HALF_LIFE_DAYS: f64 = 14.0;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct MemoryConfig {
+    /// Groups of project paths that share one memory session.
+    pub(crate) shared_scopes: Vec<SharedScope>,
+    /// How `build_context` ranks older messages: `hybrid`, `fts` or `vector`.
+    pub(crate) retrieval: RetrievalStrategy,
+    /// Age in days at which a recalled message counts half as relevant; `0` disables decay.
+    pub(crate) recency_half_life_days: f64,
+    /// Limits applied automatically while dagent runs.
+    pub(crate) retention: RetentionPolicy,
+}
+
+/// `[memory.retention]`; unset or zero limits are not enforced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub(crate) struct RetentionPolicy {
+    /// Delete records older than this many days.
+    pub(crate) max_age_days: Option<u64>,
+    /// Keep at most this many messages in each session.
+    pub(crate) max_rows_per_session: Option<usize>,
+    /// Delete the oldest records until the live data fits in this many MB.
+    pub(crate) max_db_mb: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub(crate) fn max_age_days(&self) -> Option<u64> {
+        self.max_age_days.filter(|days| *days > 0)
+    }
+
+    pub(crate) fn max_rows_per_session(&self) -> Option<usize> {
+        self.max_rows_per_session.filter(|rows| *rows > 0)
+    }
+
+    pub(crate) fn max_db_bytes(&self) -> Option<u64> {
+        self.max_db_mb
+            .filter(|mb| *mb > 0)
+            .map(|mb| mb.saturating_mul(1024 * 1024))
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_age_days().is_some()
+            || self.max_rows_per_session().is_some()
+            || self.max_db_bytes().is_some()
+    }
+
+    /// One-line summary for `/mem`, e.g. `max age 90d · 5000 rows/session`.
+    pub(crate) fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(days) = self.max_age_days() {
+            parts.push(format!("max age {days}d"));
+        }
+        if let Some(rows) = self.max_rows_per_session() {
+            parts.push(format!("{rows} rows/session"));
+        }
+        if let Some(mb) = self.max_db_mb.filter(|mb| *mb > 0) {
+            parts.push(format!("{mb} MB"));
+        }
+        if parts.is_empty() {
+            "off".to_string()
+        } else {
+            parts.join(" · ")
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            shared_scopes: Vec::new(),
+            retrieval: RetrievalStrategy::default(),
+            recency_half_life_days: DEFAULT_HALF_LIFE_DAYS,
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RetrievalStrategy {
+    /// bm25 keyword matches blended with vector similarity and recency.
+    #[default]
+    Hybrid,
+    /// bm25 keyword matches only.
+    Fts,
+    /// Hashed n-gram vector similarity only.
+    Vector,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct SharedScope {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) paths: Vec<String>,
+}
+
+/// How much memory goes into each agent prompt, measured in estimated tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ContextBudget {
+    /// Tokens for summaries plus recalled messages; pins are not counted.
+    pub(crate) max_tokens: usize,
+    /// Most recent messages always considered.
+    pub(crate) recent: usize,
+    /// Older messages pulled in by retrieval.
+    pub(crate) search: usize,
+    /// Token cap for the transcript fallback used when memory is unavailable.
+    pub(crate) transcript_tokens: usize,
+    /// Entry cap for the transcript fallback.
+    pub(crate) transcript_entries: usize,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self {
+            max_tokens: 900,
+            recent: 2,
+            search: 8,
+            transcript_tokens: 1500,
+            transcript_entries: 18,
+        }
+    }
+}
+
+/// Partial budget; unset fields fall through to the next, less specific level.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct BudgetOverrides {
+    pub(crate) max_tokens: Option<usize>,
+    pub(crate) recent: Option<usize>,
+    pub(crate) search: Option<usize>,
+    pub(crate) transcript_tokens: Option<usize>,
+    pub(crate) transcript_entries: Option<usize>,
+}
+
+impl BudgetOverrides {
+    fn apply(&self, budget: &mut ContextBudget) {
+        let fields = [
+            (self.max_tokens, &mut budget.max_tokens),
+            (self.recent, &mut budget.recent),
+            (self.search, &mut budget.search),
+            (self.transcript_tokens, &mut budget.transcript_tokens),
+            (self.transcript_entries, &mut budget.transcript_entries),
+        ];
+        for (value, slot) in fields {
+            if let Some(value) = value {
+                *slot = value;
+            }
+        }
+    }
+}
+
+/// `[context.providers.<name>]`: budget overrides plus the model that agent runs.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ProviderContext {
+    #[serde(flatten)]
+    pub(crate) budget: BudgetOverrides,
+    /// Model name used to pick `[context.models.<model>]`; the agent CLI's own
+    /// configuration still decides which model actually runs.
+    pub(crate) model: Option<String>,
+}
+
+/// `[context]` table: defaults, then `[context.providers.<name>]`, then `[context.models.<model>]`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ContextConfig {
+    #[serde(flatten)]
+    pub(crate) defaults: BudgetOverrides,
+    pub(crate) providers: HashMap<String, ProviderContext>,
+    pub(crate) models: HashMap<String, BudgetOverrides>,
+}
+
+impl ContextConfig {
+    /// Model configured for `provider`, if any.
+    pub(crate) fn model(&self, provider: &str) -> Option<&str> {
+        self.providers
+            .get(provider)
+            .and_then(|entry| entry.model.as_deref())
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+    }
+
+    pub(crate) fn budget(&self, provider: &str) -> ContextBudget {
+        let mut budget = ContextBudget::default();
+        self.defaults.apply(&mut budget);
+        if let Some(entry) = self.providers.get(provider) {
+            entry.budget.apply(&mut budget);
+        }
+        if let Some(overrides) = self
+            .model(provider)
+            .and_then(|model| self.models.get(model))
+        {
+            overrides.apply(&mut budget);
+        }
+        budget
+    }
+}
+
+impl Config {
+    pub(crate) fn load() -> Result<Self> {
+        let path = config_file_path();
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("read config {}", path.display()));
+            }
+        };
+        toml::from_str(&raw).with_context(|| format!("parse config {}", path.display()))
+    }
+}
+
+fn dagent_home() -> PathBuf {
+    if let Some(home) = std::env::var_os("HOME") {
+        PathBuf::from(home).join(".dagent")
+    } else {
+        PathBuf::from(".dagent")
+    }
+}
+
+fn config_file_path() -> PathBuf {
+    dagent_home().join("config.toml")
+}
+
+/// Directory scanned for user theme files.
+pub(crate) fn themes_dir() -> PathBuf {
+    dagent_home().join("themes")
+}
+
+/// Memory scope derived from the working directory at startup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MemoryScope {
+    /// Session id used as the default memory stream for this scope.
+    pub(crate) session_id: String,
+    /// Human-readable label shown in the startup banner and session list.
+    pub(crate) label: String,
+}
+
+impl MemoryScope {
+    pub(crate) fn detect(config: &MemoryConfig) -> Self {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self::for_root(&project_root(&cwd), config)
+    }
+
+    pub(crate) fn for_root(root: &Path, config: &MemoryConfig) -> Self {
+        for shared in &config.shared_scopes {
+            let matches = shared
+                .paths
+                .iter()
+                .map(|raw| normalize_path(&expand_home(raw)))
+                .any(|path| root.starts_with(&path));
+            if matches {
+                if let Some(slug) = slugify(&shared.name) {
+                    return Self {
+                        session_id: format!("shared-{slug}"),
+                        label: format!("shared:{}", shared.name.trim()),
+                    };
+                }
+            }
+        }
+
+        let display = root.display().to_string();
+        let base = root
+            .file_name()
+            .and_then(|name| slugify(&name.to_string_lossy()))
+            .unwrap_or_else(|| "root".to_string());
+        Self {
+            session_id: format!("{}-{:08x}", base, fnv1a32(display.as_bytes())),
+            label: display,
+        }
+    }
+}
+
+/// Nearest ancestor containing `.git`, or `cwd` itself outside a repository.
+pub(crate) fn project_root(cwd: &Path) -> PathBuf {
+    let cwd = normalize_path(cwd);
+    for dir in cwd.ancestors() {
+        if dir.join(".git").exists() {
+            return dir.to_path_buf();
+        }
+    }
+    cwd
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+pub(crate) fn expand_home(raw: &str) -> PathBuf {
+    let raw = raw.trim();
+    if let Some(rest) = raw.strip_prefix("~/") {
+        if let Some(home) = std::env::var_os("HOME") {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    PathBuf::from(raw)
+}
+
+/// Lowercase, filesystem-safe form of a name; `None` when nothing usable remains.
+pub(crate) fn slugify(name: &str) -> Option<String> {
+    let mut id = String::new();
+    for ch in name.trim().chars() {
+        if ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == '.' {
+            id.extend(ch.to_lowercase());
+        } else if (ch.is_whitespace() || ch == '/') && !id.ends_with('-') && !id.is_empty() {
+            id.push('-');
+        }
+    }
+    let id = id.trim_matches(|c| c == '-' || c == '.').to_string();
+    if id.is_empty() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+// Stable across builds, unlike `DefaultHasher`.
+fn fnv1a32(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for b in bytes {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_id_is_stable_and_distinct_per_root() {
+        let config = MemoryConfig::default();
+        let a = MemoryScope::for_root(Path::new("/work/repo-a"), &config);
+        let b = MemoryScope::for_root(Path::new("/other/repo-a"), &config);
+
+        assert!(a.session_id.starts_with("repo-a-"));
+        assert_eq!(a, MemoryScope::for_root(Path::new("/work/repo-a"), &config));
+        assert_ne!(a.session_id, b.session_id);
+        assert_eq!(a.label, "/work/repo-a");
+    }
+
+    #[test]
+    fn slugify_normalizes_user_input() {
+        assert_eq!(slugify("Bug Bash 42").as_deref(), Some("bug-bash-42"));
+        assert_eq!(slugify("  repo/feature ").as_deref(), Some("repo-feature"));
+        assert_eq!(slugify("../etc").as_deref(), Some("etc"));
+        assert!(slugify("  ").is_none());
+    }
+
+    #[test]
+    fn shared_scope_groups_configured_paths() {
+        let config: Config = toml::from_str(
+            r#"
+            [[memory.shared_scopes]]
+            name = "Platform"
+            paths = ["/work/api", "/work/web"]
+            "#,
+        )
+        .expect("parse config");
+
+        let api = MemoryScope::for_root(Path::new("/work/api"), &config.memory);
+        let web = MemoryScope::for_root(Path::new("/work/web/frontend"), &config.memory);
+        let other = MemoryScope::for_root(Path::new("/work/cli"), &config.memory);
+
+        assert_eq!(api.session_id, "shared-platform");
+        assert_eq!(api, web);
+        assert_ne!(other.session_id, api.session_id);
+        assert_eq!(config.memory.retrieval, RetrievalStrategy::Hybrid);
+    }
+
+    #[test]
+    fn retrieval_strategy_parses_from_config() {
+        let config: Config = toml::from_str("[memory]\nretrieval = \"fts\"\n").expect("parse");
+        assert_eq!(config.memory.retrieval, RetrievalStrategy::Fts);
+        assert!(toml::from_str::<Config>("[memory]\nretrieval = \"gpu\"\n").is_err());
+        assert_eq!(config.memory.recency_half_life_days, DEFAULT_HALF_LIFE_DAYS);
+
+        let config: Config =
+            toml::from_str("[memory]\nrecency_half_life_days = 3.5\n").expect("parse");
+        assert_eq!(config.memory.recency_half_life_days, 3.5);
+    }
+
+    #[test]
+    fn clipboard_backend_parses_from_config() {
+        assert_eq!(Config::default().clipboard.backend, ClipboardBackend::Osc52);
+        let config: Config = toml::from_str("[clipboard]\nbackend = \"local\"\n").expect("parse");
+        assert_eq!(config.clipboard.backend, ClipboardBackend::Local);
+    }
+
+    #[test]
+    fn retention_policy_ignores_zero_limits() {
+        assert_eq!(RetentionPolicy::default().describe(), "off");
+
+        let config: Config = toml::from_str(
+            "[memory.retention]\nmax_age_days = 90\nmax_rows_per_session = 0\nmax_db_mb = 200\n",
+        )
+        .expect("parse");
+        let policy = config.memory.retention;
+        assert!(policy.is_enabled());
+        assert_eq!(policy.max_rows_per_session(), None);
+        assert_eq!(policy.max_db_bytes(), Some(200 * 1024 * 1024));
+        assert_eq!(policy.describe(), "max age 90d · 200 MB");
+    }
+
+    #[test]
+    fn context_budget_layers_defaults_provider_and_model() {
+        let config: Config = toml::from_str(
+            r#"
+            [context]
+            max_tokens = 1200
+            recent = 3
+
+            [context.providers.codex]
+            max_tokens = 2000
+            model = "gpt-5"
+
+            [context.models."gpt-5"]
+            search = 12
+            "#,
+        )
+        .expect("parse config");
+
+        let claude = config.context.budget("claude");
+        assert_eq!(config.context.model("claude"), None);
+        assert_eq!(
+            (claude.max_tokens, claude.recent, claude.search),
+            (1200, 3, 8)
+        );
+
+        assert_eq!(config.context.model("codex"), Some("gpt-5"));
+        let codex = config.context.budget("codex");
+        assert_eq!(
+            (codex.max_tokens, codex.recent, codex.search),
+            (2000, 3, 12)
+        );
+        assert_eq!(
+            Config::default().context.budget("codex"),
+            ContextBudget::default()
+        );
+    }
+}
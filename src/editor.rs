@@ -0,0 +1,78 @@
+//! Compose a prompt in the user's editor (`$VISUAL`, then `$EDITOR`).
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+
+/// Editor command line; may carry arguments, e.g. `code --wait`.
+pub(crate) fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Open `editor` on a temp file holding `text` and return what was saved.
+/// The trailing newline most editors add is dropped.
+pub(crate) fn edit_text(editor: &str, text: &str) -> Result<String> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let path =
+        std::env::temp_dir().join(format!("dagent-prompt-{}-{nanos}.md", std::process::id()));
+    // `create_new` refuses an existing path (or a planted symlink), and the
+    // prompt stays readable by the owner only.
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .with_context(|| format!("write {}", path.display()))?;
+
+    // Through the shell so editor commands with arguments work.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("dagent")
+        .arg(&path)
+        .status()
+        .with_context(|| format!("run editor {editor}"));
+    let edited = fs::read_to_string(&path).with_context(|| format!("read {}", path.display()));
+    fs::remove_file(&path).ok();
+
+    let status = status?;
+    if !status.success() {
+        bail!("editor {editor} exited with {status}; prompt left unchanged");
+    }
+    let mut edited = edited?;
+    if edited.ends_with('\n') {
+        edited.pop();
+        if edited.ends_with('\r') {
+            edited.pop();
+        }
+    }
+    Ok(edited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edited_file_is_read_back_without_trailing_newline() {
+        // Stand-in editor: overwrite the file the way a user saving would.
+        let editor = r#"sh -c 'printf "hello there\nbye\n" > "$1"' sh"#;
+        let edited = edit_text(editor, "hello world\nbye\n").expect("edit");
+        assert_eq!(edited, "hello there\nbye");
+
+        let err = edit_text("false", "unchanged").expect_err("editor failure");
+        assert!(err.to_string().contains("prompt left unchanged"));
+    }
+}
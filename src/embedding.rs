@@ -0,0 +1,147 @@
+//! Local text vectors for memory retrieval.
+//!
+//! Words and character trigrams are hashed into a fixed number of buckets and
+//! weighted by log term frequency. Vectors are stored sparse; IDF weighting is
+//! applied at query time against the candidate set, so nothing has to be kept
+//! in sync when messages are deleted.
+
+use std::collections::HashMap;
+
+const BUCKETS: u32 = 1 << 14;
+const TRIGRAM_WEIGHT: f32 = 0.5;
+
+/// Sparse vector as sorted `(bucket, weight)` pairs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SparseVector(pub(crate) Vec<(u32, f32)>);
+
+impl SparseVector {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn to_blob(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.0.len() * 8);
+        for (bucket, weight) in &self.0 {
+            out.extend_from_slice(&bucket.to_le_bytes());
+            out.extend_from_slice(&weight.to_le_bytes());
+        }
+        out
+    }
+
+    pub(crate) fn from_blob(blob: &[u8]) -> Self {
+        let pairs = blob
+            .chunks_exact(8)
+            .map(|chunk| {
+                let bucket = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                let weight = f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                (bucket, weight)
+            })
+            .collect();
+        Self(pairs)
+    }
+}
+
+pub(crate) fn embed(text: &str) -> SparseVector {
+    let mut counts: HashMap<u32, f32> = HashMap::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+    {
+        *counts.entry(bucket(word.as_bytes(), 0)).or_default() += 1.0;
+
+        let chars = format!(" {word} ").chars().collect::<Vec<_>>();
+        for gram in chars.windows(3) {
+            let gram = gram.iter().collect::<String>();
+            *counts.entry(bucket(gram.as_bytes(), 1)).or_default() += TRIGRAM_WEIGHT;
+        }
+    }
+
+    let mut pairs = counts
+        .into_iter()
+        .map(|(bucket, tf)| (bucket, 1.0 + tf.ln_1p()))
+        .collect::<Vec<_>>();
+    pairs.sort_by_key(|(bucket, _)| *bucket);
+    SparseVector(pairs)
+}
+
+/// Inverse document frequency per bucket over `docs`.
+pub(crate) fn idf(docs: &[&SparseVector]) -> HashMap<u32, f32> {
+    let mut df: HashMap<u32, u32> = HashMap::new();
+    for doc in docs {
+        for (bucket, _) in &doc.0 {
+            *df.entry(*bucket).or_default() += 1;
+        }
+    }
+    let n = docs.len() as f32;
+    df.into_iter()
+        .map(|(bucket, df)| (bucket, ((n + 1.0) / (df as f32 + 1.0)).ln() + 1.0))
+        .collect()
+}
+
+/// Cosine similarity of two sparse vectors after IDF weighting.
+pub(crate) fn cosine(a: &SparseVector, b: &SparseVector, idf: &HashMap<u32, f32>) -> f32 {
+    let weight = |bucket: u32| idf.get(&bucket).copied().unwrap_or(1.0);
+    let norm = |v: &SparseVector| {
+        v.0.iter()
+            .map(|(bucket, w)| (w * weight(*bucket)).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    };
+
+    let (mut i, mut j, mut dot) = (0, 0, 0.0f32);
+    while i < a.0.len() && j < b.0.len() {
+        let (ba, wa) = a.0[i];
+        let (bb, wb) = b.0[j];
+        match ba.cmp(&bb) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                let w = weight(ba);
+                dot += wa * w * wb * w;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    let denom = norm(a) * norm(b);
+    if denom <= f32::EPSILON {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+fn bucket(bytes: &[u8], salt: u8) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5 ^ salt as u32;
+    for b in bytes {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash % BUCKETS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob_roundtrip_preserves_vector() {
+        let v = embed("render the spinner frame");
+        assert!(!v.is_empty());
+        assert_eq!(SparseVector::from_blob(&v.to_blob()), v);
+    }
+
+    #[test]
+    fn cosine_prefers_morphological_neighbours() {
+        let query = embed("configuring retries");
+        let close = embed("the retry configuration lives in config.toml");
+        let far = embed("spinner animation frames");
+        let docs = [&close, &far];
+        let idf = idf(&docs);
+
+        assert!(cosine(&query, &close, &idf) > cosine(&query, &far, &idf));
+        assert!((cosine(&close, &close, &idf) - 1.0).abs() < 1e-4);
+    }
+}
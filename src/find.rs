@@ -0,0 +1,253 @@
+//! In-transcript search (`Ctrl+F` / `/find`) over rendered transcript lines.
+//!
+//! Assistant answers are pre-wrapped by `wrap_spans`, so one sentence can
+//! sit on several lines. Lines marked as continuations are searched as one
+//! run of text with the line above, and a hit split by a wrap is
+//! highlighted piece by piece.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
+
+/// One occurrence of the query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct FindHit {
+    /// `(line, char columns)` for each line the hit touches.
+    pub(super) pieces: Vec<(usize, Range<usize>)>,
+}
+
+impl FindHit {
+    pub(super) fn line(&self) -> usize {
+        self.pieces[0].0
+    }
+}
+
+#[derive(Default)]
+pub(super) struct TranscriptFind {
+    pub(super) query: String,
+    /// Keys edit the query; otherwise n/N move between hits.
+    pub(super) editing: bool,
+    pub(super) hits: Vec<FindHit>,
+    /// Index into `hits` of the hit the view is on.
+    pub(super) current: usize,
+}
+
+impl TranscriptFind {
+    /// `3/12`, or `0/0` without hits.
+    pub(super) fn position(&self) -> String {
+        if self.hits.is_empty() {
+            "0/0".to_string()
+        } else {
+            format!("{}/{}", self.current + 1, self.hits.len())
+        }
+    }
+
+    pub(super) fn step(&mut self, forward: bool) {
+        let len = self.hits.len();
+        if len == 0 {
+            return;
+        }
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+    }
+}
+
+/// Every hit of `query` in `lines`, in order. The first `gutter(line)` spans
+/// of a line (the assistant label column) are not searched. Matching ignores
+/// case unless the query has an uppercase letter.
+pub(super) fn find_hits(
+    lines: &[Line],
+    gutter: impl Fn(usize) -> usize,
+    continuations: &HashSet<usize>,
+    query: &str,
+) -> Vec<FindHit> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |ch: char| {
+        if case_sensitive {
+            ch
+        } else {
+            ch.to_lowercase().next().unwrap_or(ch)
+        }
+    };
+    let needle = query.chars().map(fold).collect::<Vec<_>>();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    // (folded char, line, column); `None` separates lines that are not
+    // wrap continuations so no hit crosses them.
+    let mut text: Vec<Option<(char, usize, usize)>> = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if idx > 0 && !continuations.contains(&idx) {
+            text.push(None);
+        }
+        let skip = gutter(idx).min(line.spans.len());
+        let mut col = line.spans[..skip]
+            .iter()
+            .map(|span| span.content.chars().count())
+            .sum::<usize>();
+        for span in &line.spans[skip..] {
+            for ch in span.content.chars() {
+                text.push(Some((fold(ch), idx, col)));
+                col += 1;
+            }
+        }
+    }
+
+    let mut hits = Vec::new();
+    let mut start = 0;
+    while start + needle.len() <= text.len() {
+        let window = &text[start..start + needle.len()];
+        let matched = window
+            .iter()
+            .zip(&needle)
+            .all(|(cell, want)| cell.is_some_and(|(ch, _, _)| ch == *want));
+        if !matched {
+            start += 1;
+            continue;
+        }
+        let mut pieces: Vec<(usize, Range<usize>)> = Vec::new();
+        for (_, line, col) in window.iter().flatten() {
+            match pieces.last_mut() {
+                Some((last, range)) if last == line && range.end == *col => range.end += 1,
+                _ => pieces.push((*line, *col..*col + 1)),
+            }
+        }
+        hits.push(FindHit { pieces });
+        start += needle.len();
+    }
+    hits
+}
+
+/// Restyle the hits that fall on `lines`, which start at transcript line
+/// `first_line`. The current hit gets `current_style`, the rest `hit_style`.
+pub(super) fn highlight_hits(
+    lines: &mut [Line<'static>],
+    first_line: usize,
+    find: &TranscriptFind,
+    hit_style: Style,
+    current_style: Style,
+) {
+    let end_line = first_line + lines.len();
+    let mut ranges: Vec<Vec<(Range<usize>, Style)>> = vec![Vec::new(); lines.len()];
+    for (idx, hit) in find.hits.iter().enumerate() {
+        let style = if idx == find.current {
+            current_style
+        } else {
+            hit_style
+        };
+        for (line, cols) in &hit.pieces {
+            if (first_line..end_line).contains(line) {
+                ranges[line - first_line].push((cols.clone(), style));
+            }
+        }
+    }
+    for (line, ranges) in lines.iter_mut().zip(&ranges) {
+        if !ranges.is_empty() {
+            restyle(line, ranges);
+        }
+    }
+}
+
+/// Patch `style` onto the char `ranges` (sorted, disjoint) of `line`,
+/// splitting spans where a range starts or ends inside them.
+fn restyle(line: &mut Line<'static>, ranges: &[(Range<usize>, Style)]) {
+    let mut spans = Vec::with_capacity(line.spans.len() + ranges.len() * 2);
+    let mut col = 0;
+    for span in line.spans.drain(..) {
+        let chars = span.content.chars().collect::<Vec<_>>();
+        let (start, end) = (col, col + chars.len());
+        col = end;
+        let overlapping = ranges
+            .iter()
+            .filter(|(range, _)| range.start < end && range.end > start)
+            .collect::<Vec<_>>();
+        if overlapping.is_empty() {
+            spans.push(span);
+            continue;
+        }
+        let piece =
+            |from: usize, to: usize| chars[from - start..to - start].iter().collect::<String>();
+        let mut pos = start;
+        for (range, style) in overlapping {
+            let (from, to) = (range.start.max(start), range.end.min(end));
+            if from > pos {
+                spans.push(Span::styled(piece(pos, from), span.style));
+            }
+            spans.push(Span::styled(piece(from, to), span.style.patch(*style)));
+            pos = to;
+        }
+        if pos < end {
+            spans.push(Span::styled(piece(pos, end), span.style));
+        }
+    }
+    line.spans = spans;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Modifier;
+
+    fn plain(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn hits_cross_wrapped_lines_but_not_separate_ones() {
+        let lines = vec![
+            Line::from(vec![
+                Span::raw("claude │"),
+                Span::raw(" "),
+                Span::raw("the quick "),
+            ]),
+            Line::from(vec![
+                Span::raw("       │"),
+                Span::raw(" "),
+                Span::raw("brown fox"),
+            ]),
+            Line::from(vec![
+                Span::raw("       │"),
+                Span::raw(" "),
+                Span::raw("Quick"),
+            ]),
+        ];
+        let continuations = HashSet::from([1]);
+        let hits = find_hits(&lines, |_| 2, &continuations, "quick brown");
+        assert_eq!(
+            hits,
+            vec![FindHit {
+                pieces: vec![(0, 13..19), (1, 9..14)],
+            }]
+        );
+        // Lowercase queries ignore case; the label column is never searched.
+        assert_eq!(find_hits(&lines, |_| 2, &continuations, "quick").len(), 2);
+        assert_eq!(find_hits(&lines, |_| 2, &continuations, "Quick").len(), 1);
+        assert!(find_hits(&lines, |_| 2, &continuations, "claude").is_empty());
+        assert!(find_hits(&lines, |_| 2, &continuations, "foxquick").is_empty());
+
+        let mut find = TranscriptFind {
+            hits,
+            ..TranscriptFind::default()
+        };
+        let mut highlighted = lines.clone();
+        let reversed = Style::default().add_modifier(Modifier::REVERSED);
+        highlight_hits(&mut highlighted, 0, &find, Style::default(), reversed);
+        assert_eq!(plain(&highlighted[0]), plain(&lines[0]));
+        assert_eq!(highlighted[0].spans[3].content, "quick ");
+        assert_eq!(highlighted[0].spans[3].style, reversed);
+        assert_eq!(highlighted[1].spans[2].content, "brown");
+        assert_eq!(highlighted[1].spans[3].content, " fox");
+
+        find.step(false);
+        assert_eq!(find.position(), "1/1");
+    }
+}
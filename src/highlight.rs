@@ -0,0 +1,750 @@
+//! Lexer-based highlighting for fenced code blocks in agent replies.
+//!
+//! Each language is a small table of keywords, comment and quote markers fed to
+//! one shared tokenizer; only constructs that span lines (block comments,
+//! triple-quoted or backtick strings) carry state from one line to the next.
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Span;
+
+use super::ThemePalette;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Lang {
+    Rust,
+    Python,
+    Script,
+    Go,
+    Shell,
+    Json,
+    Toml,
+    Yaml,
+    Diff,
+}
+
+impl Lang {
+    /// Language named by a fence info string such as `rust` or `ts title="x"`.
+    pub(super) fn from_fence(info: &str) -> Option<Self> {
+        let tag = info.split_whitespace().next()?.to_ascii_lowercase();
+        match tag.as_str() {
+            "rust" | "rs" => Some(Self::Rust),
+            "python" | "py" | "python3" => Some(Self::Python),
+            "typescript" | "ts" | "tsx" | "javascript" | "js" | "jsx" | "mjs" | "cjs" => {
+                Some(Self::Script)
+            }
+            "go" | "golang" => Some(Self::Go),
+            "sh" | "bash" | "shell" | "zsh" | "console" | "shellsession" => Some(Self::Shell),
+            "json" | "jsonc" | "json5" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "diff" | "patch" | "udiff" => Some(Self::Diff),
+            _ => None,
+        }
+    }
+
+    fn spec(self) -> &'static Spec {
+        match self {
+            Self::Rust => &RUST,
+            Self::Python => &PYTHON,
+            Self::Script => &SCRIPT,
+            Self::Go => &GO,
+            Self::Shell => &SHELL,
+            Self::Json => &JSON,
+            Self::Toml => &TOML,
+            Self::Yaml => &YAML,
+            Self::Diff => &PLAIN,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Plain,
+    Keyword,
+    Str,
+    Number,
+    Comment,
+    Type,
+    Added,
+    Removed,
+    Hunk,
+    Header,
+}
+
+impl Token {
+    fn style(self, palette: ThemePalette) -> Style {
+        let base = Style::default().bg(palette.code_bg);
+        match self {
+            Self::Plain => base.fg(palette.code_fg),
+            Self::Keyword => base.fg(palette.syntax_keyword),
+            Self::Str => base.fg(palette.syntax_string),
+            Self::Number => base.fg(palette.syntax_number),
+            Self::Comment => base
+                .fg(palette.syntax_comment)
+                .add_modifier(Modifier::ITALIC),
+            Self::Type => base.fg(palette.syntax_type),
+            Self::Added => base.fg(palette.diff_added),
+            Self::Removed => base.fg(palette.diff_removed),
+            Self::Hunk => base.fg(palette.diff_hunk),
+            Self::Header => base.fg(palette.code_fg).add_modifier(Modifier::BOLD),
+        }
+    }
+}
+
+/// Lexical rules for one language.
+struct Spec {
+    keywords: &'static [&'static str],
+    /// Constants coloured like numbers (`true`, `None`, `nil`).
+    literals: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Openers of strings that may run over several lines; each closes itself.
+    multiline_quotes: &'static [&'static str],
+    /// Capitalised identifiers are type names.
+    capitalized_types: bool,
+    /// `'` opens a string only when it closes within a char literal's length,
+    /// so Rust lifetimes stay plain.
+    char_literals: bool,
+    /// `$name` and `${...}` are variables.
+    dollar_variables: bool,
+    /// A string directly followed by `:` is an object key.
+    string_keys: bool,
+}
+
+const PLAIN: Spec = Spec {
+    keywords: &[],
+    literals: &[],
+    line_comments: &[],
+    block_comment: None,
+    quotes: &[],
+    multiline_quotes: &[],
+    capitalized_types: false,
+    char_literals: false,
+    dollar_variables: false,
+    string_keys: false,
+};
+
+const RUST: Spec = Spec {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while",
+    ],
+    literals: &["true", "false"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\''],
+    multiline_quotes: &["\""],
+    capitalized_types: true,
+    char_literals: true,
+    ..PLAIN
+};
+
+const PYTHON: Spec = Spec {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "match", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+        "with", "yield",
+    ],
+    literals: &["True", "False", "None"],
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    multiline_quotes: &["\"\"\"", "'''"],
+    capitalized_types: true,
+    ..PLAIN
+};
+
+const SCRIPT: Spec = Spec {
+    keywords: &[
+        "abstract",
+        "as",
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "declare",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "implements",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "of",
+        "private",
+        "protected",
+        "public",
+        "readonly",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "type",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    literals: &["true", "false", "null", "undefined", "NaN"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    multiline_quotes: &["`"],
+    capitalized_types: true,
+    ..PLAIN
+};
+
+const GO: Spec = Spec {
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "type",
+        "var",
+    ],
+    literals: &["true", "false", "nil", "iota"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    multiline_quotes: &["`"],
+    capitalized_types: true,
+    ..PLAIN
+};
+
+const SHELL: Spec = Spec {
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "function", "in", "select", "return", "export", "local", "readonly", "declare", "unset",
+        "source", "exit",
+    ],
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    dollar_variables: true,
+    ..PLAIN
+};
+
+const JSON: Spec = Spec {
+    literals: &["true", "false", "null"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    string_keys: true,
+    ..PLAIN
+};
+
+const TOML: Spec = Spec {
+    literals: &["true", "false"],
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    multiline_quotes: &["\"\"\"", "'''"],
+    ..PLAIN
+};
+
+const YAML: Spec = Spec {
+    literals: &["true", "false", "null", "yes", "no", "on", "off", "~"],
+    line_comments: &["#"],
+    quotes: &['"', '\''],
+    ..PLAIN
+};
+
+/// Construct left open at the end of the previous line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Carry {
+    None,
+    BlockComment(&'static str),
+    Str(&'static str),
+}
+
+/// Highlights one fenced block line by line.
+pub(super) struct Highlighter {
+    lang: Option<Lang>,
+    /// Untagged fences are checked once for a unified diff.
+    sniffed: bool,
+    carry: Carry,
+}
+
+impl Highlighter {
+    pub(super) fn new(info: &str) -> Self {
+        let lang = Lang::from_fence(info);
+        Self {
+            lang,
+            sniffed: lang.is_some() || !info.trim().is_empty(),
+            carry: Carry::None,
+        }
+    }
+
+    pub(super) fn diff() -> Self {
+        Self {
+            lang: Some(Lang::Diff),
+            sniffed: true,
+            carry: Carry::None,
+        }
+    }
+
+    pub(super) fn line(&mut self, line: &str, palette: ThemePalette) -> Vec<Span<'static>> {
+        if !self.sniffed {
+            self.sniffed = true;
+            if is_diff_header(line) {
+                self.lang = Some(Lang::Diff);
+            }
+        }
+        if line.is_empty() {
+            return vec![Span::styled(" ".to_string(), Token::Plain.style(palette))];
+        }
+        let mut out = Tokens::default();
+        match self.lang {
+            None => out.push(Token::Plain, line),
+            Some(Lang::Diff) => out.push(diff_token(line), line),
+            Some(lang) => self.tokenize_line(lang, line, &mut out),
+        }
+        out.into_spans(palette)
+    }
+
+    fn tokenize_line(&mut self, lang: Lang, line: &str, out: &mut Tokens) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut start = 0;
+        if self.carry == Carry::None {
+            match lang {
+                Lang::Toml => start = toml_prefix(&chars, out),
+                Lang::Yaml => start = yaml_prefix(&chars, out),
+                _ => {}
+            }
+        }
+        self.tokenize(lang.spec(), &chars, start, out);
+    }
+
+    fn tokenize(&mut self, spec: &Spec, chars: &[char], mut i: usize, out: &mut Tokens) {
+        match self.carry {
+            Carry::None => {}
+            Carry::BlockComment(close) => {
+                let end = find_close(chars, i, close, false);
+                out.push_chars(Token::Comment, &chars[i..end.unwrap_or(chars.len())]);
+                let Some(end) = end else { return };
+                self.carry = Carry::None;
+                i = end;
+            }
+            Carry::Str(close) => {
+                let end = find_close(chars, i, close, true);
+                out.push_chars(Token::Str, &chars[i..end.unwrap_or(chars.len())]);
+                let Some(end) = end else { return };
+                self.carry = Carry::None;
+                i = end;
+            }
+        }
+
+        while i < chars.len() {
+            let c = chars[i];
+            let prev = i.checked_sub(1).map(|p| chars[p]);
+
+            if spec.line_comments.iter().any(|m| {
+                starts_with(chars, i, m) && (*m != "#" || prev.is_none_or(char::is_whitespace))
+            }) {
+                out.push_chars(Token::Comment, &chars[i..]);
+                return;
+            }
+
+            if let Some((open, close)) = spec.block_comment {
+                if starts_with(chars, i, open) {
+                    let body = i + open.chars().count();
+                    let end = find_close(chars, body, close, false);
+                    out.push_chars(Token::Comment, &chars[i..end.unwrap_or(chars.len())]);
+                    match end {
+                        Some(end) => i = end,
+                        None => {
+                            self.carry = Carry::BlockComment(close);
+                            return;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            if let Some(quote) = spec
+                .multiline_quotes
+                .iter()
+                .find(|q| starts_with(chars, i, q))
+            {
+                let body = i + quote.chars().count();
+                let end = find_close(chars, body, quote, true);
+                out.push_chars(Token::Str, &chars[i..end.unwrap_or(chars.len())]);
+                match end {
+                    Some(end) => i = end,
+                    None => {
+                        self.carry = Carry::Str(quote);
+                        return;
+                    }
+                }
+                continue;
+            }
+
+            if spec.quotes.contains(&c) {
+                let end = find_close(chars, i + 1, &c.to_string(), true);
+                let is_lifetime =
+                    spec.char_literals && c == '\'' && end.is_none_or(|end| end > i + 4);
+                if !is_lifetime {
+                    let end = end.unwrap_or(chars.len());
+                    let is_key = spec.string_keys
+                        && chars[end..].iter().find(|c| !c.is_whitespace()) == Some(&':');
+                    let token = if is_key { Token::Type } else { Token::Str };
+                    out.push_chars(token, &chars[i..end]);
+                    i = end;
+                    continue;
+                }
+            }
+
+            if spec.dollar_variables && c == '$' {
+                let end = if chars.get(i + 1) == Some(&'{') {
+                    find_close(chars, i + 2, "}", false).unwrap_or(chars.len())
+                } else {
+                    scan(chars, i + 1, |c| c.is_alphanumeric() || c == '_').max(i + 2)
+                };
+                let end = end.min(chars.len());
+                out.push_chars(Token::Type, &chars[i..end]);
+                i = end;
+                continue;
+            }
+
+            let after_word = prev.is_some_and(is_ident_char);
+            if c.is_ascii_digit() && !after_word {
+                let end = scan(chars, i, |c| c.is_alphanumeric() || c == '_' || c == '.');
+                out.push_chars(Token::Number, &chars[i..end]);
+                i = end;
+                continue;
+            }
+
+            if (c.is_alphabetic() || c == '_' || c == '~') && !after_word {
+                let end = if c == '~' {
+                    i + 1
+                } else {
+                    scan(chars, i, is_ident_char)
+                };
+                let word: String = chars[i..end].iter().collect();
+                let token = if spec.keywords.contains(&word.as_str()) {
+                    Token::Keyword
+                } else if spec.literals.contains(&word.as_str()) {
+                    Token::Number
+                } else if spec.capitalized_types && c.is_uppercase() {
+                    Token::Type
+                } else {
+                    Token::Plain
+                };
+                out.push(token, &word);
+                i = end;
+                continue;
+            }
+
+            out.push_chars(Token::Plain, &chars[i..i + 1]);
+            i += 1;
+        }
+    }
+}
+
+/// Whether `line` opens a unified diff (`diff --git`, `--- a/...`, `@@ -1 +1 @@`).
+pub(super) fn is_diff_header(line: &str) -> bool {
+    line.starts_with("diff --git ") || line.starts_with("--- ") || line.starts_with("@@ -")
+}
+
+/// Number of lines, from the start of `lines`, that form a unified diff pasted
+/// without a fence. Zero when the lines do not start one.
+pub(super) fn unfenced_diff_len(lines: &[&str]) -> usize {
+    let opens = match lines {
+        [first, ..] if first.starts_with("diff --git ") => true,
+        [first, second, ..] => first.starts_with("--- ") && second.starts_with("+++ "),
+        _ => false,
+    };
+    if !opens {
+        return 0;
+    }
+    lines
+        .iter()
+        .take_while(|line| {
+            ["diff ", "index ", "--- ", "+++ ", "@@", "+", "-", " ", "\\"]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        })
+        .count()
+}
+
+fn diff_token(line: &str) -> Token {
+    if ["diff ", "index ", "+++", "---"]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+    {
+        Token::Header
+    } else if line.starts_with("@@") {
+        Token::Hunk
+    } else if line.starts_with('+') {
+        Token::Added
+    } else if line.starts_with('-') {
+        Token::Removed
+    } else {
+        Token::Plain
+    }
+}
+
+/// `[table]` headers and `key =` prefixes; returns where the value starts.
+fn toml_prefix(chars: &[char], out: &mut Tokens) -> usize {
+    let indent = scan(chars, 0, char::is_whitespace);
+    if chars.get(indent) == Some(&'[') {
+        let end = find_close(chars, indent, "]", false).unwrap_or(chars.len());
+        let end = scan(chars, end, |c| c == ']');
+        out.push_chars(Token::Plain, &chars[..indent]);
+        out.push_chars(Token::Keyword, &chars[indent..end]);
+        return end;
+    }
+    let key_end = scan(chars, indent, |c| {
+        c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' ' | '\t')
+    });
+    if key_end > indent && chars.get(key_end) == Some(&'=') {
+        out.push_chars(Token::Plain, &chars[..indent]);
+        out.push_chars(Token::Type, &chars[indent..key_end]);
+        return key_end;
+    }
+    0
+}
+
+/// `key:` prefixes after optional indentation and list dashes.
+fn yaml_prefix(chars: &[char], out: &mut Tokens) -> usize {
+    let mut key_start = scan(chars, 0, char::is_whitespace);
+    while chars.get(key_start) == Some(&'-') && chars.get(key_start + 1) == Some(&' ') {
+        key_start = scan(chars, key_start + 1, char::is_whitespace);
+    }
+    let key_end = scan(chars, key_start, |c| {
+        c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' ' | '/')
+    });
+    let is_key = key_end > key_start
+        && chars.get(key_end) == Some(&':')
+        && chars.get(key_end + 1).is_none_or(|c| c.is_whitespace());
+    if !is_key {
+        return 0;
+    }
+    out.push_chars(Token::Plain, &chars[..key_start]);
+    out.push_chars(Token::Type, &chars[key_start..key_end]);
+    key_end
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn scan(chars: &[char], from: usize, keep: impl Fn(char) -> bool) -> usize {
+    from + chars
+        .get(from..)
+        .unwrap_or_default()
+        .iter()
+        .take_while(|&&c| keep(c))
+        .count()
+}
+
+fn starts_with(chars: &[char], at: usize, pattern: &str) -> bool {
+    pattern
+        .chars()
+        .enumerate()
+        .all(|(offset, p)| chars.get(at + offset) == Some(&p))
+}
+
+/// Index just past the first `close` at or after `from`, skipping
+/// backslash-escaped characters when `escapes` is set.
+fn find_close(chars: &[char], from: usize, close: &str, escapes: bool) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if escapes && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if starts_with(chars, i, close) {
+            return Some(i + close.chars().count());
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Adjacent runs of the same token merged into one span each.
+#[derive(Default)]
+struct Tokens(Vec<(Token, String)>);
+
+impl Tokens {
+    fn push(&mut self, token: Token, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some((last, buf)) if *last == token => buf.push_str(text),
+            _ => self.0.push((token, text.to_string())),
+        }
+    }
+
+    fn push_chars(&mut self, token: Token, chars: &[char]) {
+        self.push(token, &chars.iter().collect::<String>());
+    }
+
+    fn into_spans(self, palette: ThemePalette) -> Vec<Span<'static>> {
+        self.0
+            .into_iter()
+            .map(|(token, text)| Span::styled(text, token.style(palette)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ThemePreset;
+    use super::*;
+
+    fn tokens(lang: &str, lines: &[&str]) -> Vec<Vec<(String, Token)>> {
+        let palette = ThemePreset::Fjord.palette();
+        let mut highlighter = Highlighter::new(lang);
+        let styles = [
+            Token::Plain,
+            Token::Keyword,
+            Token::Str,
+            Token::Number,
+            Token::Comment,
+            Token::Type,
+            Token::Added,
+            Token::Removed,
+            Token::Hunk,
+            Token::Header,
+        ];
+        lines
+            .iter()
+            .map(|line| {
+                highlighter
+                    .line(line, palette)
+                    .into_iter()
+                    .map(|span| {
+                        let token = styles
+                            .iter()
+                            .copied()
+                            .find(|t| t.style(palette) == span.style)
+                            .expect("known style");
+                        (span.content.into_owned(), token)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn token_of(line: &[(String, Token)], text: &str) -> Option<Token> {
+        line.iter()
+            .find(|(t, _)| t == text)
+            .map(|(_, token)| *token)
+    }
+
+    #[test]
+    fn rust_keywords_strings_and_lifetimes() {
+        let lines = tokens(
+            "rust",
+            &[
+                "fn parse<'a>(s: &'a str) -> Option<u32> { // note",
+                "    let c = 'x'; \"multi",
+                "line\" /* open",
+                "closed */ 42",
+            ],
+        );
+        assert_eq!(token_of(&lines[0], "fn"), Some(Token::Keyword));
+        assert_eq!(token_of(&lines[0], "Option"), Some(Token::Type));
+        assert_eq!(token_of(&lines[0], "// note"), Some(Token::Comment));
+        assert!(lines[0].iter().all(|(_, t)| *t != Token::Str));
+        assert_eq!(token_of(&lines[1], "'x'"), Some(Token::Str));
+        assert_eq!(token_of(&lines[1], "\"multi"), Some(Token::Str));
+        assert_eq!(token_of(&lines[2], "line\""), Some(Token::Str));
+        assert_eq!(token_of(&lines[3], "closed */"), Some(Token::Comment));
+        assert_eq!(token_of(&lines[3], "42"), Some(Token::Number));
+    }
+
+    #[test]
+    fn data_formats_mark_keys_and_sections() {
+        let json = tokens("json", &[r#"{"name": "dagent", "ok": true}"#]);
+        assert_eq!(token_of(&json[0], "\"name\""), Some(Token::Type));
+        assert_eq!(token_of(&json[0], "\"dagent\""), Some(Token::Str));
+        assert_eq!(token_of(&json[0], "true"), Some(Token::Number));
+
+        let toml = tokens("toml", &["[memory.retention]", "max_age_days = 90 # keep"]);
+        assert_eq!(
+            token_of(&toml[0], "[memory.retention]"),
+            Some(Token::Keyword)
+        );
+        assert_eq!(token_of(&toml[1], "max_age_days "), Some(Token::Type));
+        assert_eq!(token_of(&toml[1], "90"), Some(Token::Number));
+        assert_eq!(token_of(&toml[1], "# keep"), Some(Token::Comment));
+
+        let yaml = tokens("yml", &["- name: build # ci"]);
+        assert_eq!(token_of(&yaml[0], "name"), Some(Token::Type));
+        assert_eq!(token_of(&yaml[0], "# ci"), Some(Token::Comment));
+
+        let shell = tokens("bash", &["echo \"$HOME\" ${PATH} # done"]);
+        assert_eq!(token_of(&shell[0], "${PATH}"), Some(Token::Type));
+        assert_eq!(token_of(&shell[0], "# done"), Some(Token::Comment));
+    }
+
+    #[test]
+    fn diffs_are_coloured_by_line_even_without_a_tag() {
+        let lines = tokens(
+            "",
+            &["--- a/src/app.rs", "@@ -1 +1 @@", "-old", "+new", " same"],
+        );
+        let kinds: Vec<Token> = lines.iter().map(|line| line[0].1).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Token::Header,
+                Token::Hunk,
+                Token::Removed,
+                Token::Added,
+                Token::Plain
+            ]
+        );
+
+        assert_eq!(
+            unfenced_diff_len(&["--- a/x", "+++ b/x", "@@ -1 +1 @@", "-a", "+b", "", "done"]),
+            5
+        );
+        assert_eq!(unfenced_diff_len(&["- item", "- item"]), 0);
+    }
+}
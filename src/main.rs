@@ -8,16 +8,24 @@ use std::process::{Command, Stdio};
 use anyhow::{Context, Result};
 use crossterm::cursor;
 use crossterm::event::{
-    DisableBracketedPaste, EnableBracketedPaste, KeyboardEnhancementFlags,
-    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen,
 };
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement};
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::Rect;
 use ratatui::{Terminal, TerminalOptions, Viewport};
 use unicode_width::UnicodeWidthChar;
 
 mod app;
+mod background;
+mod clipboard;
+mod config;
+mod editor;
+mod embedding;
 mod memory;
 mod orchestrator;
 mod providers;
@@ -42,13 +50,35 @@ pub(crate) enum DispatchTarget {
 }
 
 fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        match args[1].as_str() {
+    let mut options = app::StartupOptions::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
             "--version" | "-v" => {
                 println!("dagent {}", APP_VERSION);
                 return Ok(());
             }
+            "mem" => {
+                let rest = args.collect::<Vec<_>>();
+                if let Err(err) = run_memory_cli(&rest) {
+                    eprintln!("dagent mem: {err:#}");
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "--session" | "-s" => {
+                let Some(name) = args.next().filter(|v| !v.trim().is_empty()) else {
+                    eprintln!("usage: dagent --session <name>");
+                    std::process::exit(2);
+                };
+                options.session = Some(name);
+            }
+            "--fullscreen" | "-f" => options.fullscreen = true,
+            // Clicks need the transcript on screen, so mouse mode is full-screen.
+            "--mouse" | "-m" => {
+                options.mouse = true;
+                options.fullscreen = true;
+            }
             unknown => {
                 eprintln!("unknown argument: {}", unknown);
                 std::process::exit(2);
@@ -56,13 +86,92 @@ fn main() -> Result<()> {
         }
     }
 
-    let mut terminal = setup_terminal()?;
-    let result = app::run_app(&mut terminal);
-    restore_terminal(&mut terminal)?;
+    // Ask before the TUI takes over stdin, so the reply is not read as keys.
+    // A saved theme wins over the background, so skip the query then.
+    if !app::saved_theme_chosen() {
+        options.background = background::detect();
+    }
+    options.no_color = background::no_color();
+    let fullscreen = options.fullscreen;
+    let mut terminal = setup_terminal(fullscreen, options.mouse)?;
+    let result = app::run_app(&mut terminal, options);
+    restore_terminal(&mut terminal, fullscreen)?;
     result
 }
 
-fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+/// `dagent mem export|import ...`: transfer memory without starting the TUI.
+fn run_memory_cli(args: &[String]) -> Result<()> {
+    const USAGE: &str = "usage: dagent mem export <path> [--format jsonl|md] [--session <name>|--all]\n       dagent mem import <path> [--session <name>]";
+    let Some((sub, rest)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    };
+    let tokens = rest.iter().map(String::as_str).collect::<Vec<_>>();
+    let transfer = match memory::TransferArgs::parse(&tokens) {
+        Ok(transfer) => transfer,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let store = memory::MemoryStore::open_default()?;
+    let session = match transfer.session.as_deref() {
+        Some(name) => Some(
+            store
+                .resolve_session_id(name)?
+                .with_context(|| format!("invalid session name {name}"))?,
+        ),
+        None => None,
+    };
+    match sub.as_str() {
+        "export" => {
+            let scope = if transfer.all_sessions {
+                None
+            } else {
+                Some(session.unwrap_or_else(|| {
+                    let config = config::Config::load().unwrap_or_default();
+                    config::MemoryScope::detect(&config.memory).session_id
+                }))
+            };
+            let count = store.export_to_file(&transfer.path, transfer.format, scope.as_deref())?;
+            println!("exported {} records to {}", count, transfer.path.display());
+        }
+        "import" => {
+            let count = store.import_from_file(&transfer.path, session.as_deref())?;
+            println!(
+                "imported {} new records from {}",
+                count,
+                transfer.path.display()
+            );
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
+
+fn setup_terminal(fullscreen: bool, mouse: bool) -> Result<Terminal<CrosstermBackend<Stdout>>> {
+    if fullscreen {
+        enable_raw_mode().context("enable raw mode")?;
+        crossterm::execute!(std::io::stdout(), EnterAlternateScreen)
+            .context("enter alternate screen")?;
+        if mouse {
+            crossterm::execute!(std::io::stdout(), EnableMouseCapture)
+                .context("enable mouse capture")?;
+        }
+        let terminal = Terminal::with_options(
+            CrosstermBackend::new(std::io::stdout()),
+            TerminalOptions {
+                viewport: Viewport::Fullscreen,
+            },
+        )
+        .context("create fullscreen terminal")?;
+        return Ok(finish_terminal_setup(terminal));
+    }
+
     // ratatui::Terminal::insert_before requires at least one line above the viewport.
     // If cursor starts at row 0, move to row 1 first.
     if matches!(cursor::position(), Ok((_, 0))) {
@@ -74,7 +183,7 @@ fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
     let (term_width, term_height) = crossterm::terminal::size().unwrap_or((80, 24));
     let inline_height = compute_inline_height(term_height);
 
-    let mut terminal = match Terminal::with_options(
+    let terminal = match Terminal::with_options(
         CrosstermBackend::new(std::io::stdout()),
         TerminalOptions {
             viewport: Viewport::Inline(inline_height),
@@ -99,7 +208,17 @@ fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
             .with_context(|| format!("create terminal (inline failed: {inline_err})"))?
         }
     };
+    Ok(finish_terminal_setup(terminal))
+}
+
+fn finish_terminal_setup(
+    mut terminal: Terminal<CrosstermBackend<Stdout>>,
+) -> Terminal<CrosstermBackend<Stdout>> {
+    enable_input_features(&mut terminal);
+    terminal
+}
 
+fn enable_input_features(terminal: &mut Terminal<CrosstermBackend<Stdout>>) {
     if matches!(supports_keyboard_enhancement(), Ok(true)) {
         crossterm::execute!(
             std::io::stdout(),
@@ -110,7 +229,6 @@ fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
     crossterm::execute!(std::io::stdout(), EnableBracketedPaste).ok();
 
     terminal.hide_cursor().ok();
-    Ok(terminal)
 }
 
 fn compute_inline_height(term_height: u16) -> u16 {
@@ -125,12 +243,51 @@ fn compute_inline_height(term_height: u16) -> u16 {
     12u16.min(max_allowed).max(6)
 }
 
-fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    fullscreen: bool,
+) -> Result<()> {
+    suspend_terminal(terminal, fullscreen)?;
+    if !fullscreen {
+        println!();
+    }
+    Ok(())
+}
+
+/// Give the terminal back in its normal mode, on exit or while a child
+/// process such as `$EDITOR` owns it.
+fn suspend_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    fullscreen: bool,
+) -> Result<()> {
     crossterm::execute!(std::io::stdout(), DisableBracketedPaste).ok();
     crossterm::execute!(std::io::stdout(), PopKeyboardEnhancementFlags).ok();
+    if fullscreen {
+        crossterm::execute!(std::io::stdout(), DisableMouseCapture).ok();
+        crossterm::execute!(std::io::stdout(), LeaveAlternateScreen).ok();
+    }
     disable_raw_mode().context("disable raw mode")?;
     terminal.show_cursor().context("show cursor")?;
-    println!();
+    Ok(())
+}
+
+/// Undo `suspend_terminal` and repaint from scratch.
+fn resume_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    fullscreen: bool,
+    mouse: bool,
+) -> Result<()> {
+    enable_raw_mode().context("enable raw mode")?;
+    if fullscreen {
+        crossterm::execute!(std::io::stdout(), EnterAlternateScreen)
+            .context("enter alternate screen")?;
+        if mouse {
+            crossterm::execute!(std::io::stdout(), EnableMouseCapture)
+                .context("enable mouse capture")?;
+        }
+    }
+    enable_input_features(terminal);
+    terminal.clear().context("clear terminal")?;
     Ok(())
 }
 
@@ -145,14 +302,39 @@ fn default_commands() -> Vec<String> {
         "/theme solarized".to_string(),
         "/theme aurora".to_string(),
         "/theme ember".to_string(),
+        "/theme daylight".to_string(),
+        "/layout split".to_string(),
+        "/layout stacked".to_string(),
+        "/find".to_string(),
+        "/copy".to_string(),
+        "/copy code".to_string(),
         "/tool echo hello".to_string(),
         "/tool time".to_string(),
         "/tool bash ls -la".to_string(),
         "/mem".to_string(),
         "/mem show 20".to_string(),
         "/mem find spinner".to_string(),
+        "/mem find --all".to_string(),
+        "/mem find --events".to_string(),
+        "/mem use".to_string(),
         "/mem prune 200".to_string(),
+        "/mem compact".to_string(),
         "/mem clear".to_string(),
+        "/mem visibility".to_string(),
+        "/mem stats".to_string(),
+        "/mem vacuum".to_string(),
+        "/mem pin".to_string(),
+        "/mem pins".to_string(),
+        "/mem unpin".to_string(),
+        "/mem export memory.jsonl".to_string(),
+        "/mem import memory.jsonl".to_string(),
+        "/context".to_string(),
+        "/context @all ".to_string(),
+        "/session".to_string(),
+        "/session new".to_string(),
+        "/session switch".to_string(),
+        "/session rename".to_string(),
+        "/session delete".to_string(),
         "/clear".to_string(),
         "/exit".to_string(),
     ]
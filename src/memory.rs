@@ -1,15 +1,149 @@
 use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection, OptionalExtension};
+use anyhow::{bail, Context, Result};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    expand_home, ContextBudget, RetentionPolicy, RetrievalStrategy, DEFAULT_HALF_LIFE_DAYS,
+};
+use crate::embedding::{self, SparseVector};
+use crate::providers::codex::is_cjk_char;
+use crate::providers::RunEvent;
+
+#[path = "migrations.rs"]
+mod migrations;
 
-const RECENT_LIMIT: usize = 2;
-const SEARCH_LIMIT: usize = 8;
-const CONTEXT_CHAR_LIMIT: usize = 2000;
 const MAX_LINE_CHARS: usize = 500;
 const MAX_PREVIEW_CHARS: usize = 180;
+const VECTOR_SCAN_LIMIT: usize = 2000;
+const FTS_CANDIDATE_LIMIT: usize = 32;
+const MIN_SIMILARITY: f32 = 0.12;
+const KEYWORD_WEIGHT: f32 = 0.5;
+const VECTOR_WEIGHT: f32 = 0.5;
+/// Share of relevance a fully decayed message keeps.
+const DECAY_FLOOR: f32 = 0.4;
+/// Added for assistant replies from the agent the prompt is going to.
+const SAME_AGENT_BONUS: f32 = 0.15;
+const MAX_SUMMARY_CHARS: usize = 1200;
+const COMPACT_INPUT_CHAR_LIMIT: usize = 24_000;
+const COMPACT_MIN_MESSAGES: usize = 4;
+const MAX_PIN_CHARS: usize = 300;
+const MAX_EVENT_COMMAND_CHARS: usize = 400;
+const MAX_EVENT_OUTPUT_CHARS: usize = 600;
+/// Per-row bytes counted on top of the payload when estimating what a
+/// `max_db_mb` pass frees (row header, index entries, page slack).
+const RETENTION_ROW_OVERHEAD: u64 = 64;
+const DELETE_ORPHAN_VECTORS: &str =
+    "DELETE FROM message_vectors WHERE message_id NOT IN (SELECT id FROM messages)";
+/// Messages a compaction may take: shared rows for a shared digest (`?4`
+/// NULL), or only the rows private to agent `?4` for that agent's digest.
+const COMPACTABLE_ROWS: &str = "session_id = ?1 AND id BETWEEN ?2 AND ?3
+     AND ((?4 IS NULL AND visibility = 'shared') OR (visibility = 'private' AND agent = ?4))";
+
+#[derive(Debug, Clone)]
+pub(crate) struct SessionInfo {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) created_at: i64,
+    pub(crate) updated_at: i64,
+    pub(crate) message_count: usize,
+}
+
+/// One `messages` row as written by `/mem export` and read by `/mem import`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ExportRecord {
+    pub(crate) session: String,
+    pub(crate) role: String,
+    #[serde(default)]
+    pub(crate) agent: Option<String>,
+    pub(crate) content: String,
+    pub(crate) created_at: i64,
+    /// Row visibility, `shared` or `private`; older exports omit it.
+    #[serde(default = "shared_visibility")]
+    pub(crate) visibility: String,
+}
+
+fn shared_visibility() -> String {
+    "shared".to_string()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    Jsonl,
+    Markdown,
+}
+
+impl ExportFormat {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "jsonl" | "json" => Some(Self::Jsonl),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("md") | Some("markdown") => Self::Markdown,
+            _ => Self::Jsonl,
+        }
+    }
+}
+
+/// Arguments shared by `/mem export|import` and `dagent mem export|import`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TransferArgs {
+    pub(crate) path: PathBuf,
+    pub(crate) format: ExportFormat,
+    pub(crate) all_sessions: bool,
+    pub(crate) session: Option<String>,
+}
+
+impl TransferArgs {
+    pub(crate) fn parse(tokens: &[&str]) -> std::result::Result<Self, String> {
+        let mut path = None;
+        let mut format = None;
+        let mut all_sessions = false;
+        let mut session = None;
+        let mut iter = tokens.iter();
+        while let Some(token) = iter.next() {
+            match *token {
+                "--format" | "-f" => {
+                    let raw = iter.next().ok_or("--format needs jsonl or md")?;
+                    format = Some(
+                        ExportFormat::parse(raw)
+                            .ok_or_else(|| format!("unknown format {raw}; use jsonl or md"))?,
+                    );
+                }
+                "--all" => all_sessions = true,
+                "--session" => {
+                    let raw = iter.next().ok_or("--session needs a name")?;
+                    session = Some(raw.to_string());
+                }
+                flag if flag.starts_with("--") => return Err(format!("unknown option {flag}")),
+                value => {
+                    if path.is_some() {
+                        return Err(format!("unexpected argument {value}"));
+                    }
+                    path = Some(expand_home(value));
+                }
+            }
+        }
+        let path = path.ok_or("missing <path>")?;
+        let format = format.unwrap_or_else(|| ExportFormat::from_path(&path));
+        Ok(Self {
+            path,
+            format,
+            all_sessions,
+            session,
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
 struct MemoryMessage {
@@ -21,58 +155,518 @@ struct MemoryMessage {
 
 pub(crate) struct MemoryStore {
     conn: Connection,
+    retrieval: RetrievalStrategy,
+    /// Age at which a retrieval hit's relevance is halved; `0` disables decay.
+    recency_half_life_secs: f64,
+}
+
+/// Filters accepted by `/mem find`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SearchFilter {
+    pub(crate) all_sessions: bool,
+    pub(crate) agent: Option<String>,
+    pub(crate) role: Option<String>,
+    pub(crate) since_secs: Option<i64>,
+    /// Search recorded agent commands and tool calls instead of messages.
+    pub(crate) events: bool,
+}
+
+impl SearchFilter {
+    /// Split `/mem find` arguments into filters and the remaining query text.
+    pub(crate) fn parse(tokens: &[&str]) -> std::result::Result<(Self, String), String> {
+        let mut filter = Self::default();
+        let mut query = Vec::new();
+        let mut iter = tokens.iter();
+        while let Some(token) = iter.next() {
+            match *token {
+                "--all" => filter.all_sessions = true,
+                "--events" => filter.events = true,
+                "--agent" => {
+                    let agent = iter.next().map(|v| v.to_lowercase()).unwrap_or_default();
+                    if agent != "claude" && agent != "codex" {
+                        return Err("--agent expects claude or codex".to_string());
+                    }
+                    filter.agent = Some(agent);
+                }
+                "--role" => {
+                    let role = iter.next().map(|v| v.to_lowercase()).unwrap_or_default();
+                    if role != "user" && role != "assistant" {
+                        return Err("--role expects user or assistant".to_string());
+                    }
+                    filter.role = Some(role);
+                }
+                "--since" => {
+                    let raw = iter.next().copied().unwrap_or_default();
+                    filter.since_secs = Some(parse_duration_secs(raw).ok_or_else(|| {
+                        format!("invalid --since duration: {raw} (use e.g. 30m, 12h, 7d, 2w)")
+                    })?);
+                }
+                flag if flag.starts_with("--") => return Err(format!("unknown flag {flag}")),
+                word => query.push(word),
+            }
+        }
+        if filter.events && filter.role.is_some() {
+            return Err("--role does not apply to --events".to_string());
+        }
+        Ok((filter, query.join(" ")))
+    }
+
+    /// True when the filter alone is selective enough to list hits without a query.
+    fn is_narrowing(&self) -> bool {
+        self.events || self.agent.is_some() || self.role.is_some() || self.since_secs.is_some()
+    }
+}
+
+/// Where a line of injected memory came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContextSource {
+    Summary,
+    Recent,
+    Search,
+}
+
+impl ContextSource {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Summary => "summary",
+            Self::Recent => "recent",
+            Self::Search => "search",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ContextItem {
+    pub(crate) source: ContextSource,
+    /// Message id, or summary id for `ContextSource::Summary`.
+    pub(crate) id: i64,
+    pub(crate) text: String,
+    pub(crate) tokens: usize,
+    /// Retrieval score for search hits.
+    pub(crate) score: Option<f32>,
+}
+
+/// Everything `build_context` decided to send, before rendering.
+#[derive(Debug, Clone)]
+pub(crate) struct ContextPlan {
+    pub(crate) pins: Vec<Pin>,
+    pub(crate) items: Vec<ContextItem>,
+    pub(crate) prompt: String,
+}
+
+impl ContextPlan {
+    pub(crate) fn render(&self) -> String {
+        let body = if self.items.is_empty() {
+            self.prompt.clone()
+        } else {
+            format!(
+                "Shared session memory:\n{}\n\nCurrent user request:\n{}",
+                self.items
+                    .iter()
+                    .map(|item| item.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                self.prompt
+            )
+        };
+        with_pins(&self.pins, body)
+    }
+
+    /// Item count and estimated tokens contributed by `source`.
+    pub(crate) fn usage(&self, source: ContextSource) -> (usize, usize) {
+        self.items
+            .iter()
+            .filter(|item| item.source == source)
+            .fold((0, 0), |(count, tokens), item| {
+                (count + 1, tokens + item.tokens)
+            })
+    }
+
+    pub(crate) fn pin_tokens(&self) -> usize {
+        self.pins
+            .iter()
+            .map(|pin| estimate_tokens(&pin.content))
+            .sum()
+    }
+}
+
+/// Durable fact injected ahead of every prompt for its session.
+#[derive(Debug, Clone)]
+pub(crate) struct Pin {
+    pub(crate) id: i64,
+    pub(crate) content: String,
+}
+
+/// Which agents see a session's memory in their context.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Visibility {
+    /// Every agent sees every turn.
+    #[default]
+    Shared,
+    /// Agents see only their own replies and the prompts sent to them.
+    Private,
+    /// Agents see all user turns but only their own replies.
+    Split,
+}
+
+impl Visibility {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "shared" => Some(Self::Shared),
+            "private" => Some(Self::Private),
+            "split" => Some(Self::Split),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Shared => "shared",
+            Self::Private => "private",
+            Self::Split => "split",
+        }
+    }
+
+    /// Row-level visibility for a new message in a session using this mode.
+    fn row_visibility(self, role: &str, agent: Option<&str>) -> &'static str {
+        let private = match self {
+            Self::Shared => false,
+            Self::Private => role != "user" || agent.is_some(),
+            Self::Split => role != "user",
+        };
+        if private {
+            "private"
+        } else {
+            "shared"
+        }
+    }
+}
+
+/// Rows removed by one `apply_retention` pass, per rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RetentionReport {
+    pub(crate) expired: usize,
+    pub(crate) trimmed: usize,
+    pub(crate) over_size: usize,
+    /// The database is over `max_db_mb` even without any deletable record;
+    /// nothing was removed for the size limit.
+    pub(crate) size_unreachable: bool,
+}
+
+impl RetentionReport {
+    pub(crate) fn total(&self) -> usize {
+        self.expired + self.trimmed + self.over_size
+    }
+}
+
+/// Row counts and file size for `/mem stats`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MemoryStats {
+    pub(crate) sessions: usize,
+    pub(crate) messages: usize,
+    pub(crate) archived: usize,
+    pub(crate) summaries: usize,
+    pub(crate) pins: usize,
+    pub(crate) run_events: usize,
+    pub(crate) db_bytes: u64,
+    pub(crate) free_bytes: u64,
+    pub(crate) oldest: Option<i64>,
+}
+
+/// Contiguous run of older messages selected for `/mem compact`.
+#[derive(Debug, Clone)]
+pub(crate) struct CompactionBatch {
+    pub(crate) session_id: String,
+    /// Agent the digest belongs to; `None` for a digest every agent sees.
+    pub(crate) agent: Option<String>,
+    pub(crate) first_id: i64,
+    pub(crate) last_id: i64,
+    pub(crate) count: usize,
+    transcript: String,
+    /// Message too long for one prompt: `last_id` and the content a later batch summarizes.
+    split: Option<(i64, String)>,
+}
+
+impl CompactionBatch {
+    /// Instruction sent to the summarizing agent.
+    pub(crate) fn prompt(&self) -> String {
+        format!(
+            "Summarize this earlier part of a conversation between a user and coding agents \
+             into a compact digest that will replace it in future context. Keep decisions, \
+             file paths, commands, conclusions and open questions; drop chatter. Reply with \
+             the digest only, as short bullet points.\n\n{}",
+            self.transcript
+        )
+    }
+}
+
+/// Retrieval candidate with its per-signal scores in `0.0..=1.0`.
+struct Scored {
+    message: MemoryMessage,
+    created_at: i64,
+    keyword: f32,
+    similarity: f32,
 }
 
 impl MemoryStore {
     pub(crate) fn open_default() -> Result<Self> {
-        let path = memory_file_path();
+        Self::open_at(&memory_file_path())
+    }
+
+    fn open_at(path: &Path) -> Result<Self> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("create memory dir {}", parent.display()))?;
         }
 
-        let conn = Connection::open(&path)
-            .with_context(|| format!("open memory db {}", path.display()))?;
+        let conn =
+            Connection::open(path).with_context(|| format!("open memory db {}", path.display()))?;
         conn.pragma_update(None, "journal_mode", "WAL").ok();
         conn.pragma_update(None, "synchronous", "NORMAL").ok();
+        Self::from_connection(conn, Some(path))
+    }
 
-        conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS messages (
-              id INTEGER PRIMARY KEY AUTOINCREMENT,
-              session_id TEXT NOT NULL,
-              role TEXT NOT NULL,
-              agent TEXT,
-              content TEXT NOT NULL,
-              created_at INTEGER NOT NULL DEFAULT (unixepoch())
-            );
-            CREATE INDEX IF NOT EXISTS idx_messages_session_id_id
-              ON messages(session_id, id);
-            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts
-              USING fts5(content, tokenize='unicode61');
-            ",
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> Result<Self> {
+        Self::from_connection(
+            Connection::open_in_memory().context("open in-memory db")?,
+            None,
         )
-        .context("init memory schema")?;
+    }
+
+    fn from_connection(mut conn: Connection, db_path: Option<&Path>) -> Result<Self> {
+        migrations::migrate(&mut conn, db_path).context("migrate memory schema")?;
+
+        let store = Self {
+            conn,
+            retrieval: RetrievalStrategy::default(),
+            recency_half_life_secs: DEFAULT_HALF_LIFE_DAYS * 86_400.0,
+        };
+        store.index_missing_vectors()?;
+        Ok(store)
+    }
+
+    pub(crate) fn with_retrieval(mut self, retrieval: RetrievalStrategy) -> Self {
+        self.retrieval = retrieval;
+        self
+    }
 
-        Ok(Self { conn })
+    pub(crate) fn with_recency_half_life(mut self, days: f64) -> Self {
+        self.recency_half_life_secs = days.max(0.0) * 86_400.0;
+        self
     }
 
+    /// Embed messages written before vectors existed (or by an import).
+    fn index_missing_vectors(&self) -> Result<usize> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT m.id, m.content
+                 FROM messages m
+                 LEFT JOIN message_vectors v ON v.message_id = m.id
+                 WHERE v.message_id IS NULL",
+            )
+            .context("prepare missing vectors")?;
+        let missing = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .context("query missing vectors")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("scan missing vectors")?;
+        if missing.is_empty() {
+            return Ok(0);
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin vector index tx")?;
+        for (id, content) in &missing {
+            tx.execute(
+                "INSERT OR REPLACE INTO message_vectors(message_id, vector) VALUES (?1, ?2)",
+                params![id, embedding::embed(content).to_blob()],
+            )
+            .context("insert message vector")?;
+        }
+        tx.commit().context("commit vector index tx")?;
+        Ok(missing.len())
+    }
+
+    pub(crate) fn ensure_session(&self, session_id: &str) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO sessions(id, title) VALUES (?1, ?1)",
+                params![session_id],
+            )
+            .context("ensure session")?;
+        Ok(())
+    }
+
+    /// Create a named session; returns false when the id is already taken.
+    pub(crate) fn create_session(&self, session_id: &str, title: &str) -> Result<bool> {
+        let inserted = self
+            .conn
+            .execute(
+                "INSERT OR IGNORE INTO sessions(id, title) VALUES (?1, ?2)",
+                params![session_id, title],
+            )
+            .context("create session")?;
+        Ok(inserted > 0)
+    }
+
+    pub(crate) fn rename_session(&self, session_id: &str, title: &str) -> Result<bool> {
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE sessions SET title = ?2, updated_at = unixepoch() WHERE id = ?1",
+                params![session_id, title],
+            )
+            .context("rename session")?;
+        Ok(updated > 0)
+    }
+
+    pub(crate) fn delete_session(&self, session_id: &str) -> Result<usize> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin delete session tx")?;
+        tx.execute(
+            "DELETE FROM messages_fts
+             WHERE rowid IN (SELECT id FROM messages WHERE session_id = ?1)",
+            params![session_id],
+        )
+        .context("delete session fts rows")?;
+        let removed = tx
+            .execute(
+                "DELETE FROM messages WHERE session_id = ?1",
+                params![session_id],
+            )
+            .context("delete session message rows")?;
+        tx.execute(DELETE_ORPHAN_VECTORS, [])
+            .context("delete session vectors")?;
+        delete_session_summaries(&tx, session_id)?;
+        delete_session_events(&tx, session_id)?;
+        tx.execute(
+            "DELETE FROM pins WHERE session_id = ?1",
+            params![session_id],
+        )
+        .context("delete session pins")?;
+        tx.execute("DELETE FROM sessions WHERE id = ?1", params![session_id])
+            .context("delete session row")?;
+        tx.commit().context("commit delete session tx")?;
+        Ok(removed)
+    }
+
+    pub(crate) fn session_visibility(&self, session_id: &str) -> Result<Visibility> {
+        let raw = self
+            .conn
+            .query_row(
+                "SELECT visibility FROM sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("read session visibility")?;
+        Ok(raw
+            .as_deref()
+            .and_then(Visibility::parse)
+            .unwrap_or_default())
+    }
+
+    /// Applies to turns recorded from now on; existing rows keep theirs.
+    pub(crate) fn set_session_visibility(
+        &self,
+        session_id: &str,
+        visibility: Visibility,
+    ) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO sessions(id, title, visibility) VALUES (?1, ?1, ?2)
+                 ON CONFLICT(id) DO UPDATE SET visibility = excluded.visibility",
+                params![session_id, visibility.as_str()],
+            )
+            .context("update session visibility")?;
+        Ok(())
+    }
+
+    /// Map a user-supplied session name to an existing id, or to its slug.
+    pub(crate) fn resolve_session_id(&self, name: &str) -> Result<Option<String>> {
+        if let Some(found) = self.find_session(name)? {
+            return Ok(Some(found.id));
+        }
+        Ok(crate::config::slugify(name))
+    }
+
+    pub(crate) fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT s.id, s.title, s.created_at, s.updated_at,
+                        (SELECT COUNT(*) FROM messages m WHERE m.session_id = s.id)
+                 FROM sessions s
+                 ORDER BY s.updated_at DESC, s.id",
+            )
+            .context("prepare list sessions")?;
+
+        let mut rows = stmt.query([]).context("query sessions")?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next().context("scan session row")? {
+            out.push(SessionInfo {
+                id: row.get(0).context("session.id")?,
+                title: row.get(1).context("session.title")?,
+                created_at: row.get(2).context("session.created_at")?,
+                updated_at: row.get(3).context("session.updated_at")?,
+                message_count: row.get::<_, i64>(4).context("session.count")?.max(0) as usize,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Resolve a session by id first, then by title (case-insensitive).
+    pub(crate) fn find_session(&self, name: &str) -> Result<Option<SessionInfo>> {
+        let sessions = self.list_sessions()?;
+        if let Some(found) = sessions.iter().find(|s| s.id == name) {
+            return Ok(Some(found.clone()));
+        }
+        let lowered = name.to_lowercase();
+        Ok(sessions
+            .into_iter()
+            .find(|s| s.title.to_lowercase() == lowered))
+    }
+
+    /// Store one turn and return its row id, or `None` when there was nothing to store.
+    /// For user turns `agent` is the single agent the prompt was sent to, if any;
+    /// it is only kept when the session is `private`.
     pub(crate) fn append_message(
         &self,
         session_id: &str,
         role: &str,
         agent: Option<&str>,
         content: &str,
-    ) -> Result<()> {
+    ) -> Result<Option<i64>> {
         let trimmed = content.trim();
         if trimmed.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
 
+        let mode = self.session_visibility(session_id)?;
+        let agent = if role == "user" && mode != Visibility::Private {
+            None
+        } else {
+            agent
+        };
         self.conn
             .execute(
-                "INSERT INTO messages(session_id, role, agent, content) VALUES (?1, ?2, ?3, ?4)",
-                params![session_id, role, agent, trimmed],
+                "INSERT INTO messages(session_id, role, agent, content, visibility)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    session_id,
+                    role,
+                    agent,
+                    trimmed,
+                    mode.row_visibility(role, agent)
+                ],
             )
             .context("insert message")?;
 
@@ -80,10 +674,23 @@ impl MemoryStore {
         self.conn
             .execute(
                 "INSERT INTO messages_fts(rowid, content) VALUES (?1, ?2)",
-                params![msg_id, trimmed],
+                params![msg_id, fts_text(trimmed)],
             )
             .context("insert fts row")?;
-        Ok(())
+        self.conn
+            .execute(
+                "INSERT INTO message_vectors(message_id, vector) VALUES (?1, ?2)",
+                params![msg_id, embedding::embed(trimmed).to_blob()],
+            )
+            .context("insert message vector")?;
+        self.conn
+            .execute(
+                "INSERT INTO sessions(id, title) VALUES (?1, ?1)
+                 ON CONFLICT(id) DO UPDATE SET updated_at = unixepoch()",
+                params![session_id],
+            )
+            .context("touch session")?;
+        Ok(Some(msg_id))
     }
 
     pub(crate) fn clear_session(&self, session_id: &str) -> Result<()> {
@@ -102,6 +709,10 @@ impl MemoryStore {
             params![session_id],
         )
         .context("clear message rows")?;
+        tx.execute(DELETE_ORPHAN_VECTORS, [])
+            .context("clear vectors")?;
+        delete_session_summaries(&tx, session_id)?;
+        delete_session_events(&tx, session_id)?;
         tx.commit().context("commit clear tx")?;
         Ok(())
     }
@@ -120,7 +731,7 @@ impl MemoryStore {
 
     pub(crate) fn list_session_lines(&self, session_id: &str, limit: usize) -> Result<Vec<String>> {
         let mut out = Vec::new();
-        for item in self.recent_messages(session_id, limit.max(1))? {
+        for item in self.recent_messages(session_id, None, None, limit.max(1))? {
             if let Some(line) = format_preview_line(&item) {
                 out.push(format!("#{} {}", item.id, line));
             }
@@ -128,28 +739,247 @@ impl MemoryStore {
         Ok(out)
     }
 
+    /// FTS search with optional filters; each hit is shown with its time and session.
     pub(crate) fn search_session_lines(
         &self,
         session_id: &str,
         query: &str,
+        filter: &SearchFilter,
         limit: usize,
     ) -> Result<Vec<String>> {
-        let Some(normalized) = normalize_query(query) else {
+        let normalized = normalize_query(query);
+        if normalized.is_none() && !filter.is_narrowing() {
             return Ok(Vec::new());
-        };
+        }
 
-        let mut items = self.search_messages(session_id, &normalized, limit.max(1))?;
-        items.sort_by_key(|m| m.id);
+        let mut sql = String::from(
+            "SELECT m.id, m.role, m.agent, m.content, COALESCE(s.title, m.session_id), m.created_at",
+        );
+        let mut args: Vec<Value> = Vec::new();
+        let ranked = normalized.is_some();
+        if let Some(normalized) = normalized {
+            sql.push_str(
+                " FROM messages_fts f
+                  JOIN messages m ON m.id = f.rowid
+                  LEFT JOIN sessions s ON s.id = m.session_id
+                  WHERE f.content MATCH ?",
+            );
+            args.push(Value::Text(normalized));
+        } else {
+            sql.push_str(
+                " FROM messages m
+                  LEFT JOIN sessions s ON s.id = m.session_id
+                  WHERE 1 = 1",
+            );
+        }
+        if !filter.all_sessions {
+            sql.push_str(" AND m.session_id = ?");
+            args.push(Value::Text(session_id.to_string()));
+        }
+        if let Some(agent) = &filter.agent {
+            sql.push_str(" AND m.agent = ?");
+            args.push(Value::Text(agent.clone()));
+        }
+        if let Some(role) = &filter.role {
+            sql.push_str(" AND m.role = ?");
+            args.push(Value::Text(role.clone()));
+        }
+        if let Some(since) = filter.since_secs {
+            sql.push_str(" AND m.created_at >= unixepoch() - ?");
+            args.push(Value::Integer(since));
+        }
+        if ranked {
+            sql.push_str(" ORDER BY bm25(messages_fts), m.id DESC LIMIT ?");
+        } else {
+            sql.push_str(" ORDER BY m.id DESC LIMIT ?");
+        }
+        args.push(Value::Integer(limit.max(1) as i64));
+
+        let mut stmt = self.conn.prepare(&sql).context("prepare filtered search")?;
+        let mut rows = stmt
+            .query(params_from_iter(args))
+            .context("query filtered search")?;
+        let mut hits = Vec::new();
+        while let Some(row) = rows.next().context("scan filtered row")? {
+            let item = MemoryMessage {
+                id: row.get(0).context("find.id")?,
+                role: row.get(1).context("find.role")?,
+                agent: row.get(2).context("find.agent")?,
+                content: row.get(3).context("find.content")?,
+            };
+            let title: String = row.get(4).context("find.session")?;
+            let created_at: i64 = row.get(5).context("find.created_at")?;
+            hits.push((item, title, created_at));
+        }
+        hits.sort_by_key(|(item, _, _)| item.id);
 
         let mut out = Vec::new();
-        for item in items {
+        for (item, title, created_at) in hits {
             if let Some(line) = format_preview_line(&item) {
-                out.push(format!("#{} {}", item.id, line));
+                out.push(format!(
+                    "#{} {} · {} · {}",
+                    item.id,
+                    format_timestamp(created_at),
+                    title,
+                    line
+                ));
             }
         }
         Ok(out)
     }
 
+    /// Record one command or tool call; output is clipped to a short excerpt.
+    pub(crate) fn append_run_event(
+        &self,
+        session_id: &str,
+        run_id: &str,
+        provider: &str,
+        event: &RunEvent,
+    ) -> Result<()> {
+        let command = clip_chars(event.command.trim().to_string(), MAX_EVENT_COMMAND_CHARS);
+        if command.is_empty() {
+            return Ok(());
+        }
+        let output = clip_chars(event.output.trim().to_string(), MAX_EVENT_OUTPUT_CHARS);
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin run event tx")?;
+        tx.execute(
+            "INSERT INTO run_events(session_id, run_id, provider, kind, command, exit_code, output)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                session_id,
+                run_id,
+                provider,
+                event.kind.as_str(),
+                command,
+                event.exit_code,
+                output
+            ],
+        )
+        .context("insert run event")?;
+        let event_id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO run_events_fts(rowid, command, output) VALUES (?1, ?2, ?3)",
+            params![event_id, fts_text(&command), fts_text(&output)],
+        )
+        .context("insert run event fts row")?;
+        tx.commit().context("commit run event tx")?;
+        Ok(())
+    }
+
+    /// `/mem find --events`: matching agent commands, oldest first.
+    pub(crate) fn search_run_event_lines(
+        &self,
+        session_id: &str,
+        query: &str,
+        filter: &SearchFilter,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let normalized = normalize_query(query);
+        let mut sql = String::from(
+            "SELECT e.id, e.run_id, e.provider, e.kind, e.command, e.exit_code, e.output,
+                    e.created_at, COALESCE(s.title, e.session_id)",
+        );
+        let mut args: Vec<Value> = Vec::new();
+        let ranked = normalized.is_some();
+        if let Some(normalized) = normalized {
+            sql.push_str(
+                " FROM run_events_fts f
+                  JOIN run_events e ON e.id = f.rowid
+                  LEFT JOIN sessions s ON s.id = e.session_id
+                  WHERE run_events_fts MATCH ?",
+            );
+            args.push(Value::Text(normalized));
+        } else {
+            sql.push_str(
+                " FROM run_events e
+                  LEFT JOIN sessions s ON s.id = e.session_id
+                  WHERE 1 = 1",
+            );
+        }
+        if !filter.all_sessions {
+            sql.push_str(" AND e.session_id = ?");
+            args.push(Value::Text(session_id.to_string()));
+        }
+        if let Some(agent) = &filter.agent {
+            sql.push_str(" AND e.provider = ?");
+            args.push(Value::Text(agent.clone()));
+        }
+        if let Some(since) = filter.since_secs {
+            sql.push_str(" AND e.created_at >= unixepoch() - ?");
+            args.push(Value::Integer(since));
+        }
+        if ranked {
+            sql.push_str(" ORDER BY bm25(run_events_fts), e.id DESC LIMIT ?");
+        } else {
+            sql.push_str(" ORDER BY e.id DESC LIMIT ?");
+        }
+        args.push(Value::Integer(limit.max(1) as i64));
+
+        let mut stmt = self.conn.prepare(&sql).context("prepare event search")?;
+        let mut rows = stmt
+            .query(params_from_iter(args))
+            .context("query event search")?;
+        let mut hits = Vec::new();
+        while let Some(row) = rows.next().context("scan event row")? {
+            let id: i64 = row.get(0).context("event.id")?;
+            let run_id: String = row.get(1).context("event.run_id")?;
+            let provider: String = row.get(2).context("event.provider")?;
+            let kind: String = row.get(3).context("event.kind")?;
+            let command: String = row.get(4).context("event.command")?;
+            let exit_code: Option<i64> = row.get(5).context("event.exit_code")?;
+            let output: String = row.get(6).context("event.output")?;
+            let created_at: i64 = row.get(7).context("event.created_at")?;
+            let title: String = row.get(8).context("event.session")?;
+
+            let mut line = format!(
+                "#{id} {} · {title} · {run_id} · {provider} {kind}: {}",
+                format_timestamp(created_at),
+                clip_chars(squash_whitespace(&command), MAX_PREVIEW_CHARS)
+            );
+            if let Some(code) = exit_code {
+                line.push_str(&format!(" (exit {code})"));
+            }
+            let output = squash_whitespace(&output);
+            if !output.is_empty() {
+                line.push_str(&format!(" → {}", clip_chars(output, MAX_PREVIEW_CHARS)));
+            }
+            hits.push((id, line));
+        }
+        hits.sort_by_key(|(id, _)| *id);
+        Ok(hits.into_iter().map(|(_, line)| line).collect())
+    }
+
+    /// Full text of one message, for pulling a search hit into the prompt.
+    pub(crate) fn message_by_id(&self, id: i64) -> Result<Option<(String, String)>> {
+        self.conn
+            .query_row(
+                "SELECT role, agent, content FROM messages WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(MemoryMessage {
+                        id,
+                        role: row.get(0)?,
+                        agent: row.get(1)?,
+                        content: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .context("read message")
+            .map(|found| {
+                found.map(|item| {
+                    let label = message_prefix(&item)
+                        .trim_end_matches([' ', ':'])
+                        .to_string();
+                    (label, item.content)
+                })
+            })
+    }
+
     pub(crate) fn prune_session_keep_recent(&self, session_id: &str, keep: usize) -> Result<usize> {
         let tx = self
             .conn
@@ -204,89 +1034,748 @@ impl MemoryStore {
             .context("prune message rows")?
         };
 
+        tx.execute(DELETE_ORPHAN_VECTORS, [])
+            .context("prune vectors")?;
         tx.commit().context("commit prune tx")?;
         Ok(deleted_rows)
     }
 
-    pub(crate) fn build_context(&self, session_id: &str, prompt: &str) -> Result<String> {
-        let mut items = self.recent_messages(session_id, RECENT_LIMIT)?;
-        let mut seen = items.iter().map(|m| m.id).collect::<HashSet<_>>();
-
-        if let Some(query) = normalize_query(prompt) {
-            for hit in self.search_messages(session_id, &query, SEARCH_LIMIT)? {
-                if seen.insert(hit.id) {
-                    items.push(hit);
+    /// Enforce `policy` across all sessions: age first, then per-session rows,
+    /// then total size, oldest records first. Pins are never removed. The
+    /// size limit counts live pages, so the file itself only shrinks after
+    /// `/mem vacuum`.
+    pub(crate) fn apply_retention(&self, policy: &RetentionPolicy) -> Result<RetentionReport> {
+        let mut report = RetentionReport::default();
+        if let Some(days) = policy.max_age_days() {
+            let cutoff = unix_now().saturating_sub(days.saturating_mul(86_400) as i64);
+            report.expired = self.delete_records_before(cutoff)?;
+        }
+        if let Some(keep) = policy.max_rows_per_session() {
+            for session in self.list_sessions()? {
+                if session.message_count > keep {
+                    report.trimmed += self.prune_session_keep_recent(&session.id, keep)?;
                 }
             }
         }
-
-        if items.is_empty() {
-            return Ok(prompt.to_string());
+        if let Some(limit) = policy.max_db_bytes() {
+            let excess = self.live_bytes()?.saturating_sub(limit);
+            if excess > 0 {
+                match self.oldest_records_covering(excess)? {
+                    Some(records) => report.over_size = self.delete_records(&records)?,
+                    None => report.size_unreachable = true,
+                }
+            }
         }
+        Ok(report)
+    }
 
-        items.sort_by_key(|m| m.id);
+    /// Messages, summaries, archives and run events created before `cutoff`.
+    fn delete_records_before(&self, cutoff: i64) -> Result<usize> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin expire tx")?;
+        tx.execute(
+            "DELETE FROM messages_fts
+             WHERE rowid IN (SELECT id FROM messages WHERE created_at < ?1)",
+            params![cutoff],
+        )
+        .context("expire fts rows")?;
+        let removed = tx
+            .execute(
+                "DELETE FROM messages WHERE created_at < ?1",
+                params![cutoff],
+            )
+            .context("expire message rows")?;
+        tx.execute(DELETE_ORPHAN_VECTORS, [])
+            .context("expire vectors")?;
+        tx.execute(
+            "DELETE FROM messages_archive WHERE created_at < ?1",
+            params![cutoff],
+        )
+        .context("expire archived messages")?;
+        tx.execute(
+            "DELETE FROM summaries WHERE created_at < ?1",
+            params![cutoff],
+        )
+        .context("expire summaries")?;
+        tx.execute(
+            "DELETE FROM run_events_fts
+             WHERE rowid IN (SELECT id FROM run_events WHERE created_at < ?1)",
+            params![cutoff],
+        )
+        .context("expire run event fts rows")?;
+        tx.execute(
+            "DELETE FROM run_events WHERE created_at < ?1",
+            params![cutoff],
+        )
+        .context("expire run events")?;
+        tx.commit().context("commit expire tx")?;
+        Ok(removed)
+    }
 
-        let prompt_norm = squash_whitespace(prompt.trim());
-        let mut filtered = Vec::new();
-        let mut skipped_current_prompt = false;
-        for item in items.into_iter().rev() {
-            let text_norm = squash_whitespace(item.content.trim());
-            if !skipped_current_prompt
-                && item.role == "user"
-                && !prompt_norm.is_empty()
-                && text_norm == prompt_norm
-            {
-                skipped_current_prompt = true;
-                continue;
+    /// Oldest messages, archived messages, summaries and run events whose
+    /// estimated size adds up to `bytes`, as `(table, id)`. `None` when
+    /// deleting every one of them would still not free that much.
+    fn oldest_records_covering(&self, bytes: u64) -> Result<Option<Vec<(String, i64)>>> {
+        // Indexed text is stored twice: in the table and in its FTS index.
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT tbl, id, size FROM (
+                   SELECT 'messages' AS tbl, m.id AS id, m.created_at AS created_at,
+                          2 * length(CAST(m.content AS BLOB))
+                            + COALESCE(length(v.vector), 0) AS size
+                   FROM messages m LEFT JOIN message_vectors v ON v.message_id = m.id
+                   UNION ALL
+                   SELECT 'messages_archive', id, created_at, length(CAST(content AS BLOB))
+                   FROM messages_archive
+                   UNION ALL
+                   SELECT 'summaries', id, created_at, length(CAST(content AS BLOB))
+                   FROM summaries
+                   UNION ALL
+                   SELECT 'run_events', id, created_at,
+                          2 * (length(CAST(command AS BLOB)) + length(CAST(output AS BLOB)))
+                   FROM run_events
+                 )
+                 ORDER BY created_at, id",
+            )
+            .context("prepare shrink query")?;
+        let mut rows = stmt.query([]).context("query shrink candidates")?;
+        let mut records = Vec::new();
+        let mut covered = 0u64;
+        while let Some(row) = rows.next().context("read shrink candidate")? {
+            let size = row.get::<_, i64>(2)?.max(0) as u64;
+            records.push((row.get::<_, String>(0)?, row.get::<_, i64>(1)?));
+            covered += size + RETENTION_ROW_OVERHEAD;
+            if covered >= bytes {
+                return Ok(Some(records));
             }
-            filtered.push(item);
         }
-        filtered.reverse();
+        Ok(None)
+    }
 
-        let mut lines = Vec::new();
-        for item in filtered {
-            if let Some(line) = format_line(&item) {
-                lines.push(line);
+    /// Delete `(table, id)` rows picked by `oldest_records_covering`, with
+    /// their FTS rows and vectors, then merge the FTS indexes once so the
+    /// freed pages are released.
+    fn delete_records(&self, records: &[(String, i64)]) -> Result<usize> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin shrink tx")?;
+        let mut removed = 0;
+        for (table, id) in records {
+            let fts = match table.as_str() {
+                "messages" => Some("messages_fts"),
+                "run_events" => Some("run_events_fts"),
+                "messages_archive" | "summaries" => None,
+                other => bail!("unexpected shrink table {other}"),
+            };
+            if let Some(fts) = fts {
+                tx.execute(&format!("DELETE FROM {fts} WHERE rowid = ?1"), params![id])
+                    .with_context(|| format!("shrink {fts} rows"))?;
+            }
+            removed += tx
+                .execute(&format!("DELETE FROM {table} WHERE id = ?1"), params![id])
+                .with_context(|| format!("shrink {table} rows"))?;
+        }
+        tx.execute(DELETE_ORPHAN_VECTORS, [])
+            .context("shrink vectors")?;
+        for fts in ["messages_fts", "run_events_fts"] {
+            tx.execute(&format!("INSERT INTO {fts}({fts}) VALUES ('optimize')"), [])
+                .with_context(|| format!("optimize {fts}"))?;
+        }
+        tx.commit().context("commit shrink tx")?;
+        Ok(removed)
+    }
+
+    /// Bytes held by live pages, i.e. the file size after a `VACUUM`.
+    fn live_bytes(&self) -> Result<u64> {
+        let (pages, free, page_size) = self.page_counts()?;
+        Ok(pages.saturating_sub(free) * page_size)
+    }
+
+    fn page_counts(&self) -> Result<(u64, u64, u64)> {
+        let read = |pragma: &str| {
+            self.conn
+                .pragma_query_value(None, pragma, |row| row.get::<_, i64>(0))
+                .with_context(|| format!("read {pragma}"))
+                .map(|v| v.max(0) as u64)
+        };
+        Ok((
+            read("page_count")?,
+            read("freelist_count")?,
+            read("page_size")?,
+        ))
+    }
+
+    pub(crate) fn stats(&self) -> Result<MemoryStats> {
+        let count = |table: &str| -> Result<usize> {
+            self.conn
+                .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .with_context(|| format!("count {table}"))
+                .map(|n| n.max(0) as usize)
+        };
+        let (pages, free, page_size) = self.page_counts()?;
+        let oldest = self
+            .conn
+            .query_row(
+                "SELECT MIN(ts) FROM (
+                   SELECT MIN(created_at) AS ts FROM messages
+                   UNION ALL SELECT MIN(created_at) FROM messages_archive
+                   UNION ALL SELECT MIN(created_at) FROM run_events
+                 )",
+                [],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .context("query oldest record")?;
+        Ok(MemoryStats {
+            sessions: count("sessions")?,
+            messages: count("messages")?,
+            archived: count("messages_archive")?,
+            summaries: count("summaries")?,
+            pins: count("pins")?,
+            run_events: count("run_events")?,
+            db_bytes: pages * page_size,
+            free_bytes: free * page_size,
+            oldest,
+        })
+    }
+
+    /// Rebuild the database file; returns its size before and after.
+    pub(crate) fn vacuum(&self) -> Result<(u64, u64)> {
+        let before = self.stats()?.db_bytes;
+        self.conn
+            .execute_batch("VACUUM")
+            .context("vacuum memory db")?;
+        let after = self.stats()?.db_bytes;
+        Ok((before, after))
+    }
+
+    pub(crate) fn add_pin(&self, session_id: &str, text: &str) -> Result<i64> {
+        let text = squash_whitespace(text.trim());
+        if text.is_empty() {
+            bail!("pin text is empty");
+        }
+        if text.chars().count() > MAX_PIN_CHARS {
+            bail!("pin text is longer than {MAX_PIN_CHARS} chars");
+        }
+        self.conn
+            .execute(
+                "INSERT INTO pins(session_id, content) VALUES (?1, ?2)",
+                params![session_id, text],
+            )
+            .context("insert pin")?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub(crate) fn remove_pin(&self, session_id: &str, id: i64) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM pins WHERE session_id = ?1 AND id = ?2",
+                params![session_id, id],
+            )
+            .context("delete pin")?;
+        Ok(removed > 0)
+    }
+
+    pub(crate) fn list_pins(&self, session_id: &str) -> Result<Vec<Pin>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, content FROM pins WHERE session_id = ?1 ORDER BY id")
+            .context("prepare pins")?;
+        let pins = stmt
+            .query_map(params![session_id], |row| {
+                Ok(Pin {
+                    id: row.get(0)?,
+                    content: row.get(1)?,
+                })
+            })
+            .context("query pins")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("scan pins")?;
+        Ok(pins)
+    }
+
+    pub(crate) fn build_context(
+        &self,
+        session_id: &str,
+        prompt: &str,
+        agent: Option<&str>,
+        current: Option<i64>,
+        budget: &ContextBudget,
+    ) -> Result<String> {
+        Ok(self
+            .plan_context(session_id, prompt, agent, current, budget)?
+            .render())
+    }
+
+    /// Select pins, summaries, recent messages and retrieval hits for `prompt` within `budget`.
+    /// `agent` is the provider the prompt is dispatched to; its own replies rank higher.
+    /// `current` is the stored row of `prompt` itself, if it was already appended; it is
+    /// left out so the plan matches the one built before the prompt was stored.
+    pub(crate) fn plan_context(
+        &self,
+        session_id: &str,
+        prompt: &str,
+        agent: Option<&str>,
+        current: Option<i64>,
+        budget: &ContextBudget,
+    ) -> Result<ContextPlan> {
+        let summaries = self.summary_items(session_id, agent, budget.max_tokens * 2 / 5)?;
+        let summary_tokens = summaries.iter().map(|item| item.tokens).sum::<usize>();
+
+        let mut candidates = self
+            .recent_messages(session_id, agent, current, budget.recent)?
+            .into_iter()
+            .map(|m| (m, ContextSource::Recent, None))
+            .collect::<Vec<_>>();
+        let mut seen = candidates
+            .iter()
+            .map(|(m, _, _)| m.id)
+            .collect::<HashSet<_>>();
+        for (hit, score) in self.retrieve(session_id, prompt, agent, current, budget.search)? {
+            if seen.insert(hit.id) {
+                candidates.push((hit, ContextSource::Search, Some(score)));
             }
         }
+        candidates.sort_by_key(|(m, _, _)| m.id);
 
-        if lines.is_empty() {
-            return Ok(prompt.to_string());
+        let mut lines = Vec::new();
+        for (item, source, score) in candidates.into_iter().rev() {
+            if let Some(text) = format_line(&item) {
+                lines.push(ContextItem {
+                    source,
+                    id: item.id,
+                    tokens: estimate_tokens(&text),
+                    text,
+                    score,
+                });
+            }
         }
 
+        // Newest first until the budget is spent, always keeping at least one line.
+        let remaining = budget.max_tokens.saturating_sub(summary_tokens);
         let mut selected = Vec::new();
         let mut used = 0usize;
-        for line in lines.into_iter().rev() {
-            let delta = line.len().saturating_add(1);
-            if used + delta > CONTEXT_CHAR_LIMIT && !selected.is_empty() {
+        for line in lines {
+            if used + line.tokens > remaining && !selected.is_empty() {
                 break;
             }
-            used += delta;
+            used += line.tokens;
             selected.push(line);
         }
         selected.reverse();
 
-        Ok(format!(
-            "Shared session memory:\n{}\n\nCurrent user request:\n{}",
-            selected.join("\n"),
-            prompt
-        ))
+        Ok(ContextPlan {
+            pins: self.list_pins(session_id)?,
+            items: summaries.into_iter().chain(selected).collect(),
+            prompt: prompt.to_string(),
+        })
     }
 
-    fn recent_messages(&self, session_id: &str, limit: usize) -> Result<Vec<MemoryMessage>> {
+    /// Oldest messages beyond the `keep` most recent, capped to what one summary prompt can hold.
+    /// With `agent` only that agent's private rows are taken, otherwise only shared rows,
+    /// so a digest never holds anything its readers could not see.
+    pub(crate) fn compaction_batch(
+        &self,
+        session_id: &str,
+        keep: usize,
+        agent: Option<&str>,
+    ) -> Result<Option<CompactionBatch>> {
         let mut stmt = self
             .conn
-            .prepare(
+            .prepare(&format!(
                 "SELECT id, role, agent, content
                  FROM messages
-                 WHERE session_id = ?1
+                 WHERE {COMPACTABLE_ROWS}
+                 ORDER BY id ASC"
+            ))
+            .context("prepare compaction batch")?;
+        let rows = stmt
+            .query_map(params![session_id, i64::MIN, i64::MAX, agent], |row| {
+                Ok(MemoryMessage {
+                    id: row.get(0)?,
+                    role: row.get(1)?,
+                    agent: row.get(2)?,
+                    content: row.get(3)?,
+                })
+            })
+            .context("query compaction batch")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("scan compaction batch")?;
+        let eligible = rows.len().saturating_sub(keep);
+
+        // Messages go in whole; the digest replaces them, so nothing is clipped.
+        let mut lines = Vec::new();
+        let mut used = 0usize;
+        let mut last_id = None;
+        let mut split = None;
+        let mut full = false;
+        for item in rows.iter().take(eligible) {
+            let content = item.content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            let prefix = message_prefix(item);
+            let line = prefix_multiline(&prefix, content);
+            if used + line.len() > COMPACT_INPUT_CHAR_LIMIT {
+                if lines.is_empty() {
+                    let (head, rest) =
+                        split_for_budget(content, prefix.len(), COMPACT_INPUT_CHAR_LIMIT);
+                    lines.push(prefix_multiline(&prefix, head));
+                    last_id = Some(item.id);
+                    split = Some((item.id, rest.to_string()));
+                }
+                full = true;
+                break;
+            }
+            used += line.len() + 1;
+            lines.push(line);
+            last_id = Some(item.id);
+        }
+
+        let (Some(first), Some(last_id)) = (rows.first(), last_id) else {
+            return Ok(None);
+        };
+        // A batch cut short by the budget goes ahead with however few messages it holds.
+        if lines.len() < COMPACT_MIN_MESSAGES && !full {
+            return Ok(None);
+        }
+        Ok(Some(CompactionBatch {
+            session_id: session_id.to_string(),
+            agent: agent.map(str::to_string),
+            first_id: first.id,
+            last_id,
+            count: rows.iter().take_while(|m| m.id <= last_id).count(),
+            transcript: lines.join("\n"),
+            split,
+        }))
+    }
+
+    /// Store the digest for `batch` and move its messages to `messages_archive`. A split
+    /// message is archived whole once and keeps only its unsummarized rest in `messages`.
+    pub(crate) fn store_summary(&self, batch: &CompactionBatch, summary: &str) -> Result<usize> {
+        let summary = summary.trim();
+        if summary.is_empty() {
+            bail!("empty summary");
+        }
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin compaction tx")?;
+        let visibility = if batch.agent.is_some() {
+            "private"
+        } else {
+            "shared"
+        };
+        tx.execute(
+            "INSERT INTO summaries(
+               session_id, agent, visibility, content, first_message_id, last_message_id,
+               message_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                batch.session_id,
+                batch.agent,
+                visibility,
+                summary,
+                batch.first_id,
+                batch.last_id,
+                batch.count as i64
+            ],
+        )
+        .context("insert summary")?;
+        let summary_id = tx.last_insert_rowid();
+        let whole_last = batch.split.as_ref().map_or(batch.last_id, |(id, _)| id - 1);
+        let rows = params![batch.session_id, batch.first_id, whole_last, batch.agent];
+        let mut archived = tx
+            .execute(
+                &format!(
+                    "INSERT OR IGNORE INTO messages_archive(
+                       id, session_id, role, agent, content, created_at, visibility, summary_id)
+                     SELECT id, session_id, role, agent, content, created_at, visibility, ?5
+                     FROM messages
+                     WHERE {COMPACTABLE_ROWS}"
+                ),
+                params![
+                    batch.session_id,
+                    batch.first_id,
+                    whole_last,
+                    batch.agent,
+                    summary_id
+                ],
+            )
+            .context("archive compacted messages")?;
+        if let Some((id, rest)) = &batch.split {
+            archived += tx
+                .execute(
+                    "INSERT OR IGNORE INTO messages_archive(
+                       id, session_id, role, agent, content, created_at, visibility, summary_id)
+                     SELECT id, session_id, role, agent, content, created_at, visibility, ?2
+                     FROM messages
+                     WHERE id = ?1",
+                    params![id, summary_id],
+                )
+                .context("archive split message")?;
+            tx.execute(
+                "UPDATE messages SET content = ?2 WHERE id = ?1",
+                params![id, rest],
+            )
+            .context("keep split message rest")?;
+            tx.execute("DELETE FROM messages_fts WHERE rowid = ?1", params![id])
+                .context("reindex split message")?;
+            tx.execute(
+                "INSERT INTO messages_fts(rowid, content) VALUES (?1, ?2)",
+                params![id, fts_text(rest)],
+            )
+            .context("reindex split message")?;
+            tx.execute(
+                "UPDATE message_vectors SET vector = ?2 WHERE message_id = ?1",
+                params![id, embedding::embed(rest).to_blob()],
+            )
+            .context("re-embed split message")?;
+        }
+        tx.execute(
+            &format!(
+                "DELETE FROM messages_fts
+                 WHERE rowid IN (SELECT id FROM messages WHERE {COMPACTABLE_ROWS})"
+            ),
+            rows,
+        )
+        .context("compact fts rows")?;
+        tx.execute(
+            &format!("DELETE FROM messages WHERE {COMPACTABLE_ROWS}"),
+            rows,
+        )
+        .context("compact message rows")?;
+        tx.execute(DELETE_ORPHAN_VECTORS, [])
+            .context("compact vectors")?;
+        tx.commit().context("commit compaction tx")?;
+        Ok(archived)
+    }
+
+    /// Summary digests of a session that `viewer` may see, oldest first, newest kept when
+    /// over `max_tokens`.
+    fn summary_items(
+        &self,
+        session_id: &str,
+        viewer: Option<&str>,
+        max_tokens: usize,
+    ) -> Result<Vec<ContextItem>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, content, message_count
+                 FROM summaries
+                 WHERE session_id = ?1 AND (?2 IS NULL OR visibility = 'shared' OR agent = ?2)
+                 ORDER BY id DESC",
+            )
+            .context("prepare summaries")?;
+        let rows = stmt
+            .query_map(params![session_id, viewer], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })
+            .context("query summaries")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("scan summaries")?;
+
+        let mut out = Vec::new();
+        let mut used = 0usize;
+        for (id, content, count) in rows {
+            let text = clip_chars(squash_block_whitespace(content.trim()), MAX_SUMMARY_CHARS);
+            let text = prefix_multiline(&format!("summary of {count} earlier messages: "), &text);
+            let tokens = estimate_tokens(&text);
+            if used + tokens > max_tokens && !out.is_empty() {
+                break;
+            }
+            used += tokens;
+            out.push(ContextItem {
+                source: ContextSource::Summary,
+                id,
+                text,
+                tokens,
+                score: None,
+            });
+        }
+        out.reverse();
+        Ok(out)
+    }
+
+    /// Messages of one session (or all sessions) in insertion order.
+    pub(crate) fn export_records(&self, session_id: Option<&str>) -> Result<Vec<ExportRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT session_id, role, agent, content, created_at, visibility
+                 FROM messages
+                 WHERE ?1 IS NULL OR session_id = ?1
+                 ORDER BY session_id, id",
+            )
+            .context("prepare export")?;
+        let mut rows = stmt.query(params![session_id]).context("query export")?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next().context("scan export row")? {
+            out.push(ExportRecord {
+                session: row.get(0).context("export.session")?,
+                role: row.get(1).context("export.role")?,
+                agent: row.get(2).context("export.agent")?,
+                content: row.get(3).context("export.content")?,
+                created_at: row.get(4).context("export.created_at")?,
+                visibility: row.get(5).context("export.visibility")?,
+            });
+        }
+        Ok(out)
+    }
+
+    pub(crate) fn export_to_file(
+        &self,
+        path: &Path,
+        format: ExportFormat,
+        session_id: Option<&str>,
+    ) -> Result<usize> {
+        let records = self.export_records(session_id)?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("create export dir {}", parent.display()))?;
+        }
+        let mut file = fs::File::create(path)
+            .with_context(|| format!("create export file {}", path.display()))?;
+        let body = match format {
+            ExportFormat::Jsonl => render_jsonl(&records)?,
+            ExportFormat::Markdown => render_markdown_export(&records),
+        };
+        file.write_all(body.as_bytes())
+            .with_context(|| format!("write export file {}", path.display()))?;
+        Ok(records.len())
+    }
+
+    /// Import a JSONL export, skipping rows that already exist, then rebuild
+    /// `messages_fts`. `into_session` remaps every row to one session.
+    pub(crate) fn import_from_file(
+        &self,
+        path: &Path,
+        into_session: Option<&str>,
+    ) -> Result<usize> {
+        if ExportFormat::from_path(path) == ExportFormat::Markdown {
+            bail!("markdown exports are read-only; import a .jsonl export");
+        }
+        let file =
+            fs::File::open(path).with_context(|| format!("open import file {}", path.display()))?;
+        let mut records = Vec::new();
+        for (idx, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.with_context(|| format!("read import line {}", idx + 1))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: ExportRecord = serde_json::from_str(&line)
+                .with_context(|| format!("parse import line {}", idx + 1))?;
+            records.push(record);
+        }
+        self.import_records(&records, into_session)
+    }
+
+    fn import_records(
+        &self,
+        records: &[ExportRecord],
+        into_session: Option<&str>,
+    ) -> Result<usize> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .context("begin import tx")?;
+        let mut imported = 0usize;
+        for record in records {
+            let content = record.content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            if !matches!(record.visibility.as_str(), "shared" | "private") {
+                bail!(
+                    "unknown visibility {:?} in import record",
+                    record.visibility
+                );
+            }
+            let session = into_session.unwrap_or(&record.session);
+            let exists = tx
+                .query_row(
+                    "SELECT 1 FROM messages
+                     WHERE session_id = ?1 AND role = ?2 AND agent IS ?3
+                       AND content = ?4 AND created_at = ?5
+                     LIMIT 1",
+                    params![
+                        session,
+                        record.role,
+                        record.agent,
+                        content,
+                        record.created_at
+                    ],
+                    |_| Ok(()),
+                )
+                .optional()
+                .context("check import duplicate")?
+                .is_some();
+            if exists {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO messages(session_id, role, agent, content, created_at, visibility)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    session,
+                    record.role,
+                    record.agent,
+                    content,
+                    record.created_at,
+                    record.visibility
+                ],
+            )
+            .context("insert imported message")?;
+            imported += 1;
+        }
+        rebuild_fts_indexes(&tx)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO sessions(id, title, created_at, updated_at)
+               SELECT session_id, session_id, MIN(created_at), MAX(created_at)
+               FROM messages
+               GROUP BY session_id",
+            [],
+        )
+        .context("backfill imported sessions")?;
+        tx.commit().context("commit import tx")?;
+        self.index_missing_vectors()?;
+        Ok(imported)
+    }
+
+    /// Latest messages, restricted to what `viewer` may see when given and
+    /// leaving out the `exclude` row.
+    fn recent_messages(
+        &self,
+        session_id: &str,
+        viewer: Option<&str>,
+        exclude: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<MemoryMessage>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, role, agent, content
+                 FROM messages m
+                 WHERE session_id = ?1 AND (?3 IS NULL OR m.visibility = 'shared' OR m.agent = ?3)
+                   AND m.id IS NOT ?4
                  ORDER BY id DESC
                  LIMIT ?2",
             )
             .context("prepare recent messages")?;
 
         let mut rows = stmt
-            .query(params![session_id, limit as i64])
+            .query(params![session_id, limit as i64, viewer, exclude])
             .context("query recent messages")?;
 
         let mut out = Vec::new();
@@ -302,41 +1791,249 @@ impl MemoryStore {
         Ok(out)
     }
 
-    fn search_messages(
+    /// FTS matches with their bm25 score (lower is better).
+    fn search_scored(
         &self,
         session_id: &str,
+        viewer: Option<&str>,
+        exclude: Option<i64>,
         query: &str,
         limit: usize,
-    ) -> Result<Vec<MemoryMessage>> {
+    ) -> Result<Vec<(MemoryMessage, i64, f64)>> {
         let mut stmt = self
             .conn
             .prepare(
-                "SELECT m.id, m.role, m.agent, m.content
+                "SELECT m.id, m.role, m.agent, m.content, m.created_at, bm25(messages_fts)
                  FROM messages_fts f
                  JOIN messages m ON m.id = f.rowid
                  WHERE f.content MATCH ?1 AND m.session_id = ?2
+                   AND (?4 IS NULL OR m.visibility = 'shared' OR m.agent = ?4)
+                   AND m.id IS NOT ?5
                  ORDER BY bm25(messages_fts), m.id DESC
                  LIMIT ?3",
             )
             .context("prepare search messages")?;
 
         let mut rows = stmt
-            .query(params![query, session_id, limit as i64])
+            .query(params![query, session_id, limit as i64, viewer, exclude])
             .context("query search messages")?;
 
         let mut out = Vec::new();
         while let Some(row) = rows.next().context("scan search row")? {
-            out.push(MemoryMessage {
+            let message = MemoryMessage {
                 id: row.get(0).context("search.id")?,
                 role: row.get(1).context("search.role")?,
                 agent: row.get(2).context("search.agent")?,
                 content: row.get(3).context("search.content")?,
-            });
+            };
+            out.push((
+                message,
+                row.get(4).context("search.created_at")?,
+                row.get(5).context("search.bm25")?,
+            ));
+        }
+        Ok(out)
+    }
+
+    /// Older messages relevant to `prompt` with their ranking score, best first.
+    ///
+    /// Relevance comes from the configured strategy, decays exponentially with
+    /// age down to `DECAY_FLOOR`, and gets `SAME_AGENT_BONUS` for assistant
+    /// replies from `agent`.
+    fn retrieve(
+        &self,
+        session_id: &str,
+        prompt: &str,
+        agent: Option<&str>,
+        exclude: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<(MemoryMessage, f32)>> {
+        let query = normalize_query(prompt);
+        let mut candidates: Vec<Scored> = Vec::new();
+        if self.retrieval != RetrievalStrategy::Vector {
+            if let Some(query) = &query {
+                let hits =
+                    self.search_scored(session_id, agent, exclude, query, FTS_CANDIDATE_LIMIT)?;
+                // bm25 is negative, best match most negative; scale to best = 1.0.
+                let best = hits.iter().map(|(_, _, score)| *score).fold(0.0, f64::min);
+                for (message, created_at, score) in hits {
+                    let keyword = if best < 0.0 {
+                        (score / best) as f32
+                    } else {
+                        1.0
+                    };
+                    candidates.push(Scored {
+                        message,
+                        created_at,
+                        keyword,
+                        similarity: 0.0,
+                    });
+                }
+            }
+        }
+
+        let query_vector = embedding::embed(prompt);
+        if self.retrieval != RetrievalStrategy::Fts && !query_vector.is_empty() {
+            let docs = self.session_vectors(session_id, agent, exclude, VECTOR_SCAN_LIMIT)?;
+            let idf = embedding::idf(&docs.iter().map(|(_, _, v)| v).collect::<Vec<_>>());
+            for (message, created_at, vector) in docs {
+                let similarity = embedding::cosine(&query_vector, &vector, &idf);
+                if let Some(existing) = candidates.iter_mut().find(|c| c.message.id == message.id) {
+                    existing.similarity = similarity;
+                } else if similarity >= MIN_SIMILARITY {
+                    candidates.push(Scored {
+                        message,
+                        created_at,
+                        keyword: 0.0,
+                        similarity,
+                    });
+                }
+            }
+        }
+
+        let now = unix_now();
+        let score = |c: &Scored| {
+            let relevance = match self.retrieval {
+                RetrievalStrategy::Fts => c.keyword,
+                RetrievalStrategy::Vector => c.similarity,
+                RetrievalStrategy::Hybrid => {
+                    KEYWORD_WEIGHT * c.keyword + VECTOR_WEIGHT * c.similarity
+                }
+            };
+            let decay = recency_decay(now - c.created_at, self.recency_half_life_secs);
+            let same_agent = c.message.role == "assistant"
+                && agent.is_some()
+                && c.message.agent.as_deref() == agent;
+            relevance * (DECAY_FLOOR + (1.0 - DECAY_FLOOR) * decay)
+                + if same_agent { SAME_AGENT_BONUS } else { 0.0 }
+        };
+        candidates.sort_by(|a, b| {
+            score(b)
+                .total_cmp(&score(a))
+                .then(b.message.id.cmp(&a.message.id))
+        });
+        Ok(candidates
+            .into_iter()
+            .take(limit)
+            .map(|c| {
+                let score = score(&c);
+                (c.message, score)
+            })
+            .collect())
+    }
+
+    /// Most recent messages of a session with their stored vectors.
+    fn session_vectors(
+        &self,
+        session_id: &str,
+        viewer: Option<&str>,
+        exclude: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<(MemoryMessage, i64, SparseVector)>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT m.id, m.role, m.agent, m.content, m.created_at, v.vector
+                 FROM messages m
+                 JOIN message_vectors v ON v.message_id = m.id
+                 WHERE m.session_id = ?1
+                   AND (?3 IS NULL OR m.visibility = 'shared' OR m.agent = ?3)
+                   AND m.id IS NOT ?4
+                 ORDER BY m.id DESC
+                 LIMIT ?2",
+            )
+            .context("prepare session vectors")?;
+
+        let mut rows = stmt
+            .query(params![session_id, limit as i64, viewer, exclude])
+            .context("query session vectors")?;
+
+        let mut out = Vec::new();
+        while let Some(row) = rows.next().context("scan vector row")? {
+            let message = MemoryMessage {
+                id: row.get(0).context("vector.id")?,
+                role: row.get(1).context("vector.role")?,
+                agent: row.get(2).context("vector.agent")?,
+                content: row.get(3).context("vector.content")?,
+            };
+            let created_at: i64 = row.get(4).context("vector.created_at")?;
+            let blob: Vec<u8> = row.get(5).context("vector.blob")?;
+            out.push((message, created_at, SparseVector::from_blob(&blob)));
         }
         Ok(out)
     }
 }
 
+pub(crate) fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Byte count as `B`, `KB` or `MB` for `/mem stats`.
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    let b = bytes as f64;
+    if b >= KB * KB {
+        format!("{:.1} MB", b / (KB * KB))
+    } else if b >= KB {
+        format!("{:.1} KB", b / KB)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+/// `0.5^(age / half_life)`; 1.0 when decay is disabled or the clock is skewed.
+fn recency_decay(age_secs: i64, half_life_secs: f64) -> f32 {
+    if half_life_secs <= 0.0 || age_secs <= 0 {
+        return 1.0;
+    }
+    0.5f64.powf(age_secs as f64 / half_life_secs) as f32
+}
+
+/// Prefix `text` with a pinned-facts block; unchanged when there are no pins.
+pub(crate) fn with_pins(pins: &[Pin], text: String) -> String {
+    if pins.is_empty() {
+        return text;
+    }
+    let facts = pins
+        .iter()
+        .map(|pin| format!("- {}", pin.content))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("Pinned project facts (always apply):\n{facts}\n\n{text}")
+}
+
+fn delete_session_events(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM run_events_fts
+         WHERE rowid IN (SELECT id FROM run_events WHERE session_id = ?1)",
+        params![session_id],
+    )
+    .context("delete run event fts rows")?;
+    conn.execute(
+        "DELETE FROM run_events WHERE session_id = ?1",
+        params![session_id],
+    )
+    .context("delete run events")?;
+    Ok(())
+}
+
+fn delete_session_summaries(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM messages_archive WHERE session_id = ?1",
+        params![session_id],
+    )
+    .context("delete archived messages")?;
+    conn.execute(
+        "DELETE FROM summaries WHERE session_id = ?1",
+        params![session_id],
+    )
+    .context("delete summaries")?;
+    Ok(())
+}
+
 fn memory_file_path() -> PathBuf {
     if let Some(home) = std::env::var_os("HOME") {
         PathBuf::from(home).join(".dagent").join("memory.db")
@@ -345,22 +2042,219 @@ fn memory_file_path() -> PathBuf {
     }
 }
 
+fn render_jsonl(records: &[ExportRecord]) -> Result<String> {
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&serde_json::to_string(record).context("serialize export row")?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn render_markdown_export(records: &[ExportRecord]) -> String {
+    let mut out = String::from("# DAgent memory export\n");
+    let mut current_session: Option<&str> = None;
+    for record in records {
+        if current_session != Some(record.session.as_str()) {
+            out.push_str(&format!("\n## Session `{}`\n", record.session));
+            current_session = Some(record.session.as_str());
+        }
+        let actor = match &record.agent {
+            Some(agent) => format!("{} ({})", record.role, agent),
+            None => record.role.clone(),
+        };
+        out.push_str(&format!(
+            "\n### {} · {}\n\n{}\n",
+            actor,
+            format_timestamp(record.created_at),
+            record.content.trim_end()
+        ));
+    }
+    out
+}
+
+/// Format unix seconds as `YYYY-MM-DD HH:MM UTC` without a date crate.
+pub(crate) fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60
+    )
+}
+
+/// Rough token count: about four characters per token, one per CJK character.
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0, 0), |(cjk, other), ch| {
+        if is_cjk_char(ch) {
+            (cjk + 1, other)
+        } else {
+            (cjk, other + 1)
+        }
+    });
+    cjk + usize::div_ceil(other, 4)
+}
+
+/// `90s`, `30m`, `12h`, `7d` or `2w` in seconds.
+fn parse_duration_secs(raw: &str) -> Option<i64> {
+    let raw = raw.trim();
+    let split = raw.find(|c: char| !c.is_ascii_digit())?;
+    let (digits, unit) = raw.split_at(split);
+    let value = digits.parse::<i64>().ok()?;
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => return None,
+    };
+    value.checked_mul(scale)
+}
+
+/// Text as written to the FTS tables. `unicode61` keeps a run of Han
+/// characters as one token, so CJK runs are also indexed as overlapping
+/// bigrams; `normalize_query` builds the matching phrases.
+fn fts_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut run = Vec::new();
+    for ch in text.chars() {
+        if is_cjk_char(ch) {
+            run.push(ch);
+            out.push(ch);
+            continue;
+        }
+        push_cjk_bigrams(&mut out, &run);
+        run.clear();
+        out.push(ch);
+    }
+    push_cjk_bigrams(&mut out, &run);
+    out
+}
+
+fn push_cjk_bigrams(out: &mut String, run: &[char]) {
+    if run.len() < 2 {
+        return;
+    }
+    for pair in run.windows(2) {
+        out.push(' ');
+        out.extend(pair);
+    }
+    out.push(' ');
+}
+
+/// Reindex `messages_fts` and `run_events_fts` from their source tables.
+pub(super) fn rebuild_fts_indexes(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM messages_fts", [])
+        .context("clear fts index")?;
+    let rows = {
+        let mut stmt = conn
+            .prepare("SELECT id, content FROM messages")
+            .context("prepare fts rebuild")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .context("query fts rebuild")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("scan fts rebuild")?;
+        rows
+    };
+    for (id, content) in rows {
+        conn.execute(
+            "INSERT INTO messages_fts(rowid, content) VALUES (?1, ?2)",
+            params![id, fts_text(&content)],
+        )
+        .context("reindex message")?;
+    }
+
+    conn.execute("DELETE FROM run_events_fts", [])
+        .context("clear run event fts index")?;
+    let rows = {
+        let mut stmt = conn
+            .prepare("SELECT id, command, output FROM run_events")
+            .context("prepare run event fts rebuild")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .context("query run event fts rebuild")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("scan run event fts rebuild")?;
+        rows
+    };
+    for (id, command, output) in rows {
+        conn.execute(
+            "INSERT INTO run_events_fts(rowid, command, output) VALUES (?1, ?2, ?3)",
+            params![id, fts_text(&command), fts_text(&output)],
+        )
+        .context("reindex run event")?;
+    }
+    Ok(())
+}
+
+/// FTS5 `OR` query over the prompt's terms. Latin words match as tokens; a
+/// CJK run becomes a phrase of its bigrams, or a prefix match for a single
+/// character.
 fn normalize_query(input: &str) -> Option<String> {
     let mut terms = Vec::new();
     let mut seen = HashSet::new();
-    for t in input
+    'words: for word in input
         .split(|c: char| !c.is_alphanumeric())
         .map(|s| s.trim().to_lowercase())
     {
-        if t.len() < 2 {
-            continue;
-        }
-        if !seen.insert(t.clone()) {
-            continue;
+        let mut segments: Vec<(bool, String)> = Vec::new();
+        for ch in word.chars() {
+            let cjk = is_cjk_char(ch);
+            match segments.last_mut() {
+                Some((last_cjk, segment)) if *last_cjk == cjk => segment.push(ch),
+                _ => segments.push((cjk, ch.to_string())),
+            }
         }
-        terms.push(t);
-        if terms.len() >= 8 {
-            break;
+
+        for (cjk, segment) in segments {
+            let term = if !cjk {
+                if segment.len() < 2 {
+                    continue;
+                }
+                segment
+            } else {
+                let chars = segment.chars().collect::<Vec<_>>();
+                if chars.len() == 1 {
+                    format!("\"{segment}\"*")
+                } else {
+                    let bigrams = chars
+                        .windows(2)
+                        .map(|pair| pair.iter().collect::<String>())
+                        .collect::<Vec<_>>();
+                    format!("\"{}\"", bigrams.join(" "))
+                }
+            };
+            if !seen.insert(term.clone()) {
+                continue;
+            }
+            terms.push(term);
+            if terms.len() >= 8 {
+                break 'words;
+            }
         }
     }
 
@@ -431,6 +2325,27 @@ fn message_prefix(item: &MemoryMessage) -> String {
     }
 }
 
+/// Split `text` so its head, indented by `indent` on every line, stays within `budget`
+/// bytes. Cuts at a line break when one fits, else at a char boundary.
+fn split_for_budget(text: &str, indent: usize, budget: usize) -> (&str, &str) {
+    let mut end = 0;
+    let mut used = 0;
+    for line in text.split_inclusive('\n') {
+        if used + indent + line.len() > budget {
+            break;
+        }
+        used += indent + line.len();
+        end += line.len();
+    }
+    if end == 0 {
+        end = budget.saturating_sub(indent).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+    }
+    (text[..end].trim_end(), &text[end..])
+}
+
 fn format_line(item: &MemoryMessage) -> Option<String> {
     let text = squash_block_whitespace(item.content.trim());
     if text.is_empty() {
@@ -514,4 +2429,822 @@ mod tests {
         assert!(clipped.starts_with("user: "));
         assert!(clipped.ends_with("..."));
     }
+
+    #[test]
+    fn sessions_track_counts_and_support_rename_and_delete() {
+        let store = MemoryStore::open_in_memory().expect("open store");
+        store
+            .append_message("default", "user", None, "hello there")
+            .expect("append");
+        assert!(store.create_session("work", "Work Stuff").expect("create"));
+        assert!(!store.create_session("work", "dup").expect("create dup"));
+        store
+            .append_message("work", "assistant", Some("codex"), "done")
+            .expect("append work");
+
+        let sessions = store.list_sessions().expect("list");
+        assert_eq!(sessions.len(), 2);
+        let work = sessions.iter().find(|s| s.id == "work").expect("work");
+        assert_eq!(work.message_count, 1);
+
+        let by_title = store.find_session("work stuff").expect("find");
+        assert_eq!(by_title.map(|s| s.id).as_deref(), Some("work"));
+
+        assert!(store.rename_session("work", "Renamed").expect("rename"));
+        assert_eq!(
+            store.find_session("renamed").expect("find").map(|s| s.id),
+            Some("work".to_string())
+        );
+
+        assert_eq!(store.delete_session("work").expect("delete"), 1);
+        assert!(store.find_session("work").expect("find").is_none());
+        assert_eq!(store.session_message_count("default").expect("count"), 1);
+    }
+
+    #[test]
+    fn export_import_roundtrip_rebuilds_fts() {
+        let source = MemoryStore::open_in_memory().expect("open source");
+        source
+            .set_session_visibility("proj", Visibility::Split)
+            .expect("split");
+        source
+            .append_message("proj", "user", None, "how do we rotate the signing key")
+            .expect("append user");
+        source
+            .append_message("proj", "assistant", Some("codex"), "run scripts/rotate.sh")
+            .expect("append assistant");
+        let records = source.export_records(Some("proj")).expect("export");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].agent.as_deref(), Some("codex"));
+        assert_eq!(records[1].visibility, "private");
+        let legacy: ExportRecord = serde_json::from_str(
+            r#"{"session":"proj","role":"user","content":"hi","created_at":1}"#,
+        )
+        .expect("legacy record");
+        assert_eq!(legacy.visibility, "shared");
+
+        let target = MemoryStore::open_in_memory().expect("open target");
+        assert_eq!(target.import_records(&records, None).expect("import"), 2);
+        assert_eq!(target.import_records(&records, None).expect("reimport"), 0);
+
+        let hits = target
+            .search_session_lines("proj", "rotate", &SearchFilter::default(), 5)
+            .expect("search");
+        assert_eq!(hits.len(), 2);
+
+        let claude_view = target
+            .build_context(
+                "proj",
+                "next",
+                Some("claude"),
+                None,
+                &ContextBudget::default(),
+            )
+            .expect("context");
+        assert!(claude_view.contains("signing key"));
+        assert!(!claude_view.contains("rotate.sh"));
+
+        let moved = MemoryStore::open_in_memory().expect("open moved");
+        moved
+            .import_records(&records, Some("other"))
+            .expect("import into session");
+        assert_eq!(moved.session_message_count("other").expect("count"), 2);
+    }
+
+    #[test]
+    fn markdown_export_groups_by_session() {
+        let records = vec![ExportRecord {
+            session: "proj".to_string(),
+            role: "assistant".to_string(),
+            agent: Some("claude".to_string()),
+            content: "hello".to_string(),
+            created_at: 86_400 * 365,
+            visibility: "shared".to_string(),
+        }];
+        let md = render_markdown_export(&records);
+        assert!(md.contains("## Session `proj`"));
+        assert!(md.contains("### assistant (claude) · 1971-01-01 00:00 UTC"));
+    }
+
+    #[test]
+    fn transfer_args_parse_format_and_flags() {
+        let args = TransferArgs::parse(&["out.md", "--all"]).expect("parse");
+        assert_eq!(args.format, ExportFormat::Markdown);
+        assert!(args.all_sessions);
+
+        let args = TransferArgs::parse(&["out.txt", "--format", "jsonl", "--session", "x"])
+            .expect("parse");
+        assert_eq!(args.format, ExportFormat::Jsonl);
+        assert_eq!(args.session.as_deref(), Some("x"));
+
+        assert!(TransferArgs::parse(&[]).is_err());
+        assert!(TransferArgs::parse(&["a", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn hybrid_retrieval_finds_paraphrases_fts_misses() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        store
+            .append_message(
+                "s",
+                "assistant",
+                Some("codex"),
+                "The retry configuration lives in config.toml",
+            )
+            .expect("append");
+        for filler in ["spinner frames look fine", "ok", "ship it"] {
+            store
+                .append_message("s", "user", None, filler)
+                .expect("append");
+        }
+
+        let prompt = "where do I configure retries?";
+        let hybrid = store
+            .build_context("s", prompt, None, None, &ContextBudget::default())
+            .expect("context");
+        assert!(hybrid.contains("retry configuration"));
+        let plan = store
+            .plan_context("s", prompt, None, None, &ContextBudget::default())
+            .expect("plan");
+        let hit = plan
+            .items
+            .iter()
+            .find(|item| item.source == ContextSource::Search)
+            .expect("search hit");
+        assert_eq!(hit.id, 1);
+        assert!(hit.score.is_some_and(|score| score > 0.0));
+
+        let store = store.with_retrieval(RetrievalStrategy::Fts);
+        let fts = store
+            .build_context("s", prompt, None, None, &ContextBudget::default())
+            .expect("context");
+        assert!(!fts.contains("retry configuration"));
+    }
+
+    #[test]
+    fn retrieval_decays_stale_hits_and_favours_the_dispatched_agent() {
+        let store = MemoryStore::open_in_memory()
+            .expect("open")
+            .with_retrieval(RetrievalStrategy::Fts);
+        store
+            .append_message(
+                "s",
+                "assistant",
+                Some("codex"),
+                "cache ttl cache ttl: keep the cache ttl at one hour",
+            )
+            .expect("append");
+        store
+            .append_message("s", "assistant", Some("codex"), "decided: cache ttl is 10m")
+            .expect("append");
+        store
+            .conn
+            .execute(
+                "UPDATE messages SET created_at = unixepoch() - 120 * 86400 WHERE id = 1",
+                [],
+            )
+            .expect("age message");
+
+        let ids =
+            |hits: Vec<(MemoryMessage, f32)>| hits.iter().map(|(m, _)| m.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(store
+                .retrieve("s", "cache ttl", None, None, 2)
+                .expect("retrieve")),
+            vec![2, 1]
+        );
+        let no_decay = store.with_recency_half_life(0.0);
+        assert_eq!(
+            ids(no_decay
+                .retrieve("s", "cache ttl", None, None, 2)
+                .expect("retrieve"))[0],
+            1
+        );
+
+        no_decay
+            .append_message("s", "assistant", Some("claude"), "cache ttl is 10m")
+            .expect("append");
+        let hits = no_decay
+            .retrieve("s", "cache ttl is 10m", Some("claude"), None, 3)
+            .expect("retrieve");
+        assert_eq!(hits[0].0.agent.as_deref(), Some("claude"));
+        let hits = no_decay
+            .retrieve("s", "cache ttl is 10m", Some("codex"), None, 3)
+            .expect("retrieve");
+        assert_eq!(hits[0].0.agent.as_deref(), Some("codex"));
+    }
+
+    #[test]
+    fn retention_expires_old_records_and_trims_sessions() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        for i in 0..5 {
+            store
+                .append_message("a", "user", None, &format!("note {i}"))
+                .expect("append");
+        }
+        store
+            .append_message("b", "user", None, "ancient note")
+            .expect("append");
+        store.add_pin("b", "keep me").expect("pin");
+        store
+            .conn
+            .execute(
+                "UPDATE messages SET created_at = unixepoch() - 40 * 86400 WHERE session_id = 'b'",
+                [],
+            )
+            .expect("age");
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(30),
+            max_rows_per_session: Some(3),
+            max_db_mb: None,
+        };
+        let report = store.apply_retention(&policy).expect("retention");
+        assert_eq!(
+            report,
+            RetentionReport {
+                expired: 1,
+                trimmed: 2,
+                ..RetentionReport::default()
+            }
+        );
+        assert_eq!(store.session_message_count("a").expect("count"), 3);
+        assert_eq!(store.session_message_count("b").expect("count"), 0);
+        assert_eq!(store.list_pins("b").expect("pins").len(), 1);
+        assert_eq!(
+            store.apply_retention(&policy).expect("again"),
+            RetentionReport::default()
+        );
+
+        let stats = store.stats().expect("stats");
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.pins, 1);
+        assert!(stats.oldest.is_some());
+        assert!(stats.db_bytes > 0);
+        let (before, after) = store.vacuum().expect("vacuum");
+        assert!(after <= before);
+        assert_eq!(format_bytes(1536), "1.5 KB");
+    }
+
+    #[test]
+    fn size_limit_deletes_oldest_records_across_tables() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        // ~3 MB of old archive rows, then a few recent messages.
+        store
+            .conn
+            .execute(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 300)
+                 INSERT INTO messages_archive(id, session_id, role, content, created_at, summary_id)
+                 SELECT i, 'a', 'user', hex(randomblob(5000)), unixepoch() - 86400 + i, 1
+                 FROM n",
+                [],
+            )
+            .expect("archive");
+        for i in 0..5 {
+            store
+                .append_message("a", "user", None, &format!("recent note {i}"))
+                .expect("append");
+        }
+        let policy = RetentionPolicy {
+            max_db_mb: Some(1),
+            ..RetentionPolicy::default()
+        };
+        let report = store.apply_retention(&policy).expect("retention");
+        assert!(report.over_size > 0 && !report.size_unreachable);
+        assert!(store.live_bytes().expect("bytes") <= 1024 * 1024);
+        let stats = store.stats().expect("stats");
+        assert_eq!(stats.messages, 5);
+        assert!(stats.archived > 0 && stats.archived < 300);
+
+        // Pins are never deleted, so a cap they alone exceed is reported.
+        store
+            .conn
+            .execute(
+                "INSERT INTO pins(session_id, content) VALUES ('a', hex(randomblob(1200000)))",
+                [],
+            )
+            .expect("pin");
+        let report = store.apply_retention(&policy).expect("retention");
+        assert!(report.size_unreachable);
+        assert_eq!(report.over_size, 0);
+        assert_eq!(store.stats().expect("stats").messages, 5);
+    }
+
+    #[test]
+    fn visibility_keeps_agents_from_seeing_each_others_replies() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        let budget = ContextBudget::default();
+        let context = |agent: &str| {
+            store
+                .build_context("s", "rate limiter design", Some(agent), None, &budget)
+                .expect("context")
+        };
+
+        store
+            .append_message("s", "user", None, "rate limiter design?")
+            .expect("append");
+        store
+            .append_message("s", "assistant", Some("codex"), "codex: token bucket")
+            .expect("append");
+        assert!(context("claude").contains("token bucket"));
+
+        assert_eq!(
+            store.session_visibility("s").expect("read"),
+            Visibility::Shared
+        );
+        store
+            .set_session_visibility("s", Visibility::Split)
+            .expect("set");
+        store
+            .append_message("s", "user", Some("codex"), "rate limiter for uploads")
+            .expect("append");
+        store
+            .append_message("s", "assistant", Some("codex"), "codex: leaky bucket")
+            .expect("append");
+        let claude = context("claude");
+        assert!(claude.contains("rate limiter for uploads"));
+        assert!(!claude.contains("leaky bucket"));
+        assert!(context("codex").contains("leaky bucket"));
+
+        store
+            .set_session_visibility("s", Visibility::Private)
+            .expect("set");
+        store
+            .append_message(
+                "s",
+                "user",
+                Some("codex"),
+                "rate limiter for the codex-only ask",
+            )
+            .expect("append");
+        assert!(!context("claude").contains("codex-only ask"));
+        assert!(context("codex").contains("codex-only ask"));
+
+        let rows = store
+            .conn
+            .prepare("SELECT visibility FROM messages ORDER BY id")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .expect("rows");
+        assert_eq!(rows, ["shared", "shared", "shared", "private", "private"]);
+        assert_eq!(Visibility::parse("SPLIT"), Some(Visibility::Split));
+    }
+
+    #[test]
+    fn vectors_follow_message_lifecycle() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        for text in ["alpha", "beta", "gamma"] {
+            store
+                .append_message("s", "user", None, text)
+                .expect("append");
+        }
+        let count = |store: &MemoryStore| -> i64 {
+            store
+                .conn
+                .query_row("SELECT COUNT(*) FROM message_vectors", [], |row| row.get(0))
+                .expect("count")
+        };
+        assert_eq!(count(&store), 3);
+
+        store.prune_session_keep_recent("s", 1).expect("prune");
+        assert_eq!(count(&store), 1);
+
+        store
+            .conn
+            .execute("DELETE FROM message_vectors", [])
+            .expect("drop");
+        assert_eq!(store.index_missing_vectors().expect("reindex"), 1);
+        store.clear_session("s").expect("clear");
+        assert_eq!(count(&store), 0);
+    }
+
+    #[test]
+    fn compaction_archives_messages_and_keeps_digest_in_context() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        for i in 0..8 {
+            store
+                .append_message("s", "user", None, &format!("step {i} of the migration"))
+                .expect("append");
+        }
+        assert!(store
+            .compaction_batch("s", 6, None)
+            .expect("batch")
+            .is_none());
+
+        let batch = store
+            .compaction_batch("s", 3, None)
+            .expect("batch")
+            .expect("some");
+        assert_eq!(batch.count, 5);
+        assert!(batch.prompt().contains("user: step 0 of the migration"));
+        assert!(!batch.prompt().contains("step 5"));
+
+        let archived = store
+            .store_summary(&batch, "- migrated tables 0-4")
+            .expect("store");
+        assert_eq!(archived, 5);
+        assert_eq!(store.session_message_count("s").expect("count"), 3);
+        let archive: i64 = store
+            .conn
+            .query_row("SELECT COUNT(*) FROM messages_archive", [], |row| {
+                row.get(0)
+            })
+            .expect("archive count");
+        assert_eq!(archive, 5);
+
+        let ctx = store
+            .build_context("s", "unrelated", None, None, &ContextBudget::default())
+            .expect("context");
+        assert!(ctx.contains("summary of 5 earlier messages: - migrated tables 0-4"));
+
+        store.clear_session("s").expect("clear");
+        assert_eq!(
+            store
+                .build_context("s", "unrelated", None, None, &ContextBudget::default())
+                .expect("ctx"),
+            "unrelated"
+        );
+    }
+
+    #[test]
+    fn compaction_keeps_long_answers_whole_and_splits_oversized_ones() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        let answer = format!(
+            "{}\nsee src/retention.rs",
+            "decided: keep the archive. ".repeat(40)
+        );
+        store
+            .append_message("s", "assistant", Some("claude"), &answer)
+            .expect("append");
+        let huge = (0..2000)
+            .map(|i| format!("line {i} of the generated migration plan"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        store
+            .append_message("s", "assistant", Some("codex"), &huge)
+            .expect("append");
+        for i in 0..4 {
+            store
+                .append_message("s", "user", None, &format!("follow-up {i}"))
+                .expect("append");
+        }
+
+        // The first batch stops before the oversized message instead of clipping anything.
+        let batch = store
+            .compaction_batch("s", 0, None)
+            .expect("batch")
+            .expect("some");
+        assert!(batch.prompt().contains("see src/retention.rs"));
+        assert_eq!(batch.count, 1);
+        assert!(batch.split.is_none());
+        store
+            .store_summary(&batch, "- kept archive")
+            .expect("store");
+
+        let batch = store
+            .compaction_batch("s", 0, None)
+            .expect("batch")
+            .expect("some");
+        let (split_id, rest) = batch.split.clone().expect("split");
+        assert!(batch.transcript.len() <= COMPACT_INPUT_CHAR_LIMIT);
+        assert!(batch
+            .prompt()
+            .contains("line 0 of the generated migration plan"));
+        assert!(!batch.prompt().contains("line 1999 "));
+        assert!(rest.contains("line 1999 of the generated migration plan"));
+        store
+            .store_summary(&batch, "- plan, part 1")
+            .expect("store");
+
+        let (kept, archived): (String, String) = store
+            .conn
+            .query_row(
+                "SELECT m.content, a.content FROM messages m
+                 JOIN messages_archive a ON a.id = m.id WHERE m.id = ?1",
+                params![split_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("split rows");
+        assert_eq!(kept, rest);
+        assert_eq!(archived, huge);
+        let hits = store
+            .search_session_lines("s", "1999", &SearchFilter::default(), 5)
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn private_sessions_compact_and_read_digests_per_agent() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        store
+            .set_session_visibility("s", Visibility::Private)
+            .expect("private");
+        for i in 0..6 {
+            for agent in ["claude", "codex"] {
+                store
+                    .append_message("s", "user", Some(agent), &format!("{agent} task {i}"))
+                    .expect("append");
+                store
+                    .append_message("s", "assistant", Some(agent), &format!("{agent} reply {i}"))
+                    .expect("append");
+            }
+        }
+        // Private rows only: a shared digest has nothing to take.
+        assert!(store
+            .compaction_batch("s", 2, None)
+            .expect("batch")
+            .is_none());
+
+        let batch = store
+            .compaction_batch("s", 2, Some("claude"))
+            .expect("batch")
+            .expect("some");
+        assert!(batch.prompt().contains("claude reply 0"));
+        assert!(!batch.prompt().contains("codex"));
+        assert_eq!(
+            store
+                .store_summary(&batch, "- claude digest")
+                .expect("store"),
+            10
+        );
+        assert_eq!(store.session_message_count("s").expect("count"), 14);
+
+        let context = |agent: &str| {
+            store
+                .build_context("s", "next", Some(agent), None, &ContextBudget::default())
+                .expect("context")
+        };
+        assert!(context("claude").contains("- claude digest"));
+        assert!(!context("codex").contains("claude digest"));
+        let records = store.export_records(Some("s")).expect("export");
+        assert!(records.iter().any(|r| r.content == "codex reply 0"));
+    }
+
+    #[test]
+    fn pins_prefix_context_outside_the_char_budget() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        let long = "x".repeat(MAX_LINE_CHARS);
+        for _ in 0..10 {
+            store
+                .append_message("s", "user", None, &long)
+                .expect("append");
+        }
+        let first = store.add_pin("s", "use cargo nextest").expect("pin");
+        store.add_pin("s", "never   touch vendor/").expect("pin");
+        store.add_pin("other", "unrelated").expect("pin");
+        assert!(store.add_pin("s", "  ").is_err());
+        assert!(store.add_pin("s", &"y".repeat(MAX_PIN_CHARS + 1)).is_err());
+
+        let ctx = store
+            .build_context("s", "next step", None, None, &ContextBudget::default())
+            .expect("context");
+        assert!(ctx.starts_with(
+            "Pinned project facts (always apply):\n- use cargo nextest\n- never touch vendor/\n\n"
+        ));
+        assert!(!ctx.contains("unrelated"));
+
+        assert!(store.remove_pin("s", first).expect("unpin"));
+        assert!(!store.remove_pin("other", first).expect("unpin"));
+        let pins = store.list_pins("s").expect("pins");
+        assert_eq!(pins.len(), 1);
+        assert_eq!(pins[0].content, "never touch vendor/");
+    }
+
+    #[test]
+    fn legacy_file_db_is_backed_up_and_migrated() {
+        let dir = std::env::temp_dir().join(format!("dagent-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("tmp dir");
+        let path = dir.join("memory.db");
+        {
+            let conn = Connection::open(&path).expect("open legacy");
+            conn.execute_batch(
+                "CREATE TABLE messages (
+                   id INTEGER PRIMARY KEY AUTOINCREMENT,
+                   session_id TEXT NOT NULL,
+                   role TEXT NOT NULL,
+                   agent TEXT,
+                   content TEXT NOT NULL,
+                   created_at INTEGER NOT NULL DEFAULT (unixepoch())
+                 );
+                 INSERT INTO messages(session_id, role, content) VALUES ('old', 'user', 'hello');",
+            )
+            .expect("legacy schema");
+        }
+
+        let store = MemoryStore::open_at(&path).expect("migrate");
+        assert_eq!(
+            migrations::schema_version(&store.conn).expect("version"),
+            migrations::latest_version()
+        );
+        assert_eq!(store.list_sessions().expect("sessions")[0].id, "old");
+        assert_eq!(
+            store
+                .search_session_lines("old", "hello", &SearchFilter::default(), 5)
+                .expect("search")
+                .len(),
+            1
+        );
+        assert!(migrations::backup_path(&path, 0).exists());
+        drop(store);
+
+        let conn = Connection::open(&path).expect("reopen");
+        conn.pragma_update(None, "user_version", migrations::latest_version() + 1)
+            .expect("bump");
+        drop(conn);
+        let err = MemoryStore::open_at(&path).err().expect("refuse newer");
+        assert!(format!("{err:#}").contains("newer than this dagent supports"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn cjk_text_is_searchable_by_bigram_and_reindexed_by_migration() {
+        let mut store = MemoryStore::open_in_memory().expect("open");
+        store
+            .append_message("s", "user", None, "请修复缓存过期的问题")
+            .expect("append");
+        store
+            .append_message("s", "assistant", Some("codex"), "已更新配置文件")
+            .expect("append");
+
+        assert_eq!(
+            normalize_query("缓存 cache 过").as_deref(),
+            Some("\"缓存\" OR cache OR \"过\"*")
+        );
+        assert_eq!(
+            normalize_query("修复bug").as_deref(),
+            Some("\"修复\" OR bug")
+        );
+
+        let find = |store: &MemoryStore, query: &str| {
+            store
+                .search_session_lines("s", query, &SearchFilter::default(), 5)
+                .expect("search")
+        };
+        let hits = find(&store, "缓存过期");
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].contains("请修复缓存过期的问题"));
+        assert_eq!(find(&store, "配置").len(), 1);
+        assert_eq!(find(&store, "缓").len(), 1);
+
+        // A v6 index holds whole CJK runs; the migration rebuilds it.
+        store
+            .conn
+            .execute_batch(
+                "DELETE FROM messages_fts;
+                 INSERT INTO messages_fts(rowid, content) SELECT id, content FROM messages;",
+            )
+            .expect("legacy index");
+        store
+            .conn
+            .pragma_update(None, "user_version", 6)
+            .expect("downgrade");
+        assert!(find(&store, "缓存").is_empty());
+        migrations::migrate(&mut store.conn, None).expect("migrate");
+        assert_eq!(
+            migrations::schema_version(&store.conn).expect("version"),
+            migrations::latest_version()
+        );
+        assert_eq!(find(&store, "缓存").len(), 1);
+    }
+
+    #[test]
+    fn filtered_search_spans_sessions_and_narrows_by_agent_and_role() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        store
+            .append_message("a", "user", None, "how should the cache expire")
+            .expect("append");
+        store
+            .append_message(
+                "b",
+                "assistant",
+                Some("codex"),
+                "cache entries expire after 10m",
+            )
+            .expect("append");
+        store
+            .append_message("b", "assistant", Some("claude"), "the cache is fine")
+            .expect("append");
+        store.rename_session("b", "Cache work").expect("rename");
+
+        let local = store
+            .search_session_lines("a", "cache", &SearchFilter::default(), 10)
+            .expect("search");
+        assert_eq!(local.len(), 1);
+
+        let (filter, query) =
+            SearchFilter::parse(&["--all", "cache", "--agent", "codex", "--since", "7d"])
+                .expect("parse");
+        assert_eq!(query, "cache");
+        let hits = store
+            .search_session_lines("a", &query, &filter, 10)
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].contains("· Cache work · assistant(codex): cache entries"));
+        assert!(hits[0].contains(" UTC · "));
+
+        let (filter, query) = SearchFilter::parse(&["--all", "--role", "user"]).expect("parse");
+        assert!(query.is_empty());
+        let hits = store
+            .search_session_lines("b", &query, &filter, 10)
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+
+        assert!(SearchFilter::parse(&["--agent", "gpt"]).is_err());
+        assert!(SearchFilter::parse(&["--since", "soon"]).is_err());
+        assert_eq!(parse_duration_secs("2w"), Some(14 * 86_400));
+
+        let (label, content) = store.message_by_id(2).expect("read").expect("found");
+        assert_eq!(label, "assistant(codex)");
+        assert_eq!(content, "cache entries expire after 10m");
+    }
+
+    #[test]
+    fn run_events_are_searchable_and_cleared_with_the_session() {
+        use crate::providers::RunEventKind;
+
+        let store = MemoryStore::open_in_memory().expect("open");
+        let exec = RunEvent {
+            kind: RunEventKind::Exec,
+            command: "rm -rf .cache/build".to_string(),
+            exit_code: Some(0),
+            output: String::new(),
+        };
+        let read = RunEvent {
+            kind: RunEventKind::Tool,
+            command: "Read src/cache.rs".to_string(),
+            exit_code: None,
+            output: "pub fn evict()".to_string(),
+        };
+        store
+            .append_run_event("a", "run-1", "codex", &exec)
+            .expect("append");
+        store
+            .append_run_event("a", "run-2", "claude", &read)
+            .expect("append");
+        store
+            .append_run_event("b", "run-3", "codex", &exec)
+            .expect("append");
+
+        let (filter, query) =
+            SearchFilter::parse(&["--events", "cache", "--agent", "codex"]).expect("parse");
+        assert!(filter.events);
+        let hits = store
+            .search_run_event_lines("a", &query, &filter, 10)
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].contains("run-1 · codex exec: rm -rf .cache/build (exit 0)"));
+
+        let (filter, query) = SearchFilter::parse(&["--events", "--all"]).expect("parse");
+        let hits = store
+            .search_run_event_lines("a", &query, &filter, 10)
+            .expect("search");
+        assert_eq!(hits.len(), 3);
+        assert!(hits[1].contains("claude tool: Read src/cache.rs → pub fn evict()"));
+        assert!(SearchFilter::parse(&["--events", "--role", "user"]).is_err());
+
+        store.clear_session("a").expect("clear");
+        let hits = store
+            .search_run_event_lines("a", "cache", &filter, 10)
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].contains("run-3"));
+    }
+
+    #[test]
+    fn context_plan_respects_token_budget_and_reports_usage() {
+        let store = MemoryStore::open_in_memory().expect("open");
+        for i in 0..6 {
+            store
+                .append_message(
+                    "s",
+                    "user",
+                    None,
+                    &format!("deploy step {i} {}", "x".repeat(200)),
+                )
+                .expect("append");
+        }
+        store.add_pin("s", "use cargo nextest").expect("pin");
+        let budget = ContextBudget {
+            max_tokens: 120,
+            recent: 2,
+            search: 8,
+            ..ContextBudget::default()
+        };
+
+        let plan = store
+            .plan_context("s", "deploy", None, None, &budget)
+            .expect("plan");
+        let (recent, recent_tokens) = plan.usage(ContextSource::Recent);
+        let (search, search_tokens) = plan.usage(ContextSource::Search);
+        assert!(recent + search >= 1);
+        assert!(recent_tokens + search_tokens <= budget.max_tokens);
+        assert_eq!(plan.pin_tokens(), estimate_tokens("use cargo nextest"));
+        assert!(plan.render().starts_with("Pinned project facts"));
+
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("记忆"), 2);
+    }
 }
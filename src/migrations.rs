@@ -0,0 +1,274 @@
+//! Ordered schema migrations for the memory database.
+//!
+//! The applied version lives in `PRAGMA user_version`. Databases created before
+//! versioning report 0 but already hold some tables, so the early migrations
+//! stay idempotent (`IF NOT EXISTS`, `OR IGNORE`). A migration whose data
+//! change cannot be written in SQL runs a Rust `backfill` after its script.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
+    backfill: Option<fn(&Connection) -> Result<()>>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "messages and fts index",
+        sql: "
+            CREATE TABLE IF NOT EXISTS messages (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              session_id TEXT NOT NULL,
+              role TEXT NOT NULL,
+              agent TEXT,
+              content TEXT NOT NULL,
+              created_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+            CREATE INDEX IF NOT EXISTS idx_messages_session_id_id
+              ON messages(session_id, id);
+            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts
+              USING fts5(content, tokenize='unicode61');
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 2,
+        name: "named sessions",
+        sql: "
+            CREATE TABLE IF NOT EXISTS sessions (
+              id TEXT PRIMARY KEY,
+              title TEXT NOT NULL,
+              created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+              updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+            INSERT OR IGNORE INTO sessions(id, title, created_at, updated_at)
+              SELECT session_id, session_id, MIN(created_at), MAX(created_at)
+              FROM messages
+              GROUP BY session_id;
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 3,
+        name: "message vectors",
+        sql: "
+            CREATE TABLE IF NOT EXISTS message_vectors (
+              message_id INTEGER PRIMARY KEY,
+              vector BLOB NOT NULL
+            );
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 4,
+        name: "summaries and archive",
+        sql: "
+            CREATE TABLE IF NOT EXISTS summaries (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              session_id TEXT NOT NULL,
+              content TEXT NOT NULL,
+              first_message_id INTEGER NOT NULL,
+              last_message_id INTEGER NOT NULL,
+              message_count INTEGER NOT NULL,
+              created_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+            CREATE INDEX IF NOT EXISTS idx_summaries_session_id_id
+              ON summaries(session_id, id);
+            CREATE TABLE IF NOT EXISTS messages_archive (
+              id INTEGER PRIMARY KEY,
+              session_id TEXT NOT NULL,
+              role TEXT NOT NULL,
+              agent TEXT,
+              content TEXT NOT NULL,
+              created_at INTEGER NOT NULL,
+              summary_id INTEGER NOT NULL
+            );
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 5,
+        name: "pinned facts",
+        sql: "
+            CREATE TABLE IF NOT EXISTS pins (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              session_id TEXT NOT NULL,
+              content TEXT NOT NULL,
+              created_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 6,
+        name: "agent run events",
+        sql: "
+            CREATE TABLE IF NOT EXISTS run_events (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              session_id TEXT NOT NULL,
+              run_id TEXT NOT NULL,
+              provider TEXT NOT NULL,
+              kind TEXT NOT NULL,
+              command TEXT NOT NULL,
+              exit_code INTEGER,
+              output TEXT NOT NULL DEFAULT '',
+              created_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );
+            CREATE INDEX IF NOT EXISTS idx_run_events_session_id_id
+              ON run_events(session_id, id);
+            CREATE VIRTUAL TABLE IF NOT EXISTS run_events_fts
+              USING fts5(command, output, tokenize='unicode61');
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 7,
+        name: "cjk bigram fts",
+        sql: "",
+        backfill: Some(super::rebuild_fts_indexes),
+    },
+    Migration {
+        version: 8,
+        name: "memory visibility",
+        sql: "",
+        backfill: Some(add_visibility_columns),
+    },
+    Migration {
+        version: 9,
+        name: "per-agent summaries",
+        sql: "",
+        backfill: Some(add_summary_owner_columns),
+    },
+];
+
+fn add_visibility_columns(conn: &Connection) -> Result<()> {
+    for table in ["sessions", "messages"] {
+        add_column_if_missing(conn, table, "visibility", "TEXT NOT NULL DEFAULT 'shared'")?;
+    }
+    Ok(())
+}
+
+/// Summaries get an owning agent, and summaries and archived rows keep the
+/// row visibility of what they replaced. Digests written before this mixed
+/// every row of the session, so those of non-shared sessions are hidden.
+fn add_summary_owner_columns(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "summaries", "agent", "TEXT")?;
+    for table in ["summaries", "messages_archive"] {
+        add_column_if_missing(conn, table, "visibility", "TEXT NOT NULL DEFAULT 'shared'")?;
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET visibility = 'private'
+                 WHERE session_id IN (SELECT id FROM sessions WHERE visibility != 'shared')"
+            ),
+            [],
+        )
+        .with_context(|| format!("hide legacy {table} of non-shared sessions"))?;
+    }
+    Ok(())
+}
+
+/// `ALTER TABLE ADD COLUMN` has no `IF NOT EXISTS`; check `table_info` first.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+        ))
+        .and_then(|mut stmt| stmt.exists([column]))
+        .with_context(|| format!("inspect {table} columns"))?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))
+            .with_context(|| format!("add {table}.{column}"))?;
+    }
+    Ok(())
+}
+
+/// Schema version this build writes.
+pub(super) fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub(super) fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get::<_, u32>(0))
+        .context("read schema version")
+}
+
+/// Apply pending migrations, each in its own transaction.
+///
+/// A file-backed database that already holds tables is copied to
+/// `<db>.v<version>.bak` first. Returns the number of migrations applied.
+pub(super) fn migrate(conn: &mut Connection, db_path: Option<&Path>) -> Result<usize> {
+    let current = schema_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        bail!(
+            "memory db schema v{current} is newer than this dagent supports (v{latest}); upgrade dagent"
+        );
+    }
+    if current == latest {
+        return Ok(0);
+    }
+
+    if let Some(path) = db_path {
+        if has_tables(conn)? {
+            backup(conn, &backup_path(path, current))?;
+        }
+    }
+
+    let mut applied = 0;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction().context("begin migration tx")?;
+        tx.execute_batch(migration.sql).with_context(|| {
+            format!(
+                "apply migration v{} ({})",
+                migration.version, migration.name
+            )
+        })?;
+        if let Some(backfill) = migration.backfill {
+            backfill(&tx).with_context(|| {
+                format!(
+                    "backfill migration v{} ({})",
+                    migration.version, migration.name
+                )
+            })?;
+        }
+        tx.pragma_update(None, "user_version", migration.version)
+            .context("bump schema version")?;
+        tx.commit().context("commit migration tx")?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+pub(super) fn backup_path(db_path: &Path, version: u32) -> PathBuf {
+    let mut name = db_path.as_os_str().to_os_string();
+    name.push(format!(".v{version}.bak"));
+    PathBuf::from(name)
+}
+
+fn has_tables(conn: &Connection) -> Result<bool> {
+    let count = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .context("inspect schema")?;
+    Ok(count > 0)
+}
+
+fn backup(conn: &Connection, target: &Path) -> Result<()> {
+    if target.exists() {
+        fs::remove_file(target)
+            .with_context(|| format!("replace memory backup {}", target.display()))?;
+    }
+    conn.execute("VACUUM INTO ?1", [target.to_string_lossy()])
+        .with_context(|| format!("back up memory db to {}", target.display()))?;
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 
@@ -11,6 +12,7 @@ pub(crate) fn execute_line(
     primary_provider: Provider,
     available_providers: Vec<Provider>,
     line: String,
+    prompts: HashMap<Provider, String>,
     dispatch_target: DispatchTarget,
     tx: Sender<WorkerEvent>,
     child_pids: Arc<Mutex<Vec<u32>>>,
@@ -69,7 +71,10 @@ pub(crate) fn execute_line(
         .into_iter()
         .map(|provider| {
             let tx = tx.clone();
-            let line = line.clone();
+            let line = prompts
+                .get(&provider)
+                .cloned()
+                .unwrap_or_else(|| line.clone());
             let available = available_providers.clone();
             let pids = child_pids.clone();
             std::thread::spawn(move || {
@@ -137,8 +142,13 @@ fn execute_slash_command(
         "/provider" => Ok("provider alias enabled; use /primary".to_string()),
         "/primary" => Ok("primary change handled in UI".to_string()),
         "/theme" => Ok("theme change handled in UI".to_string()),
+        "/layout" => Ok("layout change handled in UI".to_string()),
+        "/copy" => Ok("copy handled in UI".to_string()),
+        "/find" => Ok("find handled in UI".to_string()),
         "/clear" => Ok("clear handled in UI".to_string()),
         "/mem" => Ok("memory command handled in UI".to_string()),
+        "/context" => Ok("context command handled in UI".to_string()),
+        "/session" => Ok("session command handled in UI".to_string()),
         _ => Err("unknown command. use /help".to_string()),
     }
 }
@@ -202,8 +212,32 @@ fn help_text() -> String {
         "  /provider [claude|codex]",
         "",
         "visibility",
-        "  /theme [fjord|graphite|solarized|aurora|ember]",
+        "  /theme [fjord|graphite|solarized|aurora|ember|daylight|<file>]",
+        "  ~/.dagent/themes/<name>.toml  custom themes, reloaded on save",
+        "  /layout [split|stacked]  multi-agent answers side by side or stacked",
+        "  /find <text> | Ctrl+F  search the transcript, n/N between hits",
+        "  Ctrl+G or Ctrl+X Ctrl+E  compose the prompt in $VISUAL / $EDITOR",
+        "  /copy [last|<n>|code [k]]  answer or code block to the clipboard (OSC 52)",
+        "  Ctrl+Y copy selected entry or last answer, Alt+Y its last code block",
         "  /mem [show|find|prune|clear]",
+        "  /mem find <query> [--all] [--agent a] [--role r] [--since 7d]",
+        "  /mem find --events <query>  commands and tools agents ran",
+        "  /mem use <id>  add a search hit to the prompt",
+        "  /mem compact [keep] [agent]  summarize older memory into a digest",
+        "  /mem stats | /mem vacuum  db size and retention, reclaim space",
+        "  /mem visibility [shared|private|split]  what agents see of each other",
+        "  /context  token budget breakdown per agent",
+        "  /context <prompt>  preview the exact prompt each agent would get",
+        "  /mem pin <text> | /mem pins | /mem unpin <id>  pinned facts",
+        "  /mem export <path> [--format jsonl|md] [--all]",
+        "  /mem import <path> [--session <name>]",
+        "  dagent mem export|import <path>  same, without the TUI",
+        "",
+        "sessions",
+        "  /session [list|new|switch|rename|delete]",
+        "  dagent --session <name>  start in a named session",
+        "  dagent --fullscreen  alternate screen with a scrollable transcript",
+        "  dagent --mouse  --fullscreen plus wheel scroll, click to select, click a fence to copy",
         "",
         "tools",
         "  /tool <echo|time|bash> [input]",
@@ -217,6 +251,7 @@ fn help_text() -> String {
         "keys",
         "  Enter send | Shift+Enter newline | PgUp/PgDn scroll",
         "  Ctrl+R history search",
+        "  Ctrl+T split/stacked | Alt+Left/Right split column focus",
     ]
     .join("\n")
 }
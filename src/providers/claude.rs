@@ -5,6 +5,7 @@ use std::sync::{Arc, Mutex};
 use crossbeam_channel::Sender;
 use serde_json::Value;
 
+use super::{RunEvent, RunEventKind};
 use crate::app::{Provider, WorkerEvent};
 
 fn is_root_user() -> bool {
@@ -81,12 +82,16 @@ fn run_stream_once(
 
     let mut emitted = false;
     let mut fallback_lines: Vec<String> = Vec::new();
+    let mut tool_calls = ToolCallTracker::default();
     for line in reader.lines() {
         let line = line.map_err(|e| format!("claude fallback read failed: {e}"))?;
         if line.trim().is_empty() {
             continue;
         }
         fallback_lines.push(line.clone());
+        for event in tool_calls.observe(&line) {
+            let _ = tx.send(WorkerEvent::RunEvent { provider, event });
+        }
         if let Some(tool_info) = extract_tool_use(&line) {
             let _ = tx.send(WorkerEvent::Tool {
                 provider: Some(provider),
@@ -105,6 +110,9 @@ fn run_stream_once(
             }
         }
     }
+    for event in tool_calls.finish() {
+        let _ = tx.send(WorkerEvent::RunEvent { provider, event });
+    }
 
     let status = child
         .wait()
@@ -178,6 +186,7 @@ pub(crate) fn run_stream(
     let mut saw_quota_error = false;
     let mut emitted = false;
     let mut emitted_non_quota = false;
+    let mut tool_calls = ToolCallTracker::default();
     for line in reader.lines() {
         let line = line.map_err(|e| format!("claude stream read failed: {e}"))?;
         if line.trim().is_empty() {
@@ -187,6 +196,9 @@ pub(crate) fn run_stream(
         if is_quota_error_text(&line) {
             saw_quota_error = true;
         }
+        for event in tool_calls.observe(&line) {
+            let _ = tx.send(WorkerEvent::RunEvent { provider, event });
+        }
         if let Some(tool_info) = extract_tool_use(&line) {
             let _ = tx.send(WorkerEvent::Tool {
                 provider: Some(provider),
@@ -213,6 +225,9 @@ pub(crate) fn run_stream(
             }
         }
     }
+    for event in tool_calls.finish() {
+        let _ = tx.send(WorkerEvent::RunEvent { provider, event });
+    }
 
     let status = child
         .wait()
@@ -432,6 +447,121 @@ fn extract_progress_event(line: &str) -> Option<String> {
     }
 }
 
+/// Pairs `tool_use` blocks with their `tool_result` so each call is recorded
+/// once, with its output.
+#[derive(Default)]
+struct ToolCallTracker {
+    pending: Vec<(String, RunEvent)>,
+}
+
+impl ToolCallTracker {
+    fn observe(&mut self, line: &str) -> Vec<RunEvent> {
+        let Some(value) = parse_json_line(line) else {
+            return Vec::new();
+        };
+        let Some(blocks) = value
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(Value::as_array)
+        else {
+            return Vec::new();
+        };
+
+        let mut done = Vec::new();
+        for block in blocks {
+            match block.get("type").and_then(Value::as_str) {
+                Some("tool_use") => {
+                    let id = block.get("id").and_then(Value::as_str).unwrap_or_default();
+                    self.pending.push((id.to_string(), tool_use_event(block)));
+                }
+                Some("tool_result") => {
+                    let id = block
+                        .get("tool_use_id")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let Some(pos) = self.pending.iter().position(|(pending, _)| pending == id)
+                    else {
+                        continue;
+                    };
+                    let (_, mut event) = self.pending.remove(pos);
+                    event.output = tool_result_text(block.get("content"));
+                    let is_error = block
+                        .get("is_error")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false);
+                    if event.kind == RunEventKind::Exec {
+                        event.exit_code = if is_error {
+                            parse_exit_code(&event.output)
+                        } else {
+                            Some(0)
+                        };
+                    }
+                    done.push(event);
+                }
+                _ => {}
+            }
+        }
+        done
+    }
+
+    /// Calls that never got a result, e.g. when the run was cancelled.
+    fn finish(self) -> Vec<RunEvent> {
+        self.pending.into_iter().map(|(_, event)| event).collect()
+    }
+}
+
+fn tool_use_event(block: &Value) -> RunEvent {
+    let name = block.get("name").and_then(Value::as_str).unwrap_or("tool");
+    let input = block.get("input");
+    if name == "Bash" {
+        if let Some(command) = input.and_then(|v| v.get("command")).and_then(Value::as_str) {
+            return RunEvent {
+                kind: RunEventKind::Exec,
+                command: command.to_string(),
+                exit_code: None,
+                output: String::new(),
+            };
+        }
+    }
+    let detail = input
+        .map(|v| {
+            ["file_path", "pattern", "query", "url"]
+                .iter()
+                .find_map(|key| v.get(*key).and_then(Value::as_str).map(str::to_string))
+                .unwrap_or_else(|| v.to_string())
+        })
+        .unwrap_or_default();
+    RunEvent {
+        kind: RunEventKind::Tool,
+        command: format!("{name} {detail}").trim().to_string(),
+        exit_code: None,
+        output: String::new(),
+    }
+}
+
+fn tool_result_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Claude's Bash tool reports failures as `Exit code N` on the first line.
+fn parse_exit_code(output: &str) -> Option<i64> {
+    output
+        .lines()
+        .next()?
+        .trim()
+        .strip_prefix("Exit code ")?
+        .parse()
+        .ok()
+}
+
 fn extract_fallback_text(line: &str) -> Option<String> {
     let value = parse_json_line(line)?;
     match value.get("type")?.as_str()? {
@@ -457,3 +587,61 @@ fn extract_fallback_text(line: &str) -> Option<String> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_calls_are_paired_with_their_results() {
+        let mut tracker = ToolCallTracker::default();
+        let assistant = r#"{"type":"assistant","message":{"content":[
+            {"type":"text","text":"checking"},
+            {"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test"}},
+            {"type":"tool_use","id":"t2","name":"Read","input":{"file_path":"src/lib.rs"}},
+            {"type":"tool_use","id":"t3","name":"Bash","input":{"command":"sleep 100"}}]}}"#
+            .replace('\n', "");
+        assert!(tracker.observe(&assistant).is_empty());
+
+        let results = r#"{"type":"user","message":{"content":[
+            {"type":"tool_result","tool_use_id":"t1","is_error":true,
+             "content":"Exit code 101\nerror: test failed"},
+            {"type":"tool_result","tool_use_id":"t2",
+             "content":[{"type":"text","text":"fn main() {}"}]},
+            {"type":"tool_result","tool_use_id":"unknown","content":"ignored"}]}}"#
+            .replace('\n', "");
+        let done = tracker.observe(&results);
+        assert_eq!(
+            done,
+            vec![
+                RunEvent {
+                    kind: RunEventKind::Exec,
+                    command: "cargo test".to_string(),
+                    exit_code: Some(101),
+                    output: "Exit code 101\nerror: test failed".to_string(),
+                },
+                RunEvent {
+                    kind: RunEventKind::Tool,
+                    command: "Read src/lib.rs".to_string(),
+                    exit_code: None,
+                    output: "fn main() {}".to_string(),
+                },
+            ]
+        );
+        assert!(tracker.observe("not json").is_empty());
+
+        let unfinished = tracker.finish();
+        assert_eq!(unfinished.len(), 1);
+        assert_eq!(unfinished[0].command, "sleep 100");
+        assert_eq!(unfinished[0].exit_code, None);
+    }
+
+    #[test]
+    fn exit_code_is_read_from_the_first_line_only() {
+        assert_eq!(parse_exit_code("Exit code 2\nno such file"), Some(2));
+        assert_eq!(parse_exit_code("  Exit code 127  "), Some(127));
+        assert_eq!(parse_exit_code("output\nExit code 1"), None);
+        assert_eq!(parse_exit_code("Exit code abc"), None);
+        assert_eq!(parse_exit_code(""), None);
+    }
+}
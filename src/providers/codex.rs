@@ -5,6 +5,7 @@ use std::sync::{Arc, Mutex};
 use crossbeam_channel::Sender;
 use serde_json::Value;
 
+use super::{RunEvent, RunEventKind};
 use crate::app::{Provider, WorkerEvent};
 
 fn codex_approval_policy() -> String {
@@ -94,6 +95,9 @@ pub(crate) fn run_stream(
                 last_progress = progress;
             }
         }
+        if let Some(event) = extract_run_event(&line) {
+            let _ = tx.send(WorkerEvent::RunEvent { provider, event });
+        }
         if let Some(chunk) = extract_text(&line) {
             if !chunk.trim().is_empty() {
                 emitted = true;
@@ -153,7 +157,7 @@ fn preview(text: &str, max_chars: usize) -> String {
     out
 }
 
-fn is_cjk_char(ch: char) -> bool {
+pub(crate) fn is_cjk_char(ch: char) -> bool {
     ('\u{4E00}'..='\u{9FFF}').contains(&ch) || ('\u{3400}'..='\u{4DBF}').contains(&ch)
 }
 
@@ -329,6 +333,58 @@ fn extract_progress(line: &str, prefer_zh: bool) -> Option<String> {
     }
 }
 
+/// Completed commands and tool calls, for the `run_events` memory table.
+fn extract_run_event(line: &str) -> Option<RunEvent> {
+    let value = parse_json_line(line)?;
+    if value.get("type")?.as_str()? != "item.completed" {
+        return None;
+    }
+    let item = value.get("item")?;
+    match item.get("type").and_then(Value::as_str)? {
+        "command_execution" => Some(RunEvent {
+            kind: RunEventKind::Exec,
+            command: item.get("command").and_then(Value::as_str)?.to_string(),
+            exit_code: item.get("exit_code").and_then(Value::as_i64),
+            output: item
+                .get("aggregated_output")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        }),
+        "function_call" | "tool_call" | "mcp_tool_call" => {
+            let name = item
+                .get("name")
+                .or_else(|| item.get("tool"))
+                .or_else(|| item.get("function").and_then(|f| f.get("name")))
+                .and_then(Value::as_str)
+                .unwrap_or("tool");
+            let arguments = item
+                .get("arguments")
+                .or_else(|| item.get("function").and_then(|f| f.get("arguments")))
+                .map(|v| match v.as_str() {
+                    Some(s) => s.to_string(),
+                    None => v.to_string(),
+                })
+                .unwrap_or_default();
+            let output = item
+                .get("output")
+                .or_else(|| item.get("result"))
+                .map(|v| match v.as_str() {
+                    Some(s) => s.to_string(),
+                    None => v.to_string(),
+                })
+                .unwrap_or_default();
+            Some(RunEvent {
+                kind: RunEventKind::Tool,
+                command: format!("{name} {arguments}").trim().to_string(),
+                exit_code: None,
+                output,
+            })
+        }
+        _ => None,
+    }
+}
+
 fn extract_text(line: &str) -> Option<String> {
     let value = parse_json_line(line)?;
     if value.get("type")?.as_str()? != "item.completed" {
@@ -342,3 +398,46 @@ fn extract_text(line: &str) -> Option<String> {
         .and_then(Value::as_str)
         .map(|s| s.replace('\r', "\n"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completed_commands_and_tool_calls_become_run_events() {
+        let exec = r#"{"type":"item.completed","item":{"id":"item_1","type":"command_execution",
+            "command":"bash -lc 'cargo build'","aggregated_output":"Finished dev","exit_code":0,
+            "status":"completed"}}"#
+            .replace('\n', "");
+        assert_eq!(
+            extract_run_event(&exec),
+            Some(RunEvent {
+                kind: RunEventKind::Exec,
+                command: "bash -lc 'cargo build'".to_string(),
+                exit_code: Some(0),
+                output: "Finished dev".to_string(),
+            })
+        );
+
+        let tool = r#"{"type":"item.completed","item":{"type":"mcp_tool_call","server":"docs",
+            "tool":"search","arguments":{"q":"fts5"},"result":"3 hits"}}"#
+            .replace('\n', "");
+        assert_eq!(
+            extract_run_event(&tool),
+            Some(RunEvent {
+                kind: RunEventKind::Tool,
+                command: r#"search {"q":"fts5"}"#.to_string(),
+                exit_code: None,
+                output: "3 hits".to_string(),
+            })
+        );
+
+        let started = r#"{"type":"item.started","item":{"type":"command_execution",
+            "command":"ls","status":"in_progress"}}"#
+            .replace('\n', "");
+        assert_eq!(extract_run_event(&started), None);
+        let message = r#"{"type":"item.completed","item":{"type":"agent_message","text":"done"}}"#;
+        assert_eq!(extract_run_event(message), None);
+        assert_eq!(extract_run_event("garbage"), None);
+    }
+}
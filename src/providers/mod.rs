@@ -7,6 +7,30 @@ use crate::app::{Provider, WorkerEvent};
 pub(crate) mod claude;
 pub(crate) mod codex;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RunEventKind {
+    Exec,
+    Tool,
+}
+
+impl RunEventKind {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Exec => "exec",
+            Self::Tool => "tool",
+        }
+    }
+}
+
+/// A shell command or tool call an agent ran, as recorded in memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RunEvent {
+    pub(crate) kind: RunEventKind,
+    pub(crate) command: String,
+    pub(crate) exit_code: Option<i64>,
+    pub(crate) output: String,
+}
+
 pub(crate) fn run_provider_stream(
     provider: Provider,
     prompt: &str,
@@ -0,0 +1,342 @@
+//! User-defined themes loaded from `~/.dagent/themes/*.toml`.
+//!
+//! A theme file starts from a preset and overrides any `ThemePalette` field:
+//!
+//! ```toml
+//! base = "daylight"          # preset to start from; defaults to graphite
+//!
+//! [colors]
+//! assistant_text = "#24292f" # hex, ANSI names ("light-blue") or 0-255 indices
+//! code_bg = "#f6f8fa"
+//!
+//! [providers]                # accent colours per agent
+//! claude = "#c15f3c"
+//! codex = "#0969da"
+//! ```
+//!
+//! Invalid values never reject the whole theme: the field keeps the base
+//! preset's colour and the problem is reported in `/theme`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use ratatui::style::Color;
+use serde::Deserialize;
+
+use super::{Provider, ThemePalette, ThemePreset};
+
+macro_rules! palette_fields {
+    ($($field:ident),* $(,)?) => {
+        /// Every `ThemePalette` field, as spelled in theme files.
+        #[cfg(test)]
+        const PALETTE_FIELDS: &[&str] = &[$(stringify!($field)),*];
+
+        fn palette_field_mut<'a>(
+            palette: &'a mut ThemePalette,
+            name: &str,
+        ) -> Option<&'a mut Color> {
+            match name {
+                $(stringify!($field) => Some(&mut palette.$field),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+palette_fields!(
+    prompt,
+    input_text,
+    muted_text,
+    highlight_fg,
+    highlight_bg,
+    activity_badge_fg,
+    activity_badge_bg,
+    activity_text,
+    status_text,
+    user_fg,
+    user_bg,
+    claude_label,
+    codex_label,
+    processing_label,
+    assistant_text,
+    assistant_processing_text,
+    system_text,
+    tool_icon,
+    tool_text,
+    error_label,
+    error_text,
+    banner_title,
+    panel_bg,
+    panel_fg,
+    approval_title,
+    code_fg,
+    code_bg,
+    inline_code_fg,
+    inline_code_bg,
+    bullet,
+    link,
+    syntax_keyword,
+    syntax_string,
+    syntax_number,
+    syntax_comment,
+    syntax_type,
+    diff_added,
+    diff_removed,
+    diff_hunk,
+);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    colors: BTreeMap<String, String>,
+    providers: BTreeMap<String, String>,
+}
+
+#[derive(Clone)]
+pub(super) struct CustomTheme {
+    pub(super) name: String,
+    pub(super) palette: ThemePalette,
+}
+
+/// Themes found in the themes directory, reloaded when its files change.
+pub(super) struct ThemeLibrary {
+    dir: Option<PathBuf>,
+    themes: Vec<CustomTheme>,
+    /// Problems found in the last load, one line each.
+    warnings: Vec<String>,
+    /// Name, mtime and size of every theme file at the last load.
+    fingerprint: Vec<(String, Option<SystemTime>, u64)>,
+}
+
+impl ThemeLibrary {
+    /// No directory: nothing is ever loaded. Used by tests.
+    pub(super) fn disabled() -> Self {
+        Self {
+            dir: None,
+            themes: Vec::new(),
+            warnings: Vec::new(),
+            fingerprint: Vec::new(),
+        }
+    }
+
+    pub(super) fn load(dir: PathBuf) -> Self {
+        let mut library = Self {
+            dir: Some(dir),
+            ..Self::disabled()
+        };
+        library.reload();
+        library
+    }
+
+    pub(super) fn themes(&self) -> &[CustomTheme] {
+        &self.themes
+    }
+
+    pub(super) fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub(super) fn get(&self, name: &str) -> Option<&CustomTheme> {
+        self.themes.iter().find(|theme| theme.name == name)
+    }
+
+    /// Reload when a theme file was added, removed or modified.
+    pub(super) fn reload_if_changed(&mut self) -> bool {
+        let Some(dir) = self.dir.as_deref() else {
+            return false;
+        };
+        if fingerprint(dir) == self.fingerprint {
+            return false;
+        }
+        self.reload();
+        true
+    }
+
+    fn reload(&mut self) {
+        self.themes.clear();
+        self.warnings.clear();
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        self.fingerprint = fingerprint(&dir);
+        for path in theme_files(&dir) {
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let name = name.to_lowercase();
+            if ThemePreset::parse(&name).is_some() {
+                self.warnings.push(format!(
+                    "{name}.toml: name taken by a built-in theme, skipped"
+                ));
+                continue;
+            }
+            let raw = match fs::read_to_string(&path) {
+                Ok(raw) => raw,
+                Err(err) => {
+                    self.warnings.push(format!("{name}.toml: {err}"));
+                    continue;
+                }
+            };
+            match parse_theme(&raw) {
+                Ok((palette, problems)) => {
+                    self.warnings
+                        .extend(problems.into_iter().map(|p| format!("{name}.toml: {p}")));
+                    self.themes.push(CustomTheme { name, palette });
+                }
+                Err(err) => self.warnings.push(format!("{name}.toml: {err}")),
+            }
+        }
+    }
+}
+
+/// Palette for one theme file plus the fields that fell back to the base.
+fn parse_theme(raw: &str) -> Result<(ThemePalette, Vec<String>), String> {
+    let file: ThemeFile = toml::from_str(raw).map_err(|err| err.message().to_string())?;
+    let mut problems = Vec::new();
+    let base = match file.base.as_deref() {
+        None => super::default_theme(),
+        Some(name) => ThemePreset::parse(name).unwrap_or_else(|| {
+            problems.push(format!("unknown base '{name}', using graphite"));
+            super::default_theme()
+        }),
+    };
+    let mut palette = base.palette();
+
+    let provider_fields = file.providers.iter().map(|(provider, value)| {
+        let field = match super::provider_from_name(provider) {
+            Some(Provider::Claude) => "claude_label",
+            Some(Provider::Codex) => "codex_label",
+            None => "",
+        };
+        (field, format!("providers.{provider}"), value)
+    });
+    let color_fields = file
+        .colors
+        .iter()
+        .map(|(field, value)| (field.as_str(), format!("colors.{field}"), value));
+    for (field, key, value) in color_fields.chain(provider_fields) {
+        let Some(slot) = palette_field_mut(&mut palette, field) else {
+            problems.push(format!("unknown key {key}"));
+            continue;
+        };
+        match parse_color(value) {
+            Some(color) => *slot = color,
+            None => problems.push(format!("{key}: invalid color '{value}'")),
+        }
+    }
+    Ok((palette, problems))
+}
+
+/// `#rrggbb`, `#rgb`, ANSI names such as `dark-gray`, or a 0-255 index.
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#').filter(|hex| hex.len() == 3) {
+        let mut channels = hex.chars().map(|c| c.to_digit(16).map(|d| (d * 17) as u8));
+        let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+        return Some(Color::Rgb(r, g, b));
+    }
+    value.parse::<Color>().ok()
+}
+
+fn theme_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn fingerprint(dir: &Path) -> Vec<(String, Option<SystemTime>, u64)> {
+    theme_files(dir)
+        .into_iter()
+        .map(|path| {
+            let meta = fs::metadata(&path).ok();
+            (
+                path.display().to_string(),
+                meta.as_ref().and_then(|meta| meta.modified().ok()),
+                meta.map(|meta| meta.len()).unwrap_or(0),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let dir = std::env::temp_dir().join(format!(
+            "dagent-themes-{tag}-{}-{nanos}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("create theme dir");
+        dir
+    }
+
+    #[test]
+    fn theme_files_override_fields_and_keep_base_on_bad_colors() {
+        let dir = temp_dir("load");
+        fs::write(
+            dir.join("Paper.toml"),
+            r##"
+base = "daylight"
+[colors]
+code_bg = "#fafafa"
+prompt = "not-a-color"
+nope = "red"
+[providers]
+claude = "#c60"
+"##,
+        )
+        .unwrap();
+        fs::write(dir.join("broken.toml"), "colors = 3").unwrap();
+        fs::write(dir.join("fjord.toml"), "").unwrap();
+
+        let library = ThemeLibrary::load(dir.clone());
+        let paper = library.get("paper").expect("paper theme loaded");
+        let daylight = ThemePreset::Daylight.palette();
+        assert_eq!(paper.palette.code_bg, Color::Rgb(250, 250, 250));
+        assert_eq!(paper.palette.claude_label, Color::Rgb(204, 102, 0));
+        assert_eq!(paper.palette.prompt, daylight.prompt);
+        assert_eq!(paper.palette.user_bg, daylight.user_bg);
+        assert_eq!(library.themes().len(), 1);
+
+        let warnings = library.warnings().join("\n");
+        assert!(warnings.contains("paper.toml: colors.prompt: invalid color 'not-a-color'"));
+        assert!(warnings.contains("paper.toml: unknown key colors.nope"));
+        assert!(warnings.contains("broken.toml"));
+        assert!(warnings.contains("fjord.toml: name taken"));
+
+        let mut library = library;
+        assert!(!library.reload_if_changed());
+        fs::write(dir.join("night.toml"), "base = \"ember\"").unwrap();
+        assert!(library.reload_if_changed());
+        assert!(library.get("night").is_some());
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn palette_fields_cover_every_field() {
+        // ThemePalette holds only colours, so its size counts its fields.
+        assert_eq!(
+            PALETTE_FIELDS.len() * std::mem::size_of::<Color>(),
+            std::mem::size_of::<ThemePalette>()
+        );
+        let mut palette = ThemePreset::Fjord.palette();
+        for field in PALETTE_FIELDS {
+            assert!(palette_field_mut(&mut palette, field).is_some(), "{field}");
+        }
+    }
+}
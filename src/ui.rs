@@ -1,3 +1,4 @@
+use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
@@ -5,7 +6,7 @@ use ratatui::widgets::{Block, BorderType, Borders, Clear, Padding, Paragraph, Wr
 use ratatui::Frame;
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use super::{App, Mode, Provider, ThemePalette};
+use super::{App, Mode, PreviewLine, Provider, ThemePalette};
 use crate::{input_cursor_position, providers_label, truncate};
 
 const PANEL_PADDING_X: u16 = 1;
@@ -16,6 +17,8 @@ const ACTIVITY_HORIZONTAL_INSET: u16 = 0;
 const ACTIVITY_VERTICAL_INSET: u16 = 0;
 const TRANSCRIPT_ACTIVITY_GAP_ROWS: u16 = 1;
 const MAX_SPINNER_ROWS_PER_AGENT: u16 = 6;
+/// Border rows plus at least one content row per split column.
+const SPLIT_MIN_ROWS: u16 = 3;
 
 pub(super) fn draw(f: &mut Frame, app: &App) {
     let frame_area = f.area();
@@ -28,6 +31,8 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
         .max(1);
 
     let show_finished = app.finished_at.is_some() && !app.running;
+    // Split columns carry each agent's elapsed time, so they replace the spinners.
+    let split = app.split_view_active();
     let activity_content_rows = if app.running {
         let spinner_agents = app.agent_entries.len().max(1) as u16;
         let spinner_rows = spinner_agents;
@@ -45,7 +50,7 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
     } else {
         0
     };
-    let activity_h = if activity_content_rows > 0 {
+    let activity_h = if activity_content_rows > 0 && !split {
         activity_content_rows.saturating_add(ACTIVITY_VERTICAL_INSET)
     } else {
         0
@@ -61,7 +66,8 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
         1u16.saturating_add(INPUT_VERTICAL_INSET)
     };
     let status_h: u16 = 1;
-    let fixed_rows = transcript_activity_gap_h + activity_h + hints_h + status_h;
+    let split_reserved_h = if split { SPLIT_MIN_ROWS } else { 0 };
+    let fixed_rows = transcript_activity_gap_h + activity_h + split_reserved_h + hints_h + status_h;
     let max_input_height = frame_area.height.saturating_sub(fixed_rows).max(3);
     let input_height = app
         .input_height(composer_width, prompt_width)
@@ -70,15 +76,27 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
     let prompt_style = theme.prompt_style();
     let input_lines = build_input_lines(app, prompt_prefix, prompt_style, theme, composer_width);
 
-    // Composer-only viewport: transcript is appended above via insert_before.
+    // Inline mode draws a composer-only viewport: transcript is appended above via
+    // insert_before. Full-screen mode owns the whole alternate screen, so the
+    // transcript gets its own scrollable pane on top.
     // Keep one blank separator row between transcript and activity/spinner panel.
     let mut constraints = Vec::new();
+    if app.fullscreen {
+        constraints.push(Constraint::Min(1));
+    }
     if transcript_activity_gap_h > 0 {
         constraints.push(Constraint::Length(transcript_activity_gap_h));
     }
     if activity_h > 0 {
         constraints.push(Constraint::Length(activity_h));
     }
+    if split {
+        constraints.push(if app.fullscreen {
+            Constraint::Percentage(60)
+        } else {
+            Constraint::Min(SPLIT_MIN_ROWS)
+        });
+    }
     constraints.push(Constraint::Length(input_height));
     if hints_h > 0 {
         constraints.push(Constraint::Length(hints_h));
@@ -91,6 +109,12 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
         .split(frame_area);
 
     let mut section_idx = 0usize;
+    let transcript_chunk = if app.fullscreen {
+        section_idx += 1;
+        Some(chunks[0])
+    } else {
+        None
+    };
     if transcript_activity_gap_h > 0 {
         section_idx += 1;
     }
@@ -101,6 +125,13 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
     } else {
         None
     };
+    let split_chunk = if split {
+        let c = chunks[section_idx];
+        section_idx += 1;
+        Some(c)
+    } else {
+        None
+    };
     let input_chunk = chunks[section_idx];
     section_idx += 1;
     let hint_chunk = if hints_h > 0 {
@@ -112,6 +143,10 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
     };
     let status_chunk = chunks[section_idx];
 
+    if let Some(area) = transcript_chunk {
+        draw_transcript_pane(f, app, area);
+    }
+
     // Real-time activity area between transcript and composer.
     if let Some(area) = activity_chunk {
         let activity_content_width = area.width.saturating_sub(ACTIVITY_HORIZONTAL_INSET).max(1);
@@ -123,6 +158,10 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
         f.render_widget(activity_panel, area);
     }
 
+    if let Some(area) = split_chunk {
+        draw_split_columns(f, app, theme, area);
+    }
+
     // Input area
     let visible_rows = input_height.saturating_sub(INPUT_VERTICAL_INSET).max(1);
     let input_scroll = app.input_scroll_offset(composer_width, prompt_width, visible_rows);
@@ -173,13 +212,35 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
 
     // Status bar
     let cancel_hint = if app.running { " | Esc cancel" } else { "" };
-    let status = Paragraph::new(format!(
-        " {} | {}{} | Ctrl+R history | Ctrl+C exit",
-        app.primary_provider.as_str(),
-        providers_label(&app.available_providers),
-        cancel_hint,
-    ))
-    .style(theme.status_style());
+    let scroll_hint = if split {
+        " | Alt+\u{2190}/\u{2192} column | PgUp/PgDn scroll | Ctrl+T stack"
+    } else if app.fullscreen {
+        " | PgUp/PgDn scroll"
+    } else {
+        ""
+    };
+    let status_text = if matches!(app.mode, Mode::TranscriptSearch) {
+        let keys = if app.find.editing {
+            "Enter done | \u{2191}/\u{2193} hits | Esc close"
+        } else {
+            "n/N next/prev | / edit | Esc close"
+        };
+        let cursor = if app.find.editing { "\u{258f}" } else { "" };
+        format!(
+            " find: {}{cursor} | {} | {keys}",
+            app.find.query,
+            app.find.position()
+        )
+    } else {
+        format!(
+            " {} | {}{}{} | Ctrl+R history | Ctrl+F find | Ctrl+C exit",
+            app.primary_provider.as_str(),
+            providers_label(&app.available_providers),
+            cancel_hint,
+            scroll_hint,
+        )
+    };
+    let status = Paragraph::new(status_text).style(theme.status_style());
     f.render_widget(status, status_chunk);
 
     if matches!(app.mode, Mode::HistorySearch) {
@@ -188,6 +249,97 @@ pub(super) fn draw(f: &mut Frame, app: &App) {
     if matches!(app.mode, Mode::Approval) {
         draw_approval(f, app, theme);
     }
+    if matches!(app.mode, Mode::ContextPreview) {
+        draw_context_preview(f, app, theme);
+    }
+    if matches!(app.mode, Mode::TranscriptSearch) && !app.fullscreen {
+        draw_find(f, app, theme);
+    }
+    if app.no_color {
+        strip_colors(f.buffer_mut(), theme);
+    }
+}
+
+/// `NO_COLOR`: drop every colour and keep only modifiers. Selection
+/// highlights become reverse video so they stay visible.
+pub(super) fn strip_colors(buf: &mut Buffer, theme: ThemePalette) {
+    for cell in &mut buf.content {
+        if cell.bg == theme.highlight_bg {
+            cell.modifier |= Modifier::REVERSED;
+        }
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
+
+/// Full-screen transcript pane. Follows the tail while `autoscroll` is set,
+/// otherwise holds the offset the user scrolled to.
+fn draw_transcript_pane(f: &mut Frame, app: &App, area: Rect) {
+    app.transcript_area.set(area);
+    let offset = transcript_offset(app, app.cached_log_rows(), area.height);
+    let (lines, skip) = app.cached_log_window(usize::from(offset), usize::from(area.height));
+    let transcript = Paragraph::new(Text::from(lines.to_vec())).wrap(Wrap { trim: false });
+    f.render_widget(transcript.scroll((skip, 0)), area);
+}
+
+/// First visible row of a transcript of `line_count` wrapped rows shown in
+/// a pane `height` rows tall.
+pub(super) fn transcript_offset(app: &App, line_count: usize, height: u16) -> u16 {
+    let max_scroll = (line_count as u16).saturating_sub(height);
+    if app.autoscroll {
+        max_scroll
+    } else {
+        app.scroll.min(max_scroll)
+    }
+}
+
+/// One bordered column per agent of a multi-agent run. Each column follows
+/// its own tail unless the user scrolled it back.
+fn draw_split_columns(f: &mut Frame, app: &App, theme: ThemePalette, area: Rect) {
+    let providers = app.split_columns();
+    if providers.is_empty() {
+        return;
+    }
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Ratio(1, providers.len() as u32);
+            providers.len()
+        ])
+        .split(area);
+    let focus = app.split_focus.min(providers.len() - 1);
+
+    for (i, (&provider, &column)) in providers.iter().zip(columns.iter()).enumerate() {
+        let elapsed_secs = app.split_column_elapsed(provider);
+        let chars = app.agent_chars.get(&provider).copied().unwrap_or(0);
+        let title = format!(
+            " {} \u{00b7} {:02}:{:02} \u{00b7} {} ",
+            provider.as_str(),
+            elapsed_secs / 60,
+            elapsed_secs % 60,
+            format_chars(chars),
+        );
+        let label_style = Style::default()
+            .fg(spinner_base_color(provider, theme))
+            .add_modifier(Modifier::BOLD);
+        let border_style = if i == focus {
+            label_style
+        } else {
+            theme.panel_border_style()
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(border_style)
+            .title(Span::styled(title, label_style));
+        let inner = block.inner(column);
+
+        let body =
+            Paragraph::new(Text::from(app.split_column_lines(provider))).wrap(Wrap { trim: false });
+        let max_scroll = (body.line_count(inner.width) as u16).saturating_sub(inner.height);
+        let offset = max_scroll.saturating_sub(app.split_scroll_back(provider));
+        f.render_widget(body.block(block).scroll((offset, 0)), column);
+    }
 }
 
 pub(super) fn draw_exit(f: &mut Frame, app: &App) {
@@ -587,6 +739,29 @@ fn draw_history(f: &mut Frame, app: &App, theme: ThemePalette) {
     f.render_widget(panel, area);
 }
 
+/// Inline mode's transcript lives in terminal scrollback, so hits are
+/// shown here with a few lines of context instead.
+fn draw_find(f: &mut Frame, app: &App, theme: ThemePalette) {
+    let area = centered_rect(100, 70, f.area());
+    let rows = area.height.saturating_sub(2) as usize;
+    let lines = if app.find.query.is_empty() {
+        vec![Line::from(Span::styled(
+            "type to search the transcript",
+            theme.muted_style(),
+        ))]
+    } else if app.find.hits.is_empty() {
+        vec![Line::from(Span::styled("(no match)", theme.muted_style()))]
+    } else {
+        app.find_preview_lines(rows / 3, rows)
+    };
+    let panel = Paragraph::new(lines)
+        .style(theme.panel_surface_style())
+        .block(modal_block(theme, "find"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(panel, area);
+}
+
 fn draw_approval(f: &mut Frame, app: &App, theme: ThemePalette) {
     let area = centered_rect(64, 40, f.area());
     let pending = app.approval.as_ref();
@@ -627,6 +802,36 @@ fn draw_approval(f: &mut Frame, app: &App, theme: ThemePalette) {
     f.render_widget(panel, area);
 }
 
+fn draw_context_preview(f: &mut Frame, app: &App, theme: ThemePalette) {
+    let area = centered_rect(92, 92, f.area());
+    let Some(preview) = app.context_preview.as_ref() else {
+        return;
+    };
+    let lines = preview
+        .lines
+        .iter()
+        .map(|line| match line {
+            PreviewLine::Heading(text) => Line::from(Span::styled(
+                text.clone(),
+                theme.title_style().add_modifier(Modifier::BOLD),
+            )),
+            PreviewLine::Meta(text) => Line::from(Span::styled(text.clone(), theme.muted_style())),
+            PreviewLine::Body(text) => Line::from(Span::styled(text.clone(), theme.body_style())),
+        })
+        .collect::<Vec<_>>();
+
+    let panel = Paragraph::new(lines)
+        .style(theme.panel_surface_style())
+        .block(modal_block(
+            theme,
+            "context preview · ↑↓ PgUp/PgDn scroll · Esc close",
+        ))
+        .wrap(Wrap { trim: false })
+        .scroll((preview.scroll, 0));
+    f.render_widget(Clear, area);
+    f.render_widget(panel, area);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let vertical = Layout::default()
         .direction(Direction::Vertical)